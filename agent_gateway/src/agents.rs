@@ -0,0 +1,462 @@
+// Agent Registry - agents, their roles and their API keys
+//
+// Agents log in at /auth/token with an API key. Keys are random strings shown
+// once, when they are issued; the registry keeps only their SHA-256 hashes,
+// each with an optional expiry. Everything is persisted to AGENTS_PATH, so
+// agents onboarded through the admin API survive restarts and redeploys. On
+// first start (no file yet) the registry is seeded with green_agent and
+// purple_agent and the keys in GREEN_AGENT_API_KEY / PURPLE_AGENT_API_KEY;
+// from then on the file is authoritative. Gateway replicas sharing the file
+// pick up each other's changes when it is modified. Every change holds an
+// exclusive lock on a file beside the registry and is applied to the file as
+// re-read under that lock, so admin calls on two replicas never overwrite
+// each other.
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chrono::{DateTime, Duration, Utc};
+use log::info;
+use ring::rand::{SecureRandom, SystemRandom};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::AgentRole;
+
+/// Longest accepted agent_id
+pub const MAX_AGENT_ID_LEN: usize = 64;
+
+/// Longest lifetime an API key may be issued with
+pub const MAX_KEY_TTL_SECS: i64 = 366 * 24 * 3600;
+
+/// Most unexpired, unrevoked keys one agent may hold at a time
+pub const MAX_ACTIVE_KEYS: usize = 5;
+
+/// Revoked and expired keys are listed for this long, then dropped
+const RETIRED_KEY_RETENTION_DAYS: i64 = 30;
+
+/// key_id of the keys seeded from the environment on first start
+const BOOTSTRAP_KEY_ID: &str = "bootstrap";
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct Agent {
+    agent_id: String,
+    role: AgentRole,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
+    created_at: DateTime<Utc>,
+    keys: Vec<ApiKey>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+struct ApiKey {
+    key_id: String,
+    hash: String, // SHA-256 of the key, hex
+    created_at: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    expires_at: Option<DateTime<Utc>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    revoked_at: Option<DateTime<Utc>>,
+}
+
+impl ApiKey {
+    fn is_active(&self, now: DateTime<Utc>) -> bool {
+        self.revoked_at.is_none() && self.expires_at.is_none_or(|at| at > now)
+    }
+
+    /// When the key stopped working, if it has
+    fn retired_at(&self, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        self.revoked_at.or(self.expires_at.filter(|at| *at <= now))
+    }
+
+    fn status(&self, now: DateTime<Utc>) -> &'static str {
+        if self.revoked_at.is_some() {
+            "revoked"
+        } else if self.is_active(now) {
+            "active"
+        } else {
+            "expired"
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct AgentsFile {
+    agents: Vec<Agent>,
+}
+
+/// An agent as the admin API shows it; key hashes never leave the registry
+#[derive(Debug, Serialize, Clone)]
+pub struct AgentSummary {
+    pub agent_id: String,
+    pub role: AgentRole,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub keys: Vec<KeySummary>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct KeySummary {
+    pub key_id: String,
+    pub status: &'static str, // active, expired or revoked
+    pub created_at: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub revoked_at: Option<DateTime<Utc>>,
+}
+
+/// A newly issued key; the only time its plaintext is available
+#[derive(Debug, Serialize, Clone)]
+pub struct IssuedKey {
+    pub agent_id: String,
+    pub key_id: String,
+    pub api_key: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+#[derive(Debug)]
+pub enum AgentError {
+    NotFound(String),
+    Exists(String),
+    Invalid(String),
+    Storage(String),
+}
+
+impl fmt::Display for AgentError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AgentError::NotFound(what) => write!(f, "{} not found", what),
+            AgentError::Exists(agent_id) => write!(f, "Agent '{}' is already registered", agent_id),
+            AgentError::Invalid(e) => f.write_str(e),
+            AgentError::Storage(e) => write!(f, "Agent registry could not be saved: {}", e),
+        }
+    }
+}
+
+pub struct AgentStore {
+    path: PathBuf,
+    agents: BTreeMap<String, Agent>,
+    by_hash: HashMap<String, String>, // key hash -> agent_id
+}
+
+impl AgentStore {
+    /// Load the registry from `path`, creating it from `seed` (agent_id, role,
+    /// API key) if the file does not exist yet
+    pub fn open(path: impl AsRef<Path>, seed: Vec<(String, AgentRole, String)>) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)?;
+        }
+
+        // Replicas starting together must not both seed the file
+        let _lock = lock(&path)?;
+        let agents = if path.exists() {
+            load(&path)?
+        } else {
+            let now = Utc::now();
+            let agents: BTreeMap<String, Agent> = seed.into_iter()
+                .map(|(agent_id, role, api_key)| (agent_id.clone(), Agent {
+                    agent_id,
+                    role,
+                    description: None,
+                    created_at: now,
+                    keys: vec![ApiKey {
+                        key_id: BOOTSTRAP_KEY_ID.to_string(),
+                        hash: hash(&api_key),
+                        created_at: now,
+                        expires_at: None,
+                        revoked_at: None,
+                    }],
+                }))
+                .collect();
+            save(&path, &agents)?;
+            info!("🪪 Agent registry {} created with {:?}", path.display(), agents.keys().collect::<Vec<_>>());
+            agents
+        };
+
+        info!("🪪 Agent registry {} loaded, {} agents", path.display(), agents.len());
+        let mut store = AgentStore { path, agents, by_hash: HashMap::new() };
+        store.index();
+        Ok(store)
+    }
+
+    /// Re-read the registry file, e.g. after another replica changed it
+    pub fn reload(&mut self) -> io::Result<()> {
+        self.agents = load(&self.path)?;
+        self.index();
+        Ok(())
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The agent and role an API key belongs to, if the key is active
+    pub fn authenticate(&self, api_key: &str, now: DateTime<Utc>) -> Option<(String, AgentRole)> {
+        let hash = hash(api_key);
+        let agent = self.agents.get(self.by_hash.get(&hash)?)?;
+        agent.keys.iter()
+            .any(|k| k.hash == hash && k.is_active(now))
+            .then(|| (agent.agent_id.clone(), agent.role.clone()))
+    }
+
+    /// Whether the agent still has `role` and an active key to log in with
+    pub fn is_enrolled(&self, agent_id: &str, role: &AgentRole, now: DateTime<Utc>) -> bool {
+        self.agents.get(agent_id)
+            .is_some_and(|a| a.role == *role && a.keys.iter().any(|k| k.is_active(now)))
+    }
+
+    pub fn list(&self, now: DateTime<Utc>) -> Vec<AgentSummary> {
+        self.agents.values().map(|a| summary(a, now)).collect()
+    }
+
+    pub fn get(&self, agent_id: &str, now: DateTime<Utc>) -> Option<AgentSummary> {
+        self.agents.get(agent_id).map(|a| summary(a, now))
+    }
+
+    /// Register a new agent with its first key
+    pub fn register(
+        &mut self,
+        agent_id: &str,
+        role: AgentRole,
+        description: Option<String>,
+        key_ttl_secs: Option<i64>,
+        now: DateTime<Utc>,
+    ) -> Result<IssuedKey, AgentError> {
+        validate_agent_id(agent_id)?;
+        let expires_at = key_expiry(key_ttl_secs, now)?;
+        let (key, api_key) = new_key(now, expires_at)?;
+        let issued = issued(agent_id, &key, api_key);
+        self.update(|agents| {
+            if agents.contains_key(agent_id) {
+                return Err(AgentError::Exists(agent_id.to_string()));
+            }
+            agents.insert(agent_id.to_string(), Agent {
+                agent_id: agent_id.to_string(),
+                role,
+                description,
+                created_at: now,
+                keys: vec![key],
+            });
+            Ok(())
+        })?;
+        Ok(issued)
+    }
+
+    /// Assign a new role; returns the previous one
+    pub fn set_role(&mut self, agent_id: &str, role: AgentRole) -> Result<AgentRole, AgentError> {
+        self.update(|agents| {
+            let agent = find(agents, agent_id)?;
+            Ok(std::mem::replace(&mut agent.role, role))
+        })
+    }
+
+    /// Issue another key, keeping the agent's current ones
+    pub fn issue_key(&mut self, agent_id: &str, key_ttl_secs: Option<i64>, now: DateTime<Utc>) -> Result<IssuedKey, AgentError> {
+        self.rotate_keys(agent_id, key_ttl_secs, None, now)
+    }
+
+    /// Issue a new key; with `grace_secs`, the agent's other active keys expire
+    /// that many seconds from now (0 revokes them at once)
+    pub fn rotate_keys(
+        &mut self,
+        agent_id: &str,
+        key_ttl_secs: Option<i64>,
+        grace_secs: Option<i64>,
+        now: DateTime<Utc>,
+    ) -> Result<IssuedKey, AgentError> {
+        let expires_at = key_expiry(key_ttl_secs, now)?;
+        if grace_secs.is_some_and(|g| !(0..=MAX_KEY_TTL_SECS).contains(&g)) {
+            return Err(AgentError::Invalid(format!("grace_secs must be between 0 and {}", MAX_KEY_TTL_SECS)));
+        }
+        let (key, api_key) = new_key(now, expires_at)?;
+        let issued = issued(agent_id, &key, api_key);
+
+        self.update(|agents| {
+            let agent = find(agents, agent_id)?;
+            if let Some(grace) = grace_secs {
+                for old in agent.keys.iter_mut().filter(|k| k.is_active(now)) {
+                    if grace == 0 {
+                        old.revoked_at = Some(now);
+                    } else {
+                        let until = now + Duration::seconds(grace);
+                        old.expires_at = Some(old.expires_at.map_or(until, |at| at.min(until)));
+                    }
+                }
+            }
+            if agent.keys.iter().filter(|k| k.is_active(now)).count() >= MAX_ACTIVE_KEYS {
+                return Err(AgentError::Invalid(format!(
+                    "Agent '{}' already has {} active keys; revoke one first", agent_id, MAX_ACTIVE_KEYS)));
+            }
+            let retention = Duration::days(RETIRED_KEY_RETENTION_DAYS);
+            agent.keys.retain(|k| k.retired_at(now).is_none_or(|at| at > now - retention));
+            agent.keys.push(key);
+            Ok(())
+        })?;
+        Ok(issued)
+    }
+
+    pub fn revoke_key(&mut self, agent_id: &str, key_id: &str, now: DateTime<Utc>) -> Result<(), AgentError> {
+        self.update(|agents| {
+            let agent = find(agents, agent_id)?;
+            let key = agent.keys.iter_mut()
+                .find(|k| k.key_id == key_id)
+                .ok_or_else(|| AgentError::NotFound(format!("Key '{}' of agent '{}'", key_id, agent_id)))?;
+            key.revoked_at.get_or_insert(now);
+            Ok(())
+        })
+    }
+
+    pub fn remove(&mut self, agent_id: &str) -> Result<(), AgentError> {
+        self.update(|agents| {
+            agents.remove(agent_id)
+                .map(|_| ())
+                .ok_or_else(|| AgentError::NotFound(format!("Agent '{}'", agent_id)))
+        })
+    }
+
+    /// Under the registry lock, re-read the file, apply a change to a copy of
+    /// it, save it, and only then adopt it
+    fn update<T>(&mut self, change: impl FnOnce(&mut BTreeMap<String, Agent>) -> Result<T, AgentError>) -> Result<T, AgentError> {
+        let storage = |e: io::Error| AgentError::Storage(e.to_string());
+        let _lock = lock(&self.path).map_err(storage)?;
+        self.reload().map_err(storage)?;
+        let mut agents = self.agents.clone();
+        let result = change(&mut agents)?;
+        save(&self.path, &agents).map_err(storage)?;
+        self.agents = agents;
+        self.index();
+        Ok(result)
+    }
+
+    fn index(&mut self) {
+        self.by_hash = self.agents.values()
+            .flat_map(|a| a.keys.iter().map(|k| (k.hash.clone(), a.agent_id.clone())))
+            .collect();
+    }
+}
+
+/// Last-modified time of the registry file, for change detection
+pub fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+fn load(path: &Path) -> io::Result<BTreeMap<String, Agent>> {
+    let file: AgentsFile = serde_json::from_slice(&fs::read(path)?)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path.display(), e)))?;
+    Ok(file.agents.into_iter().map(|a| (a.agent_id.clone(), a)).collect())
+}
+
+fn find<'a>(agents: &'a mut BTreeMap<String, Agent>, agent_id: &str) -> Result<&'a mut Agent, AgentError> {
+    agents.get_mut(agent_id).ok_or_else(|| AgentError::NotFound(format!("Agent '{}'", agent_id)))
+}
+
+fn summary(agent: &Agent, now: DateTime<Utc>) -> AgentSummary {
+    AgentSummary {
+        agent_id: agent.agent_id.clone(),
+        role: agent.role.clone(),
+        description: agent.description.clone(),
+        created_at: agent.created_at,
+        keys: agent.keys.iter()
+            .map(|k| KeySummary {
+                key_id: k.key_id.clone(),
+                status: k.status(now),
+                created_at: k.created_at,
+                expires_at: k.expires_at,
+                revoked_at: k.revoked_at,
+            })
+            .collect(),
+    }
+}
+
+fn issued(agent_id: &str, key: &ApiKey, api_key: String) -> IssuedKey {
+    IssuedKey {
+        agent_id: agent_id.to_string(),
+        key_id: key.key_id.clone(),
+        api_key,
+        expires_at: key.expires_at,
+    }
+}
+
+fn validate_agent_id(agent_id: &str) -> Result<(), AgentError> {
+    let valid = !agent_id.is_empty()
+        && agent_id.len() <= MAX_AGENT_ID_LEN
+        && agent_id.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+    if valid {
+        Ok(())
+    } else {
+        Err(AgentError::Invalid(format!(
+            "agent_id must be 1-{} characters of a-z, 0-9 or '_'", MAX_AGENT_ID_LEN)))
+    }
+}
+
+fn key_expiry(ttl_secs: Option<i64>, now: DateTime<Utc>) -> Result<Option<DateTime<Utc>>, AgentError> {
+    match ttl_secs {
+        None => Ok(None),
+        Some(ttl) if (1..=MAX_KEY_TTL_SECS).contains(&ttl) => Ok(Some(now + Duration::seconds(ttl))),
+        Some(_) => Err(AgentError::Invalid(format!("ttl_secs must be between 1 and {}", MAX_KEY_TTL_SECS))),
+    }
+}
+
+/// A fresh key record and the key itself
+fn new_key(now: DateTime<Utc>, expires_at: Option<DateTime<Utc>>) -> Result<(ApiKey, String), AgentError> {
+    let mut bytes = [0u8; 32];
+    SystemRandom::new()
+        .fill(&mut bytes)
+        .map_err(|_| AgentError::Storage("No randomness available for API key".to_string()))?;
+    let api_key = URL_SAFE_NO_PAD.encode(bytes);
+    let key = ApiKey {
+        key_id: format!("key-{}", &hash(&api_key)[..12]),
+        hash: hash(&api_key),
+        created_at: now,
+        expires_at,
+        revoked_at: None,
+    };
+    Ok((key, api_key))
+}
+
+/// Hold an exclusive lock on a shared JSON file until the returned file is
+/// dropped. The lock file sits beside it, since `replace_file` renames over it.
+pub(crate) fn lock(path: &Path) -> io::Result<File> {
+    use std::os::unix::fs::OpenOptionsExt;
+    let file = fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .mode(0o600)
+        .open(path.with_extension("json.lock"))?;
+    file.lock()?;
+    Ok(file)
+}
+
+fn save(path: &Path, agents: &BTreeMap<String, Agent>) -> io::Result<()> {
+    let file = AgentsFile { agents: agents.values().cloned().collect() };
+    let json = serde_json::to_vec_pretty(&file).map_err(io::Error::other)?;
+    replace_file(path, &json)
+}
+
+/// Write `json` to a temporary file and rename it over `path`, so a crash
+/// never leaves a half-written file behind
+pub(crate) fn replace_file(path: &Path, json: &[u8]) -> io::Result<()> {
+    let tmp = path.with_extension("json.tmp");
+    {
+        use std::io::Write;
+        use std::os::unix::fs::OpenOptionsExt;
+        let mut out = fs::OpenOptions::new().write(true).create(true).truncate(true).mode(0o600).open(&tmp)?;
+        out.write_all(json)?;
+        out.sync_all()?;
+    }
+    fs::rename(&tmp, path)
+}
+
+fn hash(api_key: &str) -> String {
+    hex::encode(Sha256::digest(api_key.as_bytes()))
+}
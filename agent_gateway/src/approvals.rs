@@ -0,0 +1,299 @@
+// Approvals - human sign-off for sensitive MCP calls
+//
+// Operations with an `approval` policy are authorized, checked and audited
+// like any other call, then parked here instead of being forwarded. One of
+// the approvers named in the policy approves or rejects the call at
+// /approvals/{id}; an approved call is then made on behalf of the agent that
+// requested it, and its result is kept with the approval. Calls nobody
+// decides on expire. Approvals are kept in APPROVALS_PATH, which gateway
+// replicas share like the agent registry: every change is made under its lock
+// to the file as re-read, so any replica can decide on a call another one
+// parked, and only one of them can approve it or expire it.
+
+use chrono::{DateTime, Duration, Utc};
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::agents;
+use crate::Claims;
+
+/// Most calls one agent may have waiting for approval at a time
+pub const MAX_PENDING_PER_AGENT: usize = 20;
+
+/// Longest comment an approver may leave
+const MAX_APPROVAL_COMMENT_LEN: usize = 1000;
+
+/// Decided approvals stay queryable for this long, then they are dropped
+const DECIDED_RETENTION_HOURS: i64 = 24;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum ApprovalStatus {
+    Pending,   // Waiting for an approver
+    Approved,  // Approved; the call is being made
+    Rejected,
+    Expired,   // Nobody decided in time
+    Completed, // Approved, and the MCP server answered
+    Failed,    // Approved, but the call could not be made or failed
+}
+
+impl ApprovalStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ApprovalStatus::Pending => "pending",
+            ApprovalStatus::Approved => "approved",
+            ApprovalStatus::Rejected => "rejected",
+            ApprovalStatus::Expired => "expired",
+            ApprovalStatus::Completed => "completed",
+            ApprovalStatus::Failed => "failed",
+        }
+    }
+}
+
+/// How a parked call reached the gateway; an approved call is made the same way
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum Transport {
+    #[default]
+    Rest,    // POST /mcp/invoke
+    JsonRpc, // tools/call on POST /mcp/{target_mcp}
+}
+
+/// A parked call and what became of it
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct Approval {
+    pub approval_id: String,
+    pub request_id: String,       // Of the original call; shared by its audit entries
+    pub agent_id: String,         // Who made the call
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub task_id: Option<String>,
+    pub target_mcp: String,
+    pub operation: String,
+    pub payload: serde_json::Value,
+    #[serde(default)]
+    pub transport: Transport,
+    pub approvers: Vec<String>,
+    pub status: ApprovalStatus,
+    pub requested_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub decided_by: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub decided_at: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<serde_json::Value>, // Set once an approved call completes
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,             // Set when an approved call fails
+    #[serde(skip_serializing)]
+    pub claims: Claims,                    // The caller's token, which the call is made with
+}
+
+/// An approval as stored, with the claims the API never shows
+#[derive(Serialize)]
+struct StoredApproval<'a> {
+    #[serde(flatten)]
+    approval: &'a Approval,
+    claims: &'a Claims,
+}
+
+#[derive(Deserialize, Default)]
+struct ApprovalsFile {
+    approvals: Vec<Approval>,
+}
+
+#[derive(Debug)]
+pub enum ApprovalError {
+    NotFound,
+    Invalid(String),
+    NotApprover,
+    OwnCall,
+    Decided(ApprovalStatus),
+    Expired,
+    Storage(String),
+}
+
+impl fmt::Display for ApprovalError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ApprovalError::NotFound => write!(f, "Approval not found"),
+            ApprovalError::Invalid(msg) => write!(f, "{}", msg),
+            ApprovalError::NotApprover => write!(f, "Caller is not an approver for this call"),
+            ApprovalError::OwnCall => write!(f, "Agents may not approve their own calls"),
+            ApprovalError::Decided(status) => write!(f, "Approval is already {}", status.as_str()),
+            ApprovalError::Expired => write!(f, "Approval has expired"),
+            ApprovalError::Storage(e) => write!(f, "Approvals could not be read or saved: {}", e),
+        }
+    }
+}
+
+pub struct ApprovalStore {
+    path: PathBuf,
+}
+
+impl Approval {
+    /// Whether `agent_id` may see this approval: the caller and its approvers can
+    pub fn involves(&self, agent_id: &str) -> bool {
+        self.agent_id == agent_id || self.approvers.iter().any(|a| a == agent_id)
+    }
+}
+
+impl ApprovalStore {
+    /// Use the approvals kept at `path`, which need not exist yet
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)?;
+        }
+        let pending = load(&path)?.values().filter(|a| a.status == ApprovalStatus::Pending).count();
+        info!("🖊️ Approvals {} opened, {} pending", path.display(), pending);
+        Ok(ApprovalStore { path })
+    }
+
+    pub fn insert(&self, approval: Approval) -> Result<(), ApprovalError> {
+        self.update(|approvals| {
+            approvals.insert(approval.approval_id.clone(), approval);
+            Ok(())
+        })
+    }
+
+    pub fn get(&self, approval_id: &str) -> Result<Option<Approval>, ApprovalError> {
+        Ok(self.read()?.remove(approval_id))
+    }
+
+    /// Calls `agent_id` has waiting for approval
+    pub fn pending_for(&self, agent_id: &str) -> Result<usize, ApprovalError> {
+        Ok(self.read()?.values()
+            .filter(|a| a.status == ApprovalStatus::Pending && a.agent_id == agent_id)
+            .count())
+    }
+
+    /// Approvals `visible` allows, optionally only those with `status`, oldest first
+    pub fn list(
+        &self,
+        status: Option<ApprovalStatus>,
+        visible: impl Fn(&Approval) -> bool,
+    ) -> Result<Vec<Approval>, ApprovalError> {
+        let mut approvals: Vec<Approval> = self.read()?.into_values()
+            .filter(|a| status.is_none_or(|s| a.status == s) && visible(a))
+            .collect();
+        approvals.sort_by_key(|a| a.requested_at);
+        Ok(approvals)
+    }
+
+    /// Record `approver`'s decision on a pending call. Approving moves it to
+    /// `Approved`, so a second approval cannot make the call twice.
+    pub fn decide(
+        &self,
+        approval_id: &str,
+        approver: &str,
+        approve: bool,
+        comment: Option<String>,
+        now: DateTime<Utc>,
+    ) -> Result<Approval, ApprovalError> {
+        if comment.as_ref().is_some_and(|c| c.len() > MAX_APPROVAL_COMMENT_LEN) {
+            return Err(ApprovalError::Invalid(format!("comment must be at most {} bytes", MAX_APPROVAL_COMMENT_LEN)));
+        }
+        self.update(|approvals| {
+            let approval = approvals.get_mut(approval_id).ok_or(ApprovalError::NotFound)?;
+            if approval.agent_id == approver {
+                return Err(ApprovalError::OwnCall);
+            }
+            if !approval.approvers.iter().any(|a| a == approver) {
+                return Err(ApprovalError::NotApprover);
+            }
+            if approval.status != ApprovalStatus::Pending {
+                return Err(ApprovalError::Decided(approval.status));
+            }
+            if approval.expires_at <= now {
+                return Err(ApprovalError::Expired);
+            }
+
+            approval.status = if approve { ApprovalStatus::Approved } else { ApprovalStatus::Rejected };
+            approval.decided_by = Some(approver.to_string());
+            approval.decided_at = Some(now);
+            approval.comment = comment;
+            Ok(approval.clone())
+        })
+    }
+
+    /// Record the outcome of an approved call
+    pub fn finish(&self, approval_id: &str, outcome: Result<serde_json::Value, String>) -> Result<Approval, ApprovalError> {
+        self.update(|approvals| {
+            let approval = approvals.get_mut(approval_id).ok_or(ApprovalError::NotFound)?;
+            match outcome {
+                Ok(result) => {
+                    approval.status = ApprovalStatus::Completed;
+                    approval.result = Some(result);
+                }
+                Err(error) => {
+                    approval.status = ApprovalStatus::Failed;
+                    approval.error = Some(error);
+                }
+            }
+            Ok(approval.clone())
+        })
+    }
+
+    /// Expire pending calls past their deadline and return them, so they can
+    /// be audited; drop decided ones past their retention
+    pub fn prune(&self, now: DateTime<Utc>) -> Result<Vec<Approval>, ApprovalError> {
+        self.update(|approvals| {
+            let retention = Duration::hours(DECIDED_RETENTION_HOURS);
+            approvals.retain(|_, a| {
+                a.status == ApprovalStatus::Pending || a.decided_at.is_none_or(|at| at + retention > now)
+            });
+
+            let mut expired = Vec::new();
+            for approval in approvals.values_mut() {
+                if approval.status == ApprovalStatus::Pending && approval.expires_at <= now {
+                    approval.status = ApprovalStatus::Expired;
+                    approval.decided_at = Some(now);
+                    expired.push(approval.clone());
+                }
+            }
+            Ok(expired)
+        })
+    }
+
+    /// The approvals as last saved; saves replace the file whole, so no lock is needed
+    fn read(&self) -> Result<HashMap<String, Approval>, ApprovalError> {
+        load(&self.path).map_err(storage)
+    }
+
+    /// Under the lock, re-read the approvals, change them, and save them
+    /// unless the change fails
+    fn update<T>(&self, change: impl FnOnce(&mut HashMap<String, Approval>) -> Result<T, ApprovalError>) -> Result<T, ApprovalError> {
+        let _lock = agents::lock(&self.path).map_err(storage)?;
+        let mut approvals = load(&self.path).map_err(storage)?;
+        let result = change(&mut approvals)?;
+        let file = serde_json::json!({
+            "approvals": approvals.values()
+                .map(|approval| StoredApproval { approval, claims: &approval.claims })
+                .collect::<Vec<_>>(),
+        });
+        let json = serde_json::to_vec(&file).map_err(|e| storage(io::Error::other(e)))?;
+        agents::replace_file(&self.path, &json).map_err(storage)?;
+        Ok(result)
+    }
+}
+
+fn load(path: &Path) -> io::Result<HashMap<String, Approval>> {
+    let file: ApprovalsFile = match fs::read(path) {
+        Ok(bytes) => serde_json::from_slice(&bytes)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path.display(), e)))?,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => ApprovalsFile::default(),
+        Err(e) => return Err(e),
+    };
+    Ok(file.approvals.into_iter().map(|a| (a.approval_id.clone(), a)).collect())
+}
+
+fn storage(e: io::Error) -> ApprovalError {
+    ApprovalError::Storage(e.to_string())
+}
@@ -0,0 +1,645 @@
+// Audit Store - append-only, hash-chained audit trail on disk
+//
+// Every AuditEntry is written as one JSON line. Each entry records the hash of
+// the entry before it, and its own hash covers all of its fields (including
+// that back-link), so editing, reordering or removing an entry in the middle
+// of the file breaks the chain at that point. Gateway replicas share one log
+// on a shared volume: each append locks the file, first reads any entries
+// other replicas appended since, and links onto the true end of the chain. A
+// line cut short by a crash mid-append is dropped by the next writer, so the
+// next entry starts on a line of its own. Appends (with their fsync) and
+// verification run on the blocking thread pool, off the request workers. A sparse index of where every
+// INDEX_STRIDE-th entry starts lets a cursor query seek close to its place
+// instead of reading the log from the first line.
+
+use actix_web::web::{self, Bytes};
+use chrono::{DateTime, FixedOffset};
+use futures_util::{stream, Stream, StreamExt};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, BufRead, BufReader, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncBufReadExt, AsyncSeekExt, Lines};
+use log::{info, warn};
+
+use crate::AuditEntry;
+
+/// prev_hash of the first entry in a fresh log
+pub const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// Default and maximum page size for JSON queries
+pub const DEFAULT_PAGE_SIZE: usize = 100;
+pub const MAX_PAGE_SIZE: usize = 1000;
+
+/// Entries between two points of the seq index
+const INDEX_STRIDE: u64 = 1024;
+
+const CSV_HEADER: &str = "seq,timestamp,agent_id,task_id,target_mcp,operation,authorized,request_id,trace_id,denial_reason,approval_id,approval_status,approver,subject,prev_hash,hash\n";
+
+/// Where the chain currently ends; guarded so appends are serialized
+struct ChainHead {
+    path: PathBuf,
+    file: File,
+    seq: u64,
+    last_hash: String,
+    offset: u64,            // End of the file, where the next entry starts
+    index: Vec<(u64, u64)>, // (seq, byte offset) of every INDEX_STRIDE-th entry
+}
+
+impl ChainHead {
+    /// Note where entry `seq` starts if it is due a point in the index
+    fn index(&mut self, seq: u64, offset: u64) {
+        if self.index.last().is_none_or(|&(last, _)| seq >= last + INDEX_STRIDE) {
+            self.index.push((seq, offset));
+        }
+    }
+
+    /// Read the entries past `offset`: all of them on open, afterwards those
+    /// other replicas appended. Call with the file locked.
+    fn catch_up(&mut self) -> io::Result<()> {
+        let len = self.file.metadata()?.len();
+        if len == self.offset {
+            return Ok(());
+        }
+        if len < self.offset {
+            warn!("Audit log {} shrank to {} bytes; reading it again", self.path.display(), len);
+            self.seq = 0;
+            self.last_hash = GENESIS_HASH.to_string();
+            self.offset = 0;
+            self.index.clear();
+        }
+
+        let mut reader = BufReader::new(File::open(&self.path)?);
+        reader.seek(SeekFrom::Start(self.offset))?;
+        let mut line = Vec::new();
+        loop {
+            line.clear();
+            let read = reader.read_until(b'\n', &mut line)?;
+            if read == 0 {
+                break;
+            }
+            if line.last() != Some(&b'\n') {
+                // Appends hold the lock until they are synced, so this is one cut short by a crash
+                warn!("Audit log {} ends in an incomplete line of {} bytes; dropping it", self.path.display(), read);
+                self.file.set_len(self.offset)?;
+                self.file.sync_data()?;
+                break;
+            }
+            let start = self.offset;
+            self.offset += read as u64;
+            if line.trim_ascii().is_empty() {
+                continue;
+            }
+            match serde_json::from_slice::<AuditEntry>(&line) {
+                Ok(entry) => {
+                    self.index(entry.seq, start);
+                    self.seq = entry.seq;
+                    self.last_hash = entry.hash;
+                }
+                Err(e) => warn!("Audit log {} line at byte {} is unreadable: {}", self.path.display(), start, e),
+            }
+        }
+        Ok(())
+    }
+}
+
+pub struct AuditStore {
+    path: PathBuf,
+    head: Arc<Mutex<ChainHead>>,
+}
+
+/// Query parameters accepted by GET /audit/log
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+pub struct AuditQuery {
+    pub agent_id: Option<String>,
+    pub task_id: Option<String>,
+    pub trace_id: Option<String>,
+    pub approval_id: Option<String>,
+    pub target_mcp: Option<String>,
+    pub operation: Option<String>,
+    pub authorized: Option<bool>,
+    pub since: Option<String>,   // RFC 3339, inclusive
+    pub until: Option<String>,   // RFC 3339, exclusive
+    pub cursor: Option<u64>,     // Only entries with seq > cursor
+    pub limit: Option<usize>,    // Page size for JSON responses
+    #[serde(default)]
+    pub format: ExportFormat,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    #[default]
+    Json,   // One page, with a cursor for the next
+    Ndjson, // Streamed, every matching entry
+    Csv,    // Streamed, every matching entry
+}
+
+/// Parsed form of an AuditQuery
+#[derive(Debug, Clone)]
+pub struct AuditFilter {
+    agent_id: Option<String>,
+    task_id: Option<String>,
+    trace_id: Option<String>,
+    approval_id: Option<String>,
+    target_mcp: Option<String>,
+    operation: Option<String>,
+    authorized: Option<bool>,
+    since: Option<DateTime<FixedOffset>>,
+    until: Option<DateTime<FixedOffset>>,
+    after_seq: u64,
+}
+
+impl AuditFilter {
+    pub fn from_query(query: &AuditQuery) -> Result<Self, String> {
+        let parse_time = |name: &str, value: &Option<String>| {
+            value.as_deref()
+                .map(DateTime::parse_from_rfc3339)
+                .transpose()
+                .map_err(|e| format!("Invalid {} timestamp: {}", name, e))
+        };
+
+        Ok(AuditFilter {
+            agent_id: query.agent_id.clone(),
+            task_id: query.task_id.clone(),
+            trace_id: query.trace_id.clone(),
+            approval_id: query.approval_id.clone(),
+            target_mcp: query.target_mcp.clone(),
+            operation: query.operation.clone(),
+            authorized: query.authorized,
+            since: parse_time("since", &query.since)?,
+            until: parse_time("until", &query.until)?,
+            after_seq: query.cursor.unwrap_or(0),
+        })
+    }
+
+    pub fn matches(&self, entry: &AuditEntry) -> bool {
+        if entry.seq <= self.after_seq
+            || self.agent_id.as_ref().is_some_and(|v| *v != entry.agent_id)
+            || self.task_id.as_ref().is_some_and(|v| Some(v) != entry.task_id.as_ref())
+            || self.trace_id.as_ref().is_some_and(|v| Some(v) != entry.trace_id.as_ref())
+            || self.approval_id.as_ref().is_some_and(|v| Some(v) != entry.approval_id.as_ref())
+            || self.target_mcp.as_ref().is_some_and(|v| *v != entry.target_mcp)
+            || self.operation.as_ref().is_some_and(|v| *v != entry.operation)
+            || self.authorized.is_some_and(|v| v != entry.authorized)
+        {
+            return false;
+        }
+
+        if self.since.is_some() || self.until.is_some() {
+            let Ok(ts) = DateTime::parse_from_rfc3339(&entry.timestamp) else {
+                return false;
+            };
+            if self.since.is_some_and(|since| ts < since) || self.until.is_some_and(|until| ts >= until) {
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct AuditPage {
+    pub entries: Vec<AuditEntry>,
+    pub next_cursor: Option<u64>, // Pass back as `cursor` to continue
+    pub has_more: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct VerifyReport {
+    pub valid: bool,
+    pub entries_checked: u64,
+    pub first_broken: Option<BrokenLink>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct BrokenLink {
+    pub line: u64,
+    pub seq: Option<u64>,
+    pub request_id: Option<String>,
+    pub reason: String,
+}
+
+impl AuditStore {
+    /// Open (or create) the log at `path` and resume the chain from its last entry
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)?;
+        }
+
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let mut head = ChainHead {
+            path: path.clone(),
+            file,
+            seq: 0,
+            last_hash: GENESIS_HASH.to_string(),
+            offset: 0,
+            index: Vec::new(),
+        };
+        head.file.lock()?;
+        let read = head.catch_up();
+        head.file.unlock()?;
+        read?;
+        info!("📜 Audit log {} opened at seq {}", path.display(), head.seq);
+
+        Ok(AuditStore { path, head: Arc::new(Mutex::new(head)) })
+    }
+
+    /// Link `entry` onto the chain, persist it, and return the sealed entry
+    pub async fn append(&self, entry: AuditEntry) -> io::Result<AuditEntry> {
+        let head = self.head.clone();
+        web::block(move || append_to(&head, entry)).await.map_err(io::Error::other)?
+    }
+
+    /// Number of entries appended so far
+    pub fn len(&self) -> u64 {
+        self.head.lock().unwrap().seq
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// One page of matching entries, oldest first
+    pub async fn page(&self, filter: &AuditFilter, limit: usize) -> io::Result<AuditPage> {
+        let mut lines = self.lines_after(filter.after_seq).await?;
+        let mut entries = Vec::new();
+        let mut has_more = false;
+
+        while let Some(line) = lines.next_line().await? {
+            let Ok(entry) = serde_json::from_str::<AuditEntry>(&line) else {
+                continue;
+            };
+            if !filter.matches(&entry) {
+                continue;
+            }
+            if entries.len() == limit {
+                has_more = true;
+                break;
+            }
+            entries.push(entry);
+        }
+
+        let next_cursor = if has_more { entries.last().map(|e| e.seq) } else { None };
+        Ok(AuditPage { entries, next_cursor, has_more })
+    }
+
+    /// Stream every matching entry as NDJSON or CSV without buffering the log
+    pub async fn export(
+        &self,
+        filter: AuditFilter,
+        format: ExportFormat,
+    ) -> io::Result<impl Stream<Item = io::Result<Bytes>>> {
+        let lines = self.lines_after(filter.after_seq).await?;
+        let header = (format == ExportFormat::Csv).then_some(Ok(Bytes::from_static(CSV_HEADER.as_bytes())));
+
+        let rows = stream::unfold((lines, filter), move |(mut lines, filter)| async move {
+            loop {
+                match lines.next_line().await {
+                    Ok(Some(line)) => {
+                        let Ok(entry) = serde_json::from_str::<AuditEntry>(&line) else {
+                            continue;
+                        };
+                        if !filter.matches(&entry) {
+                            continue;
+                        }
+                        let chunk = match format {
+                            ExportFormat::Csv => csv_row(&entry),
+                            _ => line + "\n",
+                        };
+                        return Some((Ok(Bytes::from(chunk)), (lines, filter)));
+                    }
+                    Ok(None) => return None,
+                    Err(e) => return Some((Err(e), (lines, filter))),
+                }
+            }
+        });
+
+        Ok(stream::iter(header).chain(rows))
+    }
+
+    /// Lines of the log from the last indexed entry at or before `after_seq + 1`
+    async fn lines_after(&self, after_seq: u64) -> io::Result<Lines<tokio::io::BufReader<tokio::fs::File>>> {
+        let start = {
+            let head = self.head.lock().unwrap();
+            let i = head.index.partition_point(|&(seq, _)| seq <= after_seq + 1);
+            i.checked_sub(1).map_or(0, |i| head.index[i].1)
+        };
+        let mut file = tokio::fs::File::open(&self.path).await?;
+        file.seek(SeekFrom::Start(start)).await?;
+        Ok(tokio::io::BufReader::new(file).lines())
+    }
+
+    /// Walk the whole file and report the first entry that breaks the chain
+    pub async fn verify(&self) -> io::Result<VerifyReport> {
+        let path = self.path.clone();
+        web::block(move || verify_file(&path)).await.map_err(io::Error::other)?
+    }
+}
+
+/// Seal `entry` onto the chain and write it through to disk
+fn append_to(head: &Mutex<ChainHead>, entry: AuditEntry) -> io::Result<AuditEntry> {
+    let mut head = head.lock().unwrap();
+    head.file.lock()?;
+    let sealed = head.catch_up().and_then(|_| seal(&mut head, entry));
+    if let Err(e) = head.file.unlock() {
+        warn!("Audit log {} could not be unlocked: {}", head.path.display(), e);
+    }
+    sealed
+}
+
+/// Link `entry` onto the end of the chain; the file is locked and caught up
+fn seal(head: &mut ChainHead, mut entry: AuditEntry) -> io::Result<AuditEntry> {
+    entry.seq = head.seq + 1;
+    entry.prev_hash = head.last_hash.clone();
+    entry.hash = entry_hash(&entry);
+
+    let mut line = serde_json::to_vec(&entry).map_err(io::Error::other)?;
+    line.push(b'\n');
+    if let Err(e) = head.file.write_all(&line).and_then(|_| head.file.sync_data()) {
+        // Cut off whatever part of the line reached the file, so the next entry
+        // does not land on the torn bytes
+        if let Err(cut) = head.file.set_len(head.offset) {
+            warn!("Audit log could not drop a failed append: {}", cut);
+        }
+        return Err(e);
+    }
+
+    let start = head.offset;
+    head.index(entry.seq, start);
+    head.offset += line.len() as u64;
+    head.seq = entry.seq;
+    head.last_hash = entry.hash.clone();
+    Ok(entry)
+}
+
+fn verify_file(path: &Path) -> io::Result<VerifyReport> {
+    let mut expected_seq = 1;
+    let mut expected_prev = GENESIS_HASH.to_string();
+    let mut checked = 0;
+
+    // Shared lock: no append is half written while the chain is walked
+    let file = File::open(path)?;
+    file.lock_shared()?;
+    for (idx, line) in BufReader::new(&file).lines().enumerate() {
+        let line = line?;
+        let line_no = idx as u64 + 1;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let broken = |seq: Option<u64>, request_id: Option<String>, reason: String| VerifyReport {
+            valid: false,
+            entries_checked: checked,
+            first_broken: Some(BrokenLink { line: line_no, seq, request_id, reason }),
+        };
+
+        let entry: AuditEntry = match serde_json::from_str(&line) {
+            Ok(e) => e,
+            Err(e) => return Ok(broken(None, None, format!("Unparseable entry: {}", e))),
+        };
+        let seq = Some(entry.seq);
+        let request_id = Some(entry.request_id.clone());
+
+        if entry.seq != expected_seq {
+            return Ok(broken(seq, request_id,
+                format!("Expected seq {}, found {}", expected_seq, entry.seq)));
+        }
+        if entry.prev_hash != expected_prev {
+            return Ok(broken(seq, request_id,
+                "prev_hash does not match the hash of the preceding entry".to_string()));
+        }
+        if entry.hash != entry_hash(&entry) {
+            return Ok(broken(seq, request_id,
+                "hash does not match entry contents".to_string()));
+        }
+
+        checked += 1;
+        expected_seq += 1;
+        expected_prev = entry.hash;
+    }
+
+    Ok(VerifyReport { valid: true, entries_checked: checked, first_broken: None })
+}
+
+/// SHA-256 over the entry serialized with an empty `hash` field
+pub fn entry_hash(entry: &AuditEntry) -> String {
+    let mut unsealed = entry.clone();
+    unsealed.hash = String::new();
+    let bytes = serde_json::to_vec(&unsealed).expect("AuditEntry is always serializable");
+    hex::encode(Sha256::digest(&bytes))
+}
+
+fn csv_row(entry: &AuditEntry) -> String {
+    let fields = [
+        entry.seq.to_string(),
+        entry.timestamp.clone(),
+        entry.agent_id.clone(),
+        entry.task_id.clone().unwrap_or_default(),
+        entry.target_mcp.clone(),
+        entry.operation.clone(),
+        entry.authorized.to_string(),
+        entry.request_id.clone(),
+        entry.trace_id.clone().unwrap_or_default(),
+        entry.denial_reason.clone().unwrap_or_default(),
+        entry.approval_id.clone().unwrap_or_default(),
+        entry.approval_status.clone().unwrap_or_default(),
+        entry.approver.clone().unwrap_or_default(),
+        entry.subject.clone().unwrap_or_default(),
+        entry.prev_hash.clone(),
+        entry.hash.clone(),
+    ];
+    let mut row = fields.iter().map(|f| csv_field(f)).collect::<Vec<_>>().join(",");
+    row.push('\n');
+    row
+}
+
+/// Quote a CSV field when it contains a delimiter, quote or line break. A field
+/// a spreadsheet would read as a formula gets a leading `'` so it stays text.
+fn csv_field(value: &str) -> String {
+    let value = if value.starts_with(['=', '+', '-', '@', '\t', '\r']) {
+        format!("'{}", value)
+    } else {
+        value.to_string()
+    };
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_log() -> PathBuf {
+        std::env::temp_dir().join(format!("audit-test-{}.jsonl", uuid::Uuid::new_v4()))
+    }
+
+    fn entry(operation: &str) -> AuditEntry {
+        AuditEntry {
+            seq: 0,
+            timestamp: "2026-01-01T00:00:00+00:00".to_string(),
+            agent_id: "green_agent".to_string(),
+            task_id: None,
+            target_mcp: "cobol_mcp".to_string(),
+            operation: operation.to_string(),
+            authorized: true,
+            request_id: format!("req-{}", operation),
+            trace_id: None,
+            denial_reason: None,
+            approval_id: None,
+            approval_status: None,
+            approver: None,
+            subject: None,
+            prev_hash: String::new(),
+            hash: String::new(),
+        }
+    }
+
+    /// Log at a fresh path with one entry per operation
+    fn log_with(operations: &[&str]) -> (PathBuf, AuditStore) {
+        let path = temp_log();
+        let store = AuditStore::open(&path).unwrap();
+        for op in operations {
+            append_to(&store.head, entry(op)).unwrap();
+        }
+        (path, store)
+    }
+
+    fn rewrite_lines(path: &Path, change: impl FnOnce(&mut Vec<String>)) {
+        let mut lines = fs::read_to_string(path).unwrap().lines().map(String::from).collect::<Vec<_>>();
+        change(&mut lines);
+        fs::write(path, lines.join("\n") + "\n").unwrap();
+    }
+
+    #[test]
+    fn appended_entries_form_a_valid_chain() {
+        let (path, store) = log_with(&["compile", "execute", "analyze"]);
+        assert_eq!(store.len(), 3);
+        assert_eq!(verify_file(&path).unwrap(),
+                   VerifyReport { valid: true, entries_checked: 3, first_broken: None });
+
+        let entries = fs::read_to_string(&path).unwrap().lines()
+            .map(|l| serde_json::from_str::<AuditEntry>(l).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(entries[0].prev_hash, GENESIS_HASH);
+        assert_eq!(entries[1].prev_hash, entries[0].hash);
+        assert_eq!(entries[2].prev_hash, entries[1].hash);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn reopened_log_continues_the_chain() {
+        let (path, store) = log_with(&["compile", "execute"]);
+        drop(store);
+        let store = AuditStore::open(&path).unwrap();
+        assert_eq!(store.len(), 2);
+        assert_eq!(append_to(&store.head, entry("analyze")).unwrap().seq, 3);
+        assert!(verify_file(&path).unwrap().valid);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn edited_entry_is_detected() {
+        let (path, _store) = log_with(&["compile", "execute", "analyze"]);
+        rewrite_lines(&path, |lines| lines[1] = lines[1].replace("\"execute\"", "\"delete\""));
+
+        let report = verify_file(&path).unwrap();
+        assert!(!report.valid);
+        assert_eq!(report.entries_checked, 1);
+        let broken = report.first_broken.unwrap();
+        assert_eq!((broken.line, broken.seq), (2, Some(2)));
+        assert_eq!(broken.reason, "hash does not match entry contents");
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn removed_entry_is_detected() {
+        let (path, _store) = log_with(&["compile", "execute", "analyze"]);
+        rewrite_lines(&path, |lines| { lines.remove(1); });
+
+        let broken = verify_file(&path).unwrap().first_broken.unwrap();
+        assert_eq!((broken.line, broken.seq), (2, Some(3)));
+        assert_eq!(broken.reason, "Expected seq 2, found 3");
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn resealed_entry_is_detected_by_its_successor() {
+        let (path, _store) = log_with(&["compile", "execute", "analyze"]);
+        rewrite_lines(&path, |lines| {
+            let mut forged: AuditEntry = serde_json::from_str(&lines[1]).unwrap();
+            forged.operation = "delete".to_string();
+            forged.hash = entry_hash(&forged);
+            lines[1] = serde_json::to_string(&forged).unwrap();
+        });
+
+        let broken = verify_file(&path).unwrap().first_broken.unwrap();
+        assert_eq!((broken.line, broken.seq), (3, Some(3)));
+        assert_eq!(broken.reason, "prev_hash does not match the hash of the preceding entry");
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn torn_line_is_dropped_by_the_next_append() {
+        let (path, store) = log_with(&["compile", "execute"]);
+        OpenOptions::new().append(true).open(&path).unwrap()
+            .write_all(b"{\"seq\":3,\"timest").unwrap();
+
+        let sealed = append_to(&store.head, entry("analyze")).unwrap();
+        assert_eq!(sealed.seq, 3);
+        let contents = fs::read_to_string(&path).unwrap();
+        assert!(contents.lines().all(|l| serde_json::from_str::<AuditEntry>(l).is_ok()));
+        assert_eq!(verify_file(&path).unwrap(),
+                   VerifyReport { valid: true, entries_checked: 3, first_broken: None });
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn entries_appended_by_another_replica_are_chained_onto() {
+        let (path, first) = log_with(&["compile"]);
+        let second = AuditStore::open(&path).unwrap();
+        append_to(&second.head, entry("execute")).unwrap();
+
+        assert_eq!(append_to(&first.head, entry("analyze")).unwrap().seq, 3);
+        assert!(verify_file(&path).unwrap().valid);
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn formula_cells_stay_text() {
+        assert_eq!(csv_field("=HYPERLINK(\"http://evil\")"), "\"'=HYPERLINK(\"\"http://evil\"\")\"");
+        assert_eq!(csv_field("+1"), "'+1");
+        assert_eq!(csv_field("-1"), "'-1");
+        assert_eq!(csv_field("@SUM(A1)"), "'@SUM(A1)");
+        assert_eq!(csv_field("\t=1"), "'\t=1");
+        assert_eq!(csv_field("\r=1"), "\"'\r=1\"");
+    }
+
+    #[test]
+    fn plain_and_delimited_fields() {
+        assert_eq!(csv_field("green_agent"), "green_agent");
+        assert_eq!(csv_field(""), "");
+        assert_eq!(csv_field("a=b"), "a=b");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+        assert_eq!(csv_field("two\nlines"), "\"two\nlines\"");
+    }
+
+    #[test]
+    fn csv_row_matches_the_header() {
+        let mut denied = entry("execute");
+        denied.authorized = false;
+        denied.denial_reason = Some("-role, not allowed".to_string());
+        let row = csv_row(&denied);
+        assert!(row.ends_with('\n'));
+        assert!(row.contains(",false,req-execute,,\"'-role, not allowed\","));
+        assert_eq!(row.matches(',').count() - 1, CSV_HEADER.matches(',').count());
+    }
+}
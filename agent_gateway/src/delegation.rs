@@ -0,0 +1,166 @@
+// Delegated Tokens - short-lived access tokens scoped to one task
+//
+// An agent can trade its access token at /auth/delegate for a narrower one
+// covering a single pipeline run: the token names the task_id and lists the
+// exact MCP operations the run needs, optionally pinned to an S3 bucket and
+// key prefix. Calls outside that scope are denied even where the role would
+// allow them, and every audit entry the token produces carries the task_id.
+// The token lives until its TTL runs out or the task's tokens are revoked
+// with `/auth/revoke {"task_id": ...}`, whichever comes first.
+
+use serde::{Deserialize, Serialize};
+
+/// Lifetime of a delegated token when the request does not set `ttl_secs`
+pub const DEFAULT_DELEGATED_TTL_SECS: i64 = 900;
+
+/// Most operations one delegated token may be scoped to
+pub const MAX_SCOPE_GRANTS: usize = 32;
+
+/// Longest accepted task_id
+pub const MAX_TASK_ID_LEN: usize = 128;
+
+/// One operation a delegated token may call
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Hash)]
+#[serde(deny_unknown_fields)]
+pub struct ScopeGrant {
+    pub target_mcp: String,
+    pub operation: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bucket: Option<String>,     // Payload `bucket` must equal this
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub key_prefix: Option<String>, // Payload `key` must start with this
+}
+
+impl ScopeGrant {
+    fn covers(&self, mcp: &str, operation: &str) -> bool {
+        self.target_mcp == mcp && self.operation == operation
+    }
+
+    /// Why `payload` is outside this grant's bucket or key prefix, if it is
+    fn resource_violation(&self, payload: &serde_json::Value) -> Option<String> {
+        if let Some(bucket) = &self.bucket {
+            match payload.get("bucket").and_then(|b| b.as_str()) {
+                Some(b) if b == bucket => {}
+                Some(b) => return Some(format!("bucket '{}' is outside the delegated bucket '{}'", b, bucket)),
+                None => return Some(format!("payload has no bucket; the token is limited to bucket '{}'", bucket)),
+            }
+        }
+        if let Some(prefix) = &self.key_prefix {
+            match payload.get("key").and_then(|k| k.as_str()) {
+                Some(k) if k.starts_with(prefix.as_str()) => {}
+                Some(k) => return Some(format!("key '{}' is outside the delegated prefix '{}'", k, prefix)),
+                None => return Some(format!("payload has no key; the token is limited to keys under '{}'", prefix)),
+            }
+        }
+        None
+    }
+}
+
+/// Check a call against a delegated token's scope. A call passes if any grant
+/// for its operation accepts the payload.
+pub fn check_scope(
+    scope: &[ScopeGrant],
+    task_id: &str,
+    mcp: &str,
+    operation: &str,
+    payload: &serde_json::Value,
+) -> Result<(), String> {
+    let mut violations = scope.iter()
+        .filter(|grant| grant.covers(mcp, operation))
+        .map(|grant| grant.resource_violation(payload))
+        .peekable();
+
+    if violations.peek().is_none() {
+        return Err(format!("Token for task {} is not scoped to call {} on {}", task_id, operation, mcp));
+    }
+    let mut reasons = Vec::new();
+    for violation in violations {
+        match violation {
+            None => return Ok(()),
+            Some(reason) => reasons.push(reason),
+        }
+    }
+    Err(format!("Token for task {}: {}", task_id, reasons.join("; ")))
+}
+
+/// Whether `scope` (if any) names the operation at all, ignoring resources
+pub fn scope_covers(scope: Option<&[ScopeGrant]>, mcp: &str, operation: &str) -> bool {
+    scope.is_none_or(|grants| grants.iter().any(|grant| grant.covers(mcp, operation)))
+}
+
+/// Task ids end up in tokens, logs and CSV exports, so keep them plain
+pub fn is_valid_task_id(task_id: &str) -> bool {
+    !task_id.is_empty()
+        && task_id.len() <= MAX_TASK_ID_LEN
+        && task_id.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | ':'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn grant(mcp: &str, operation: &str, bucket: Option<&str>, key_prefix: Option<&str>) -> ScopeGrant {
+        ScopeGrant {
+            target_mcp: mcp.to_string(),
+            operation: operation.to_string(),
+            bucket: bucket.map(String::from),
+            key_prefix: key_prefix.map(String::from),
+        }
+    }
+
+    #[test]
+    fn unscoped_operation_is_denied() {
+        let scope = [grant("cobol_mcp", "compile", None, None)];
+        assert_eq!(check_scope(&scope, "job-1", "cobol_mcp", "compile", &json!({})), Ok(()));
+        assert_eq!(check_scope(&scope, "job-1", "cobol_mcp", "execute", &json!({})),
+                   Err("Token for task job-1 is not scoped to call execute on cobol_mcp".to_string()));
+        assert!(check_scope(&scope, "job-1", "rust_mcp", "compile", &json!({})).is_err());
+    }
+
+    #[test]
+    fn bucket_and_prefix_pin_the_payload() {
+        let scope = [grant("s3_mcp", "get_object", Some("legacy"), Some("jobs/job-1/"))];
+        let call = |payload| check_scope(&scope, "job-1", "s3_mcp", "get_object", &payload);
+
+        assert_eq!(call(json!({"bucket": "legacy", "key": "jobs/job-1/PAYROLL.cbl"})), Ok(()));
+        assert_eq!(call(json!({"bucket": "other", "key": "jobs/job-1/PAYROLL.cbl"})),
+                   Err("Token for task job-1: bucket 'other' is outside the delegated bucket 'legacy'".to_string()));
+        assert_eq!(call(json!({"bucket": "legacy", "key": "jobs/job-2/PAYROLL.cbl"})),
+                   Err("Token for task job-1: key 'jobs/job-2/PAYROLL.cbl' is outside the delegated prefix 'jobs/job-1/'".to_string()));
+        assert!(call(json!({"key": "jobs/job-1/PAYROLL.cbl"})).is_err());
+        assert!(call(json!({"bucket": "legacy"})).is_err());
+    }
+
+    #[test]
+    fn any_matching_grant_admits_the_call() {
+        let scope = [
+            grant("s3_mcp", "put_object", Some("legacy"), Some("out/")),
+            grant("s3_mcp", "put_object", Some("modern"), None),
+        ];
+        assert_eq!(check_scope(&scope, "job-1", "s3_mcp", "put_object", &json!({"bucket": "modern", "key": "x"})), Ok(()));
+
+        let denied = check_scope(&scope, "job-1", "s3_mcp", "put_object", &json!({"bucket": "other", "key": "out/x"}))
+            .unwrap_err();
+        assert!(denied.contains("outside the delegated bucket 'legacy'"));
+        assert!(denied.contains("outside the delegated bucket 'modern'"));
+    }
+
+    #[test]
+    fn scope_covers_ignores_resources() {
+        let scope = [grant("s3_mcp", "get_object", Some("legacy"), None)];
+        assert!(scope_covers(Some(&scope), "s3_mcp", "get_object"));
+        assert!(!scope_covers(Some(&scope), "s3_mcp", "put_object"));
+        assert!(!scope_covers(Some(&[]), "s3_mcp", "get_object"));
+        assert!(scope_covers(None, "s3_mcp", "put_object"));
+    }
+
+    #[test]
+    fn task_ids_stay_plain() {
+        assert!(is_valid_task_id("job-42_run.1:retry"));
+        assert!(!is_valid_task_id(""));
+        assert!(!is_valid_task_id("job 42"));
+        assert!(!is_valid_task_id("=cmd|calc"));
+        assert!(!is_valid_task_id(&"a".repeat(MAX_TASK_ID_LEN + 1)));
+    }
+}
@@ -0,0 +1,258 @@
+// Signing Keys - asymmetric JWT keys with key IDs and rotation
+//
+// Keys are PKCS#8 PEM files in JWT_KEYS_DIR, one per key, named `<kid>.pem`
+// (Ed25519 -> EdDSA, RSA -> RS256). The active key signs new tokens; it is
+// JWT_ACTIVE_KID, or the greatest kid in the directory, so adding a key with a
+// later name (e.g. a date) rotates to it. Every key in the directory verifies,
+// and a key removed from the directory keeps verifying until tokens it signed
+// have expired. The public halves are served as a JWKS so MCP servers can
+// verify gateway tokens themselves.
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use jsonwebtoken::jwk::{
+    AlgorithmParameters, CommonParameters, EllipticCurve, Jwk, JwkSet, KeyAlgorithm,
+    OctetKeyPairParameters, OctetKeyPairType, PublicKeyUse, RSAKeyParameters, RSAKeyType,
+};
+use jsonwebtoken::{decode, decode_header, encode, Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use ring::rand::SystemRandom;
+use ring::rsa::PublicKeyComponents;
+use ring::signature::{Ed25519KeyPair, KeyPair, RsaKeyPair};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use log::{info, warn};
+
+use crate::TOKEN_TTL_SECS;
+
+pub struct SigningKey {
+    pub kid: String,
+    pub algorithm: Algorithm,
+    pub jwk: Jwk,
+    encoding: EncodingKey,
+    decoding: DecodingKey,
+    retired_at: Option<i64>, // Set once the key leaves the keys directory
+}
+
+pub struct KeyStore {
+    dir: Option<PathBuf>,
+    pinned_kid: Option<String>, // JWT_ACTIVE_KID, if set
+    active_kid: String,
+    keys: Vec<SigningKey>,
+}
+
+#[derive(Debug)]
+pub enum KeyError {
+    Io { path: String, error: std::io::Error },
+    Invalid { path: String, message: String },
+    NoKeys { dir: String },
+    UnknownActiveKid { kid: String },
+}
+
+impl fmt::Display for KeyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KeyError::Io { path, error } => write!(f, "cannot read signing key {}: {}", path, error),
+            KeyError::Invalid { path, message } => write!(f, "invalid signing key {}: {}", path, message),
+            KeyError::NoKeys { dir } => write!(f, "no *.pem signing keys found in {}", dir),
+            KeyError::UnknownActiveKid { kid } => write!(f, "JWT_ACTIVE_KID '{}' has no key file", kid),
+        }
+    }
+}
+
+impl std::error::Error for KeyError {}
+
+impl SigningKey {
+    /// Parse a PKCS#8 (or PKCS#1 RSA) private key PEM
+    pub fn from_pem(kid: &str, pem_bytes: &[u8], path: &str) -> Result<Self, KeyError> {
+        let invalid = |message: String| KeyError::Invalid { path: path.to_string(), message };
+        let parsed = pem::parse(pem_bytes).map_err(|e| invalid(e.to_string()))?;
+        let der = parsed.contents();
+
+        if parsed.tag() == "PRIVATE KEY" {
+            if let Ok(pair) = Ed25519KeyPair::from_pkcs8_maybe_unchecked(der) {
+                let encoding = EncodingKey::from_ed_pem(pem_bytes).map_err(|e| invalid(e.to_string()))?;
+                return SigningKey::ed25519(kid, encoding, pair.public_key().as_ref());
+            }
+        }
+
+        let pair = match parsed.tag() {
+            "PRIVATE KEY" => RsaKeyPair::from_pkcs8(der),
+            "RSA PRIVATE KEY" => RsaKeyPair::from_der(der),
+            tag => return Err(invalid(format!("unsupported PEM block '{}'", tag))),
+        }
+        .map_err(|e| invalid(format!("not an Ed25519 or RSA private key: {}", e)))?;
+
+        let encoding = EncodingKey::from_rsa_pem(pem_bytes).map_err(|e| invalid(e.to_string()))?;
+        let public = PublicKeyComponents::<Vec<u8>>::from(pair.public());
+        let jwk = Jwk {
+            common: common_parameters(kid, KeyAlgorithm::RS256),
+            algorithm: AlgorithmParameters::RSA(RSAKeyParameters {
+                key_type: RSAKeyType::RSA,
+                n: URL_SAFE_NO_PAD.encode(&public.n),
+                e: URL_SAFE_NO_PAD.encode(&public.e),
+            }),
+        };
+        SigningKey::new(kid, Algorithm::RS256, jwk, encoding)
+    }
+
+    /// Fresh Ed25519 key that only lives in memory
+    pub fn generate(kid: &str) -> Result<Self, KeyError> {
+        let invalid = |message: String| KeyError::Invalid { path: "<generated>".to_string(), message };
+        let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new())
+            .map_err(|e| invalid(e.to_string()))?;
+        let pair = Ed25519KeyPair::from_pkcs8(pkcs8.as_ref()).map_err(|e| invalid(e.to_string()))?;
+        SigningKey::ed25519(kid, EncodingKey::from_ed_der(pkcs8.as_ref()), pair.public_key().as_ref())
+    }
+
+    fn ed25519(kid: &str, encoding: EncodingKey, public_key: &[u8]) -> Result<Self, KeyError> {
+        let jwk = Jwk {
+            common: common_parameters(kid, KeyAlgorithm::EdDSA),
+            algorithm: AlgorithmParameters::OctetKeyPair(OctetKeyPairParameters {
+                key_type: OctetKeyPairType::OctetKeyPair,
+                curve: EllipticCurve::Ed25519,
+                x: URL_SAFE_NO_PAD.encode(public_key),
+            }),
+        };
+        SigningKey::new(kid, Algorithm::EdDSA, jwk, encoding)
+    }
+
+    fn new(kid: &str, algorithm: Algorithm, jwk: Jwk, encoding: EncodingKey) -> Result<Self, KeyError> {
+        // Verify with exactly what the JWKS publishes
+        let decoding = DecodingKey::from_jwk(&jwk).map_err(|e| KeyError::Invalid {
+            path: kid.to_string(),
+            message: e.to_string(),
+        })?;
+        Ok(SigningKey { kid: kid.to_string(), algorithm, jwk, encoding, decoding, retired_at: None })
+    }
+}
+
+impl KeyStore {
+    /// Load keys from `dir`, or generate an ephemeral key when there is none
+    pub fn load(dir: Option<&Path>, active_kid: Option<&str>) -> Result<Self, KeyError> {
+        let Some(dir) = dir else {
+            let kid = format!("ephemeral-{}", chrono::Utc::now().format("%Y%m%d%H%M%S"));
+            warn!("JWT_KEYS_DIR not set: signing with ephemeral key '{}'. Tokens will not \
+                   survive a restart or verify on other replicas.", kid);
+            let keys = vec![SigningKey::generate(&kid)?];
+            return Ok(KeyStore { dir: None, pinned_kid: None, active_kid: kid, keys });
+        };
+
+        let keys = read_key_dir(dir)?;
+        let pinned_kid = active_kid.map(String::from);
+        let active_kid = match active_kid {
+            Some(kid) if keys.iter().any(|k| k.kid == kid) => kid.to_string(),
+            Some(kid) => return Err(KeyError::UnknownActiveKid { kid: kid.to_string() }),
+            None => keys.iter().map(|k| k.kid.clone()).max()
+                .ok_or_else(|| KeyError::NoKeys { dir: dir.display().to_string() })?,
+        };
+
+        info!("🔑 Signing with key '{}' ({} keys loaded from {})", active_kid, keys.len(), dir.display());
+        Ok(KeyStore {
+            dir: Some(dir.to_path_buf()),
+            pinned_kid,
+            active_kid,
+            keys,
+        })
+    }
+
+    /// Re-read the keys directory; keys that disappeared keep verifying until
+    /// every token they signed has expired
+    pub fn reload(&mut self, now: i64) -> Result<(), KeyError> {
+        let Some(dir) = self.dir.clone() else { return Ok(()) };
+        let mut next = KeyStore::load(Some(&dir), self.pinned_kid.as_deref())?;
+
+        for mut old in self.keys.drain(..) {
+            if next.keys.iter().any(|k| k.kid == old.kid) {
+                continue;
+            }
+            old.retired_at.get_or_insert(now);
+            info!("🔑 Key '{}' retired; verifying until its tokens expire", old.kid);
+            next.keys.push(old);
+        }
+
+        *self = next;
+        self.prune(now);
+        Ok(())
+    }
+
+    /// Forget retired keys whose tokens have all expired
+    pub fn prune(&mut self, now: i64) {
+        self.keys.retain(|k| k.retired_at.is_none_or(|at| at + TOKEN_TTL_SECS >= now));
+    }
+
+    pub fn dir(&self) -> Option<&Path> {
+        self.dir.as_deref()
+    }
+
+    pub fn active_kid(&self) -> &str {
+        &self.active_kid
+    }
+
+    /// Sign `claims` with the active key, naming it in the `kid` header
+    pub fn sign<T: Serialize>(&self, claims: &T) -> Result<String, String> {
+        let key = self.keys.iter()
+            .find(|k| k.kid == self.active_kid)
+            .ok_or("Active signing key missing")?;
+        let mut header = Header::new(key.algorithm);
+        header.kid = Some(key.kid.clone());
+        encode(&header, claims, &key.encoding).map_err(|e| format!("Token signing failed: {}", e))
+    }
+
+    /// Verify a token against the key named by its `kid` header
+    pub fn verify<T: DeserializeOwned>(&self, token: &str) -> Result<T, String> {
+        let header = decode_header(token).map_err(|e| format!("Token decode failed: {}", e))?;
+        let kid = header.kid.ok_or("Token has no key ID")?;
+        let key = self.keys.iter()
+            .find(|k| k.kid == kid)
+            .ok_or_else(|| format!("Unknown signing key '{}'", kid))?;
+        if header.alg != key.algorithm {
+            return Err(format!("Algorithm {:?} does not match key '{}'", header.alg, kid));
+        }
+
+        decode::<T>(token, &key.decoding, &Validation::new(key.algorithm))
+            .map(|data| data.claims)
+            .map_err(|e| format!("Token decode failed: {}", e))
+    }
+
+    /// Public keys for /.well-known/jwks.json
+    pub fn jwks(&self) -> JwkSet {
+        JwkSet { keys: self.keys.iter().map(|k| k.jwk.clone()).collect() }
+    }
+}
+
+/// Last-modified time of the keys directory, for change detection
+pub fn modified(dir: &Path) -> Option<SystemTime> {
+    std::fs::metadata(dir).and_then(|m| m.modified()).ok()
+}
+
+fn read_key_dir(dir: &Path) -> Result<Vec<SigningKey>, KeyError> {
+    let io_err = |path: &Path, error| KeyError::Io { path: path.display().to_string(), error };
+    let mut keys = Vec::new();
+
+    for entry in std::fs::read_dir(dir).map_err(|e| io_err(dir, e))? {
+        let path = entry.map_err(|e| io_err(dir, e))?.path();
+        if path.extension().and_then(|e| e.to_str()) != Some("pem") {
+            continue;
+        }
+        let Some(kid) = path.file_stem().and_then(|s| s.to_str()) else { continue };
+        let bytes = std::fs::read(&path).map_err(|e| io_err(&path, e))?;
+        keys.push(SigningKey::from_pem(kid, &bytes, &path.display().to_string())?);
+    }
+
+    if keys.is_empty() {
+        return Err(KeyError::NoKeys { dir: dir.display().to_string() });
+    }
+    Ok(keys)
+}
+
+fn common_parameters(kid: &str, algorithm: KeyAlgorithm) -> CommonParameters {
+    CommonParameters {
+        public_key_use: Some(PublicKeyUse::Signature),
+        key_algorithm: Some(algorithm),
+        key_id: Some(kid.to_string()),
+        ..Default::default()
+    }
+}
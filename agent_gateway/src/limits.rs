@@ -0,0 +1,121 @@
+// Rate Limits - token buckets and daily quotas for MCP calls
+//
+// Limits come from the policy: a bucket per agent_id (all of its calls), a
+// bucket per (role, MCP server, operation) shared by every agent with that
+// role, and a daily quota per agent for expensive operations. A call must
+// pass every check before any of them is charged, so a throttled call costs
+// nothing. Quota usage lives in memory and resets at 00:00 UTC.
+
+use chrono::{DateTime, NaiveDate, Utc};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Instant;
+
+use crate::policy::RateLimit;
+
+/// One token bucket a call draws from
+pub struct BucketCheck {
+    pub key: String,   // Identifies the bucket across calls
+    pub limit: RateLimit,
+    pub scope: String, // Human-readable, for the denial reason
+}
+
+/// A daily quota a call counts against
+pub struct QuotaCheck {
+    pub key: String,
+    pub per_day: u32,
+    pub scope: String,
+}
+
+/// Why a call was throttled, and when it may be retried
+#[derive(Debug, Clone)]
+pub struct Throttle {
+    pub reason: String,
+    pub retry_after_secs: u64,
+}
+
+struct TokenBucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    fn refill(&mut self, limit: &RateLimit, now: Instant) {
+        let elapsed = now.duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * limit.per_second()).min(limit.capacity() as f64);
+        self.updated = now;
+    }
+
+    /// Seconds until a whole token is available
+    fn wait_secs(&self, limit: &RateLimit) -> u64 {
+        ((1.0 - self.tokens) / limit.per_second()).ceil().max(1.0) as u64
+    }
+}
+
+#[derive(Default)]
+struct LimiterState {
+    buckets: HashMap<String, TokenBucket>,
+    usage: HashMap<String, (NaiveDate, u32)>, // quota key -> (day, calls that day)
+}
+
+#[derive(Default)]
+pub struct RateLimiter {
+    state: Mutex<LimiterState>,
+}
+
+impl RateLimiter {
+    /// Admit a call against `buckets` and `quota`, charging them only if all pass
+    pub fn check(&self, buckets: &[BucketCheck], quota: Option<&QuotaCheck>) -> Result<(), Throttle> {
+        let now = Instant::now();
+        let utc_now = Utc::now();
+        let today = utc_now.date_naive();
+        let mut state = self.state.lock().unwrap();
+
+        for check in buckets {
+            let bucket = state.buckets.entry(check.key.clone()).or_insert_with(|| TokenBucket {
+                tokens: check.limit.capacity() as f64,
+                updated: now,
+            });
+            bucket.refill(&check.limit, now);
+            if bucket.tokens < 1.0 {
+                return Err(Throttle {
+                    reason: format!("rate limit exceeded: {} ({}/min)", check.scope, check.limit.per_minute),
+                    retry_after_secs: bucket.wait_secs(&check.limit),
+                });
+            }
+        }
+
+        if let Some(quota) = quota {
+            let used = state.usage.get(&quota.key)
+                .filter(|(day, _)| *day == today)
+                .map_or(0, |(_, calls)| *calls);
+            if used >= quota.per_day {
+                return Err(Throttle {
+                    reason: format!("daily quota exceeded: {} ({}/day)", quota.scope, quota.per_day),
+                    retry_after_secs: secs_until_next_day(utc_now),
+                });
+            }
+            state.usage.insert(quota.key.clone(), (today, used + 1));
+        }
+
+        for check in buckets {
+            if let Some(bucket) = state.buckets.get_mut(&check.key) {
+                bucket.tokens -= 1.0;
+            }
+        }
+        Ok(())
+    }
+
+    /// Forget full buckets and past days' usage
+    pub fn prune(&self, idle_secs: u64) {
+        let today = Utc::now().date_naive();
+        let mut state = self.state.lock().unwrap();
+        state.buckets.retain(|_, b| b.updated.elapsed().as_secs() < idle_secs);
+        state.usage.retain(|_, (day, _)| *day == today);
+    }
+}
+
+fn secs_until_next_day(now: DateTime<Utc>) -> u64 {
+    let tomorrow = now.date_naive().succ_opt().and_then(|d| d.and_hms_opt(0, 0, 0));
+    tomorrow.map_or(1, |t| (t.and_utc() - now).num_seconds().max(1) as u64)
+}
@@ -4,15 +4,77 @@
 // Architecture: Zero-trust gateway that validates all agent-to-MCP communication
 // Uses JWT tokens + API key validation with role-based access control (RBAC)
 
+mod agents;
+mod approvals;
+mod audit;
+mod delegation;
+mod keys;
+mod limits;
+mod metrics;
+mod policy;
+mod refresh;
+mod revocations;
+mod upstream;
+
 use actix_web::{web, App, HttpServer, HttpRequest, HttpResponse, middleware};
 use actix_web::web::Data;
-use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation, Algorithm};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::sync::RwLock;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
 use chrono::{Utc, Duration};
 use uuid::Uuid;
 use log::{info, warn, error};
+use mcp_common::auth::{self as assertions, GatewayAssertion, ASSERTION_HEADER, ASSERTION_ISSUER, ASSERTION_TTL_SECS};
+use mcp_common::health::Check;
+use mcp_common::telemetry::{self, KeyValue};
+use mcp_common::mcp::{jsonrpc_error, INTERNAL_ERROR, INVALID_PARAMS, INVALID_REQUEST, METHOD_NOT_FOUND, PARSE_ERROR};
+use mcp_common::tls::{self, TlsSettings};
+
+use agents::{AgentError, AgentStore};
+use approvals::{Approval, ApprovalError, ApprovalStatus, ApprovalStore, Transport, MAX_PENDING_PER_AGENT};
+use delegation::{ScopeGrant, DEFAULT_DELEGATED_TTL_SECS, MAX_SCOPE_GRANTS, MAX_TASK_ID_LEN};
+use keys::KeyStore;
+use limits::{BucketCheck, QuotaCheck, RateLimiter, Throttle};
+use metrics::{Decision, Metrics, UNKNOWN_LABEL};
+use refresh::{RefreshError, RefreshStore, REFRESH_TOKEN_TTL_SECS};
+use policy::{
+    ApprovalPolicy, CircuitBreakerPolicy, LimitsPolicy, Policy, RateLimit, RetryPolicy,
+    DEFAULT_MAX_PAYLOAD_BYTES, DEFAULT_UPSTREAM_TIMEOUT_SECS, MAX_REQUEST_BYTES, MAX_UPSTREAM_TIMEOUT_SECS,
+};
+use revocations::{Revocation, RevocationList};
+use upstream::{BreakerState, CallError, CircuitBreakers, Upstream};
+use audit::{AuditFilter, AuditQuery, AuditStore, ExportFormat, DEFAULT_PAGE_SIZE, MAX_PAGE_SIZE};
+
+/// Lifetime of an issued access token, in seconds
+const TOKEN_TTL_SECS: i64 = 3600;
+
+/// How often expired revocations and retired keys are pruned
+const REVOCATION_PRUNE_INTERVAL_SECS: u64 = 60;
+
+/// How often the policy file, keys directory and agent registry are checked for changes
+const CONFIG_POLL_INTERVAL_SECS: u64 = 5;
+
+/// JSON-RPC error code for a tools/call the caller's role may not make
+const JSONRPC_FORBIDDEN: i64 = -32003;
+
+/// JSON-RPC error code for a tools/call refused by a rate limit or quota
+const JSONRPC_THROTTLED: i64 = -32029;
+
+/// JSON-RPC error code for a tools/call parked until an approver signs it off
+const JSONRPC_APPROVAL_REQUIRED: i64 = -32202;
+
+/// Rate limit buckets idle this long are full again and can be forgotten
+const RATE_LIMIT_IDLE_SECS: u64 = 3600;
+
+/// How long /ready waits on each MCP server's /ready, which runs its own checks
+const UPSTREAM_PROBE_TIMEOUT_SECS: u64 = 15;
+
+/// How long a /ready result is reused before the MCP servers are probed again
+const READY_CACHE_SECS: u64 = 5;
+
+/// target_mcp of audit entries for admin API changes, which reach no MCP server
+const ADMIN_AUDIT_TARGET: &str = "agent_gateway";
 
 // ─── Data Structures ──────────────────────────────────────────────────────────
 
@@ -22,8 +84,27 @@ pub struct Claims {
     pub role: AgentRole,      // Role determines MCP server access
     pub exp: usize,           // Expiry timestamp
     pub iat: usize,           // Issued at
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub iat_ms: Option<i64>,  // Issued at, in milliseconds, for revocation cutoffs
     pub jti: String,          // JWT ID for revocation
     pub allowed_mcps: Vec<String>, // Specific MCP servers this agent can call
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub task_id: Option<String>,   // Set on delegated tokens: the task they were minted for
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scope: Option<Vec<ScopeGrant>>, // Delegated tokens: the only calls they may make
+}
+
+impl Claims {
+    /// Whether this is a task-scoped token minted at /auth/delegate
+    pub fn is_delegated(&self) -> bool {
+        self.task_id.is_some()
+    }
+
+    /// When the token was issued, in milliseconds; a token without `iat_ms`
+    /// counts as issued at the end of its second
+    pub fn issued_at_ms(&self) -> i64 {
+        self.iat_ms.unwrap_or(self.iat as i64 * 1000 + 999)
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Hash)]
@@ -34,6 +115,17 @@ pub enum AgentRole {
     ReadOnly,       // Audit/monitoring only
 }
 
+impl AgentRole {
+    /// Serialized name, as used in tokens and the policy file
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AgentRole::Orchestrator => "orchestrator",
+            AgentRole::Modernizer => "modernizer",
+            AgentRole::ReadOnly => "read_only",
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Hash)]
 pub struct TokenRequest {
     pub agent_id: String,
@@ -46,8 +138,77 @@ pub struct TokenResponse {
     pub access_token: String,
     pub token_type: String,
     pub expires_in: i64,
+    pub refresh_token: String,     // Single use; redeem at /auth/refresh
+    pub refresh_expires_in: i64,
+    pub agent_id: String,
+    pub role: AgentRole,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Hash)]
+pub struct RefreshRequest {
+    pub refresh_token: String,
+}
+
+/// Mint a token for one task, limited to `scope`
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Hash)]
+pub struct DelegateRequest {
+    pub task_id: String,
+    pub scope: Vec<ScopeGrant>,
+    pub ttl_secs: Option<i64>, // Capped by the caller's own token expiry
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Hash)]
+pub struct DelegateResponse {
+    pub access_token: String,
+    pub token_type: String,
+    pub expires_in: i64,
+    pub jti: String,
+    pub task_id: String,
+    pub scope: Vec<ScopeGrant>,
+}
+
+/// Register an agent with an initial API key
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Hash)]
+pub struct RegisterAgentRequest {
     pub agent_id: String,
     pub role: AgentRole,
+    pub description: Option<String>,
+    pub key_ttl_secs: Option<i64>, // No expiry if unset
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Hash)]
+pub struct AssignRoleRequest {
+    pub role: AgentRole,
+}
+
+/// Issue or rotate an agent's API key
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Hash)]
+pub struct IssueKeyRequest {
+    pub ttl_secs: Option<i64>,   // No expiry if unset
+    pub grace_secs: Option<i64>, // Rotation only: how long the old keys keep working (default 0)
+}
+
+/// Query parameters accepted by GET /approvals
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct ApprovalQuery {
+    pub status: Option<ApprovalStatus>,
+}
+
+/// Approve or reject a parked call
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Hash)]
+pub struct DecisionRequest {
+    pub comment: Option<String>, // Kept with the approval and in the gateway log
+}
+
+/// Revoke a single token (`jti`), every token of an agent (`agent_id`), the
+/// delegated tokens of a task (`task_id`, with `agent_id` defaulting to the
+/// caller), or every token issued before a point in time (`issued_before` alone).
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Hash)]
+pub struct RevokeRequest {
+    pub jti: Option<String>,
+    pub agent_id: Option<String>,
+    pub task_id: Option<String>,
+    pub issued_before: Option<String>, // RFC 3339; defaults to now for agent_id
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Hash)]
@@ -71,12 +232,75 @@ pub struct McpProxyResponse {
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Hash)]
 pub struct AuditEntry {
+    pub seq: u64,                 // Position in the hash chain (1-based)
     pub timestamp: String,
     pub agent_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub task_id: Option<String>,  // Task of the delegated token the call was made with
     pub target_mcp: String,
     pub operation: String,
     pub authorized: bool,
     pub request_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub trace_id: Option<String>, // OpenTelemetry trace the call was part of
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub denial_reason: Option<String>, // Why an unauthorized call was refused
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub approval_id: Option<String>,     // Calls that needed human sign-off
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub approval_status: Option<String>, // pending, approved, rejected or expired
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub approver: Option<String>,        // Who approved or rejected the call
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub subject: Option<String>,  // Agent (and its key or new role) an admin action changed
+    pub prev_hash: String,        // Hash of the preceding entry
+    pub hash: String,             // SHA-256 over this entry with `hash` empty
+}
+
+impl AuditEntry {
+    /// New, not yet chained entry; `AuditStore::append` fills in seq and hashes.
+    /// The call counts as authorized unless there is a `denial_reason`.
+    pub fn new(
+        claims: &Claims,
+        target_mcp: &str,
+        operation: &str,
+        request_id: &str,
+        denial_reason: Option<String>,
+    ) -> Self {
+        AuditEntry {
+            seq: 0,
+            timestamp: Utc::now().to_rfc3339(),
+            agent_id: claims.sub.clone(),
+            task_id: claims.task_id.clone(),
+            target_mcp: target_mcp.to_string(),
+            operation: operation.to_string(),
+            authorized: denial_reason.is_none(),
+            request_id: request_id.to_string(),
+            trace_id: telemetry::current_trace_id(),
+            denial_reason,
+            approval_id: None,
+            approval_status: None,
+            approver: None,
+            subject: None,
+            prev_hash: String::new(),
+            hash: String::new(),
+        }
+    }
+
+    /// New entry for a change made through the admin API, e.g. `register_agent`
+    pub fn admin(admin: &Claims, action: &str, subject: String) -> Self {
+        let mut entry = AuditEntry::new(admin, ADMIN_AUDIT_TARGET, action, &Uuid::new_v4().to_string(), None);
+        entry.subject = Some(subject);
+        entry
+    }
+
+    /// Tie the entry to an approval, in the state the entry records
+    pub fn with_approval(mut self, approval: &Approval, status: ApprovalStatus) -> Self {
+        self.approval_id = Some(approval.approval_id.clone());
+        self.approval_status = Some(status.as_str().to_string());
+        self.approver = approval.decided_by.clone();
+        self
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Hash)]
@@ -84,7 +308,30 @@ pub struct HealthResponse {
     pub status: String,
     pub version: String,
     pub registered_mcps: Vec<String>,
-    pub active_tokens: usize,
+    pub active_tokens: usize,  // Issued access tokens not yet expired or revoked
+    pub revoked_tokens: usize, // Individually revoked tokens not yet expired
+    pub circuit_breakers: BTreeMap<String, BreakerState>, // MCP server -> breaker state
+}
+
+/// GET /ready - the gateway is ready when every registered MCP server is
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct ReadinessResponse {
+    pub status: String, // "ready" or "not_ready"
+    pub version: String,
+    pub upstreams: Vec<UpstreamStatus>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct UpstreamStatus {
+    pub name: String,
+    pub url: String,
+    pub status: String,          // "ready", "not_ready" or "unreachable"
+    pub version: Option<String>, // As reported by the server
+    pub latency_ms: u64,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub checks: Vec<Check>,      // The server's own dependency checks
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
 }
 
 // ─── MCP Registry ─────────────────────────────────────────────────────────────
@@ -93,75 +340,96 @@ pub struct HealthResponse {
 pub struct McpRegistry {
     // mcp_name -> (url, allowed_roles_and_operations)
     servers: HashMap<String, McpServer>,
+    agent_limits: LimitsPolicy,
+    client_certs: HashMap<String, String>, // certificate name -> agent_id
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct McpServer {
     pub url: String,
     pub allowed_operations: HashMap<AgentRole, Vec<String>>,
+    pub rate_limits: HashMap<String, HashMap<AgentRole, RateLimit>>, // operation -> role -> limit
+    pub daily_quotas: HashMap<String, HashMap<AgentRole, u32>>,      // operation -> role -> calls/day
+    #[serde(skip)]
+    pub payload_rules: HashMap<String, PayloadRule>,                 // operation -> payload checks
+    pub timeout_secs: u64,
+    pub retry: RetryPolicy,
+    pub circuit_breaker: CircuitBreakerPolicy,
+    pub idempotent_operations: HashSet<String>,                      // Retried when unreachable
+    #[serde(skip)]
+    pub approvals: HashMap<String, ApprovalPolicy>,                  // operation -> sign-off required
 }
 
-impl McpRegistry {
-    pub fn new() -> Self {
-        let mut servers = HashMap::new();
-
-        // S3 MCP: Handles all AWS S3 operations
-        let mut s3_ops: HashMap<AgentRole, Vec<String>> = HashMap::new();
-        s3_ops.insert(AgentRole::Orchestrator, vec![
-            "fetch_source".to_string(),
-            "fetch_data".to_string(),
-            "save_output".to_string(),
-            "generate_presigned_url".to_string(),
-            "list_objects".to_string(),
-        ]);
-        s3_ops.insert(AgentRole::ReadOnly, vec!["list_objects".to_string()]);
-        servers.insert("s3_mcp".to_string(), McpServer {
-            url: std::env::var("S3_MCP_URL").unwrap_or("http://s3-mcp:8081".to_string()),
-            allowed_operations: s3_ops,
-        });
+/// What an operation's payload must satisfy before it is forwarded
+#[derive(Debug, Clone)]
+pub struct PayloadRule {
+    pub max_bytes: usize,
+    pub schema: Option<Arc<jsonschema::Validator>>,
+    pub resources: BTreeMap<String, Vec<String>>, // payload field -> allowed values
+}
 
-        // AI MCP: AI translation operations
-        let mut ai_ops: HashMap<AgentRole, Vec<String>> = HashMap::new();
-        ai_ops.insert(AgentRole::Orchestrator, vec![
-            "translate_cobol".to_string(),
-            "translate_assembler".to_string(),
-            "explain_code".to_string(),
-        ]);
-        ai_ops.insert(AgentRole::Modernizer, vec![
-            "translate_cobol".to_string(),
-            "translate_assembler".to_string(),
-        ]);
-        servers.insert("ai_mcp".to_string(), McpServer {
-            url: std::env::var("AI_MCP_URL").unwrap_or("http://ai-mcp:8082".to_string()),
-            allowed_operations: ai_ops,
-        });
+/// Why a payload was rejected at the gateway
+pub enum PayloadError {
+    TooLarge { size: usize, max: usize },
+    SchemaViolations(Vec<serde_json::Value>), // {path, message} per violation
+}
 
-        // COBOL Compiler MCP: GnuCOBOL compilation & execution
-        let mut cobol_ops: HashMap<AgentRole, Vec<String>> = HashMap::new();
-        cobol_ops.insert(AgentRole::Orchestrator, vec![
-            "compile".to_string(),
-            "execute".to_string(),
-            "validate_syntax".to_string(),
-        ]);
-        servers.insert("cobol_mcp".to_string(), McpServer {
-            url: std::env::var("COBOL_MCP_URL").unwrap_or("http://cobol-mcp:8083".to_string()),
-            allowed_operations: cobol_ops,
-        });
+impl std::fmt::Display for PayloadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            PayloadError::TooLarge { size, max } => {
+                write!(f, "payload is {} bytes, over the {} byte limit", size, max)
+            }
+            PayloadError::SchemaViolations(_) => write!(f, "payload does not match the operation's schema"),
+        }
+    }
+}
 
-        // Rust Compiler MCP: Cargo compilation & execution
-        let mut rust_ops: HashMap<AgentRole, Vec<String>> = HashMap::new();
-        rust_ops.insert(AgentRole::Orchestrator, vec![
-            "compile".to_string(),
-            "execute".to_string(),
-            "cargo_check".to_string(),
-            "clippy".to_string(),
-        ]);
-        servers.insert("rust_mcp".to_string(), McpServer {
-            url: std::env::var("RUST_MCP_URL").unwrap_or("http://rust-mcp:8084".to_string()),
-            allowed_operations: rust_ops,
-        });
+impl McpRegistry {
+    /// Build the registry from a validated policy
+    pub fn from_policy(policy: &Policy) -> Self {
+        let servers = policy.servers.iter()
+            .map(|(name, server)| {
+                (name.clone(), McpServer {
+                    url: server.resolved_url(),
+                    allowed_operations: server.grants.clone(),
+                    rate_limits: server.operations.iter()
+                        .map(|(op, p)| (op.clone(), p.rate_limit.clone()))
+                        .collect(),
+                    daily_quotas: server.operations.iter()
+                        .map(|(op, p)| (op.clone(), p.daily_quota.clone()))
+                        .collect(),
+                    payload_rules: server.operations.iter()
+                        .map(|(op, p)| (op.clone(), PayloadRule {
+                            max_bytes: p.max_payload_bytes
+                                .or(server.max_payload_bytes)
+                                .unwrap_or(DEFAULT_MAX_PAYLOAD_BYTES),
+                            // Policy validation already compiled every schema once
+                            schema: p.schema.as_ref()
+                                .and_then(|s| jsonschema::validator_for(s).ok())
+                                .map(Arc::new),
+                            resources: p.resources.clone(),
+                        }))
+                        .collect(),
+                    timeout_secs: server.timeout_secs.unwrap_or(DEFAULT_UPSTREAM_TIMEOUT_SECS),
+                    retry: server.retry,
+                    circuit_breaker: server.circuit_breaker,
+                    idempotent_operations: server.operations.iter()
+                        .filter(|(_, p)| p.idempotent)
+                        .map(|(op, _)| op.clone())
+                        .collect(),
+                    approvals: server.operations.iter()
+                        .filter_map(|(op, p)| p.approval.clone().map(|a| (op.clone(), a)))
+                        .collect(),
+                })
+            })
+            .collect();
 
-        McpRegistry { servers }
+        McpRegistry {
+            servers,
+            agent_limits: policy.limits.clone(),
+            client_certs: policy.client_certs.clone().into_iter().collect(),
+        }
     }
 
     pub fn is_authorized(&self, role: &AgentRole, mcp: &str, operation: &str) -> bool {
@@ -177,41 +445,263 @@ impl McpRegistry {
         self.servers.get(mcp).map(|s| s.url.clone())
     }
 
+    /// How to forward a call to `mcp`; only idempotent calls get more than one attempt
+    pub fn upstream(&self, mcp: &str, idempotent: bool) -> Option<Upstream> {
+        self.servers.get(mcp).map(|server| Upstream {
+            mcp: mcp.to_string(),
+            url: server.url.clone(),
+            timeout: std::time::Duration::from_secs(server.timeout_secs),
+            retry: if idempotent {
+                server.retry
+            } else {
+                RetryPolicy { max_attempts: 1, ..server.retry }
+            },
+            breaker: server.circuit_breaker,
+        })
+    }
+
+    pub fn is_idempotent(&self, mcp: &str, operation: &str) -> bool {
+        self.servers.get(mcp).is_some_and(|s| s.idempotent_operations.contains(operation))
+    }
+
+    /// Circuit breaker state of every registered server
+    pub fn breaker_states(&self, breakers: &CircuitBreakers) -> BTreeMap<String, BreakerState> {
+        self.servers.iter()
+            .map(|(name, server)| (name.clone(), breakers.state(name, &server.circuit_breaker)))
+            .collect()
+    }
+
     pub fn list_servers(&self) -> Vec<String> {
         self.servers.keys().cloned().collect()
     }
+
+    /// Metric labels for a call: the names the policy declares, else "unknown"
+    pub fn metric_labels<'a>(&self, mcp: &'a str, operation: &'a str) -> (&'a str, &'a str) {
+        match self.servers.get(mcp) {
+            Some(server) if server.payload_rules.contains_key(operation) => (mcp, operation),
+            Some(_) => (mcp, UNKNOWN_LABEL),
+            None => (UNKNOWN_LABEL, UNKNOWN_LABEL),
+        }
+    }
+
+    /// Agent a client certificate belongs to, matched by its CN or DNS names in order
+    pub fn certificate_agent(&self, names: &[String]) -> Option<String> {
+        names.iter().find_map(|name| self.client_certs.get(name).cloned())
+    }
+
+    /// Check `payload` against the operation's size cap and JSON Schema
+    pub fn check_payload(&self, mcp: &str, operation: &str, payload: &serde_json::Value) -> Result<(), PayloadError> {
+        let Some(rule) = self.servers.get(mcp).and_then(|s| s.payload_rules.get(operation)) else {
+            return Ok(());
+        };
+
+        let size = serde_json::to_vec(payload).map_or(0, |b| b.len());
+        if size > rule.max_bytes {
+            return Err(PayloadError::TooLarge { size, max: rule.max_bytes });
+        }
+
+        if let Some(schema) = &rule.schema {
+            let violations = schema.iter_errors(payload)
+                .map(|e| serde_json::json!({
+                    "path": e.instance_path.to_string(),
+                    "message": e.to_string()
+                }))
+                .collect::<Vec<_>>();
+            if !violations.is_empty() {
+                return Err(PayloadError::SchemaViolations(violations));
+            }
+        }
+        Ok(())
+    }
+
+    /// Check the payload fields the operation constrains, e.g. the S3 `key`.
+    /// A constrained field must be present and match one of its allowed values.
+    pub fn check_resources(&self, mcp: &str, operation: &str, payload: &serde_json::Value) -> Result<(), String> {
+        let Some(rule) = self.servers.get(mcp).and_then(|s| s.payload_rules.get(operation)) else {
+            return Ok(());
+        };
+
+        for (field, patterns) in &rule.resources {
+            let allowed = || patterns.join(", ");
+            match payload.get(field).and_then(|v| v.as_str()) {
+                Some(value) if patterns.iter().any(|p| policy::resource_allows(p, value)) => {}
+                Some(value) => {
+                    return Err(format!("{} '{}' is outside the resources allowed for {} on {} ({})",
+                                       field, value, operation, mcp, allowed()));
+                }
+                None => {
+                    return Err(format!("{} on {} requires {} to be one of: {}", operation, mcp, field, allowed()));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The sign-off a call needs before it is forwarded, if any: the operation
+    /// has an approval policy and the payload matches every field in its `when`.
+    /// A field the payload lacks counts as a match, so omitting it skips nothing.
+    pub fn approval_for(&self, mcp: &str, operation: &str, payload: &serde_json::Value) -> Option<&ApprovalPolicy> {
+        let approval = self.servers.get(mcp)?.approvals.get(operation)?;
+        approval.when.iter()
+            .all(|(field, patterns)| match payload.get(field).and_then(|v| v.as_str()) {
+                Some(value) => patterns.iter().any(|p| policy::resource_allows(p, value)),
+                None => true,
+            })
+            .then_some(approval)
+    }
+
+    /// Token buckets and daily quota a call by `claims.sub` counts against
+    pub fn limits_for(&self, claims: &Claims, mcp: &str, operation: &str) -> (Vec<BucketCheck>, Option<QuotaCheck>) {
+        let mut buckets = Vec::new();
+        let agent_limit = self.agent_limits.agents.get(&claims.sub).or(self.agent_limits.agent_default.as_ref());
+        if let Some(limit) = agent_limit {
+            buckets.push(BucketCheck {
+                key: format!("agent:{}", claims.sub),
+                limit: *limit,
+                scope: format!("agent {}", claims.sub),
+            });
+        }
+
+        let Some(server) = self.servers.get(mcp) else { return (buckets, None) };
+        let role = claims.role.as_str();
+        if let Some(limit) = server.rate_limits.get(operation).and_then(|l| l.get(&claims.role)) {
+            buckets.push(BucketCheck {
+                key: format!("op:{}:{}:{}", role, mcp, operation),
+                limit: *limit,
+                scope: format!("role {} calling {} on {}", role, operation, mcp),
+            });
+        }
+        let quota = server.daily_quotas.get(operation)
+            .and_then(|q| q.get(&claims.role))
+            .map(|per_day| QuotaCheck {
+                key: format!("{}:{}:{}", claims.sub, mcp, operation),
+                per_day: *per_day,
+                scope: format!("agent {} calling {} on {}", claims.sub, operation, mcp),
+            });
+        (buckets, quota)
+    }
 }
 
 // ─── App State ────────────────────────────────────────────────────────────────
 
 pub struct AppState {
-    pub jwt_secret: String,
-    pub api_keys: RwLock<HashMap<String, (String, AgentRole)>>, // api_key -> (agent_id, role)
-    pub revoked_tokens: RwLock<Vec<String>>,                    // revoked JWT IDs
-    pub audit_log: RwLock<Vec<AuditEntry>>,
-    pub mcp_registry: McpRegistry,
+    pub keys: RwLock<KeyStore>,                                 // JWT signing/verification keys
+    pub agents: RwLock<AgentStore>,                             // Registered agents and hashed API keys
+    pub revoked_tokens: RwLock<RevocationList>,
+    pub issued_tokens: RwLock<HashMap<String, Claims>>, // jti -> claims, until the token expires
+    pub refresh_tokens: RefreshStore,           // Refresh token families, shared by replicas
+    pub audit: AuditStore,
+    pub mcp_registry: RwLock<Arc<McpRegistry>>, // swapped whole on policy reload
+    pub policy_path: Option<PathBuf>,
+    pub limiter: RateLimiter,                   // Rate limit and quota usage
+    pub verify_client_certs: bool,              // Listener asks for client certificates (mTLS)
+    pub metrics: Metrics,
+    pub http_client: reqwest::Client,           // Shared pool for MCP calls; timeouts are per call
+    pub breakers: CircuitBreakers,
+    pub approvals: ApprovalStore,               // Calls parked for human sign-off, shared by replicas
+    pub last_ready: tokio::sync::Mutex<Option<(std::time::Instant, ReadinessResponse)>>, // One probe round at a time
 }
 
 impl AppState {
-    pub fn new() -> Self {
-        let mut api_keys = HashMap::new();
-
-        // Pre-configured agent API keys (in production: use Kubernetes secrets)
+    pub fn new(tls: Option<&TlsSettings>) -> std::io::Result<Self> {
+        // Agent API keys the registry starts with on first run (in production: use Kubernetes secrets)
         let green_key = std::env::var("GREEN_AGENT_API_KEY")
             .unwrap_or("green-agent-dev-key-change-in-prod".to_string());
         let purple_key = std::env::var("PURPLE_AGENT_API_KEY")
             .unwrap_or("purple-agent-dev-key-change-in-prod".to_string());
+        let agents_path = std::env::var("AGENTS_PATH")
+            .unwrap_or("/var/lib/agent-gateway/agents.json".to_string());
+        let agents = AgentStore::open(agents_path, vec![
+            ("green_agent".to_string(), AgentRole::Orchestrator, green_key),
+            ("purple_agent".to_string(), AgentRole::Modernizer, purple_key),
+        ])?;
+
+        let policy_path = std::env::var("POLICY_PATH").ok().map(PathBuf::from);
+        let policy = Policy::load(policy_path.as_deref())
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+
+        let keys_dir = std::env::var("JWT_KEYS_DIR").ok().map(PathBuf::from);
+        let active_kid = std::env::var("JWT_ACTIVE_KID").ok();
+        let keys = KeyStore::load(keys_dir.as_deref(), active_kid.as_deref())
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+
+        let audit_path = std::env::var("AUDIT_LOG_PATH")
+            .unwrap_or("/var/lib/agent-gateway/audit.jsonl".to_string());
+        let revocations_path = std::env::var("REVOCATIONS_PATH")
+            .unwrap_or("/var/lib/agent-gateway/revocations.json".to_string());
+        let approvals_path = std::env::var("APPROVALS_PATH")
+            .unwrap_or("/var/lib/agent-gateway/approvals.json".to_string());
+        let refresh_tokens_path = std::env::var("REFRESH_TOKENS_PATH")
+            .unwrap_or("/var/lib/agent-gateway/refresh_tokens.json".to_string());
+
+        Ok(AppState {
+            keys: RwLock::new(keys),
+            agents: RwLock::new(agents),
+            revoked_tokens: RwLock::new(RevocationList::open(revocations_path)?),
+            issued_tokens: RwLock::new(HashMap::new()),
+            refresh_tokens: RefreshStore::open(refresh_tokens_path)?,
+            audit: AuditStore::open(audit_path)?,
+            mcp_registry: RwLock::new(Arc::new(McpRegistry::from_policy(&policy))),
+            policy_path,
+            limiter: RateLimiter::default(),
+            verify_client_certs: tls.is_some_and(|t| t.verifies_clients()),
+            metrics: Metrics::new().map_err(std::io::Error::other)?,
+            http_client: tls::http_client(std::time::Duration::from_secs(MAX_UPSTREAM_TIMEOUT_SECS))?,
+            breakers: CircuitBreakers::default(),
+            approvals: ApprovalStore::open(approvals_path)?,
+            last_ready: tokio::sync::Mutex::new(None),
+        })
+    }
+
+    /// Current registry; callers keep their snapshot even if a reload swaps it
+    pub fn registry(&self) -> Arc<McpRegistry> {
+        self.mcp_registry.read().unwrap().clone()
+    }
+
+    /// Issued access tokens that are neither expired nor revoked
+    pub fn active_tokens(&self) -> usize {
+        let now = Utc::now().timestamp() as usize;
+        let revoked = self.revoked_tokens.read().unwrap();
+        self.issued_tokens.read().unwrap()
+            .values()
+            .filter(|claims| claims.exp > now && !revoked.is_revoked(claims))
+            .count()
+    }
+
+    /// Remember an issued access token, so active tokens can be counted
+    fn record_issued(&self, claims: &Claims, kind: &str) {
+        self.issued_tokens.write().unwrap().insert(claims.jti.clone(), claims.clone());
+        self.metrics.token_issued(kind, claims.role.as_str());
+    }
+
+    /// Re-read the policy file, keeping the current registry if it is invalid
+    pub fn reload_policy(&self) {
+        match Policy::load(self.policy_path.as_deref()) {
+            Ok(policy) => {
+                let registry = McpRegistry::from_policy(&policy);
+                info!("📋 Policy reloaded, MCP servers: {:?}", registry.list_servers());
+                self.breakers.retain(|mcp| registry.get_server_url(mcp).is_some());
+                *self.mcp_registry.write().unwrap() = Arc::new(registry);
+            }
+            Err(e) => error!("Policy reload rejected, keeping previous policy: {}", e),
+        }
+    }
 
-        api_keys.insert(green_key, ("green_agent".to_string(), AgentRole::Orchestrator));
-        api_keys.insert(purple_key, ("purple_agent".to_string(), AgentRole::Modernizer));
+    /// Re-read the agent registry, keeping the current one if it is unreadable
+    pub fn reload_agents(&self) {
+        let mut agents = self.agents.write().unwrap();
+        match agents.reload() {
+            Ok(()) => info!("🪪 Agent registry reloaded"),
+            Err(e) => error!("Agent registry reload rejected, keeping previous agents: {}", e),
+        }
+    }
 
-        AppState {
-            jwt_secret: std::env::var("JWT_SECRET")
-                .unwrap_or("dev-secret-change-in-production-minimum-32-chars".to_string()),
-            api_keys: RwLock::new(api_keys),
-            revoked_tokens: RwLock::new(Vec::new()),
-            audit_log: RwLock::new(Vec::new()),
-            mcp_registry: McpRegistry::new(),
+    /// Re-read the keys directory, keeping the current keys if it is invalid
+    pub fn reload_keys(&self) {
+        let mut keys = self.keys.write().unwrap();
+        match keys.reload(Utc::now().timestamp()) {
+            Ok(()) => info!("🔑 Signing keys reloaded, active key '{}'", keys.active_kid()),
+            Err(e) => error!("Key reload rejected, keeping previous keys: {}", e),
         }
     }
 }
@@ -220,13 +710,13 @@ impl AppState {
 
 async fn issue_token(
     state: Data<AppState>,
+    http_req: HttpRequest,
     req: web::Json<TokenRequest>,
 ) -> HttpResponse {
-    let api_keys = state.api_keys.read().unwrap();
-
     // Validate API key and agent identity
-    let (agent_id, allowed_role) = match api_keys.get(&req.api_key) {
-        Some((id, role)) => (id.clone(), role.clone()),
+    let enrolled = state.agents.read().unwrap().authenticate(&req.api_key, Utc::now());
+    let (agent_id, allowed_role) = match enrolled {
+        Some(agent) => agent,
         None => {
             warn!("Invalid API key attempt for agent: {}", req.agent_id);
             return HttpResponse::Unauthorized().json(serde_json::json!({
@@ -245,6 +735,11 @@ async fn issue_token(
         }));
     }
 
+    // Over mTLS, the client certificate must belong to the same agent
+    if let Err(failure) = require_certificate_for(&http_req, &state, &agent_id) {
+        return failure.into_response();
+    }
+
     // Cannot escalate privileges beyond assigned role
     if req.requested_role != allowed_role {
         warn!("Role escalation attempt by {}: requested {:?}, allowed {:?}",
@@ -255,17 +750,103 @@ async fn issue_token(
         }));
     }
 
+    issue_tokens(&state, agent_id, allowed_role, None).await
+}
+
+/// Redeem a refresh token for a new access token and refresh token
+async fn refresh_token(
+    state: Data<AppState>,
+    http_req: HttpRequest,
+    req: web::Json<RefreshRequest>,
+) -> HttpResponse {
+    let certificate_agent = match certificate_agent(&http_req, &state) {
+        Ok(agent) => agent,
+        Err(failure) => return failure.into_response(),
+    };
+
+    let refresh_tokens = state.refresh_tokens.clone();
+    let token = req.into_inner().refresh_token;
+    let redeemed = web::block(move || {
+        // Checked before redeeming: a stolen token presented without its agent's
+        // certificate is neither burned nor allowed to revoke the family
+        if let (Some(agent), Some(holder)) = (&certificate_agent, refresh_tokens.holder(&token)?) {
+            if *agent != holder {
+                return Ok(Err(holder));
+            }
+        }
+        refresh_tokens.redeem(&token, Utc::now().timestamp()).map(Ok)
+    }).await.unwrap_or_else(|e| Err(RefreshError::Storage(e.to_string())));
+    let grant = match redeemed {
+        Ok(Ok(grant)) => grant,
+        Ok(Err(holder)) => {
+            warn!("Refresh rejected: client certificate does not belong to agent {}", holder);
+            return AuthFailure::Certificate(format!(
+                "Client certificate does not belong to agent {}", holder)).into_response();
+        }
+        Err(e) => {
+            match &e {
+                RefreshError::Reused { agent_id } => {
+                    warn!("Refresh token REUSED for agent: {}; revoked its token family", agent_id);
+                }
+                RefreshError::Storage(_) => {
+                    error!("Refresh failed: {}", e);
+                    return HttpResponse::ServiceUnavailable().json(serde_json::json!({
+                        "error": e.to_string(),
+                        "code": "REFRESH_STORE_UNAVAILABLE"
+                    }));
+                }
+                _ => warn!("Refresh rejected: {}", e),
+            }
+            return HttpResponse::Unauthorized().json(serde_json::json!({
+                "error": e.to_string(),
+                "code": "INVALID_REFRESH_TOKEN"
+            }));
+        }
+    };
+
+    // Another replica may have revoked the agent's tokens since this one was issued
+    if state.revoked_tokens.read().unwrap().revokes_login(&grant.agent_id, grant.issued_at_ms) {
+        warn!("Refresh rejected: tokens of agent {} were revoked", grant.agent_id);
+        return HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": RefreshError::Unknown.to_string(),
+            "code": "INVALID_REFRESH_TOKEN"
+        }));
+    }
+
+    // The agent must still hold an API key for the role it logged in with
+    let enrolled = state.agents.read().unwrap().is_enrolled(&grant.agent_id, &grant.role, Utc::now());
+    if !enrolled {
+        warn!("Refresh rejected: agent {} no longer has role {:?}", grant.agent_id, grant.role);
+        return HttpResponse::Unauthorized().json(serde_json::json!({
+            "error": "Agent is no longer enrolled for this role",
+            "code": "INVALID_REFRESH_TOKEN"
+        }));
+    }
+
+    issue_tokens(&state, grant.agent_id, grant.role, Some(grant.family)).await
+}
+
+/// Sign an access token and issue a refresh token for an authenticated agent
+async fn issue_tokens(
+    state: &AppState,
+    agent_id: String,
+    allowed_role: AgentRole,
+    family: Option<String>, // Set when rotating a refresh token
+) -> HttpResponse {
+    let kind = if family.is_some() { "refresh" } else { "login" };
+
     // Determine allowed MCP servers for this role
-    let allowed_mcps = state.mcp_registry.list_servers()
+    let registry = state.registry();
+    let allowed_mcps = registry.list_servers()
         .into_iter()
         .filter(|mcp| {
             // Check if this role has ANY access to this MCP
-            state.mcp_registry.mcp_registry_has_role(mcp, &allowed_role)
+            registry.mcp_registry_has_role(mcp, &allowed_role)
         })
         .collect::<Vec<_>>();
 
     let now = Utc::now();
-    let expiry = now + Duration::hours(1);
+    let expiry = now + Duration::seconds(TOKEN_TTL_SECS);
     let jti = Uuid::new_v4().to_string();
 
     let claims = Claims {
@@ -273,124 +854,690 @@ async fn issue_token(
         role: allowed_role.clone(),
         exp: expiry.timestamp() as usize,
         iat: now.timestamp() as usize,
+        iat_ms: Some(now.timestamp_millis()),
         jti: jti.clone(),
         allowed_mcps: allowed_mcps.clone(),
+        task_id: None,
+        scope: None,
+    };
+
+    let token = match state.keys.read().unwrap().sign(&claims) {
+        Ok(t) => t,
+        Err(e) => {
+            error!("{}", e);
+            return HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": e,
+                "code": "SIGNING_FAILED"
+            }));
+        }
     };
 
-    let token = encode(
-        &Header::default(),
-        &claims,
-        &EncodingKey::from_secret(state.jwt_secret.as_bytes()),
-    ).unwrap();
+    let refresh_tokens = state.refresh_tokens.clone();
+    let (holder, role) = (agent_id.clone(), allowed_role.clone());
+    let issued = web::block(move || refresh_tokens.issue(&holder, &role, family, now)).await;
+    let refresh_token = match issued.unwrap_or_else(|e| Err(e.to_string())) {
+        Ok(t) => t,
+        Err(e) => {
+            error!("{}", e);
+            return HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": e,
+                "code": "SIGNING_FAILED"
+            }));
+        }
+    };
+    state.record_issued(&claims, kind);
 
     info!("Token issued for agent: {} with role: {:?}", agent_id, allowed_role);
 
     HttpResponse::Ok().json(TokenResponse {
         access_token: token,
         token_type: "Bearer".to_string(),
-        expires_in: 3600,
+        expires_in: TOKEN_TTL_SECS,
+        refresh_token,
+        refresh_expires_in: REFRESH_TOKEN_TTL_SECS,
         agent_id,
         role: allowed_role,
     })
 }
 
-// ─── MCP Proxy with AuthZ ─────────────────────────────────────────────────────
-
-async fn proxy_mcp_request(
+/// POST /auth/delegate - mint a token for one task, limited to the listed
+/// operations (a subset of the caller's own grants) and expiring no later than
+/// the caller's token
+async fn delegate_token(
     state: Data<AppState>,
     http_req: HttpRequest,
-    body: web::Json<McpProxyRequest>,
+    req: web::Json<DelegateRequest>,
 ) -> HttpResponse {
-    let request_id = Uuid::new_v4().to_string();
+    let claims = match authenticate(&http_req, &state) {
+        Ok(c) => c,
+        Err(failure) => return failure.into_response(),
+    };
 
-    // Extract and validate Bearer token
-    let token = match extract_bearer_token(&http_req) {
-        Some(t) => t,
-        None => {
-            return HttpResponse::Unauthorized().json(serde_json::json!({
-                "error": "Missing Authorization header",
-                "code": "NO_TOKEN"
-            }));
-        }
+    if claims.is_delegated() {
+        warn!("Delegation DENIED: agent={} tried to delegate from a task token", claims.sub);
+        return HttpResponse::Forbidden().json(serde_json::json!({
+            "error": "Delegated tokens cannot delegate further",
+            "code": "DELEGATION_DENIED"
+        }));
+    }
+
+    let invalid = |error: String| HttpResponse::BadRequest().json(serde_json::json!({
+        "error": error,
+        "code": "INVALID_REQUEST"
+    }));
+    if !delegation::is_valid_task_id(&req.task_id) {
+        return invalid(format!(
+            "task_id must be 1-{} characters of letters, digits, '-', '_', '.' or ':'", MAX_TASK_ID_LEN));
+    }
+    if req.scope.is_empty() || req.scope.len() > MAX_SCOPE_GRANTS {
+        return invalid(format!("scope must list between 1 and {} operations", MAX_SCOPE_GRANTS));
+    }
+    let ttl = req.ttl_secs.unwrap_or(DEFAULT_DELEGATED_TTL_SECS);
+    if !(1..=TOKEN_TTL_SECS).contains(&ttl) {
+        return invalid(format!("ttl_secs must be between 1 and {}", TOKEN_TTL_SECS));
+    }
+
+    // A delegated token can only narrow what the caller may already do
+    let registry = state.registry();
+    if let Some(grant) = req.scope.iter()
+        .find(|g| !registry.is_authorized(&claims.role, &g.target_mcp, &g.operation))
+    {
+        warn!("Delegation DENIED: agent={} role={:?} cannot delegate {} on {}",
+              claims.sub, claims.role, grant.operation, grant.target_mcp);
+        return HttpResponse::Forbidden().json(serde_json::json!({
+            "error": format!("Role {:?} is not authorized to call {} on {}",
+                             claims.role, grant.operation, grant.target_mcp),
+            "code": "DELEGATION_DENIED"
+        }));
+    }
+
+    let now = Utc::now();
+    let exp = ((now + Duration::seconds(ttl)).timestamp() as usize).min(claims.exp);
+    let mut allowed_mcps = req.scope.iter().map(|g| g.target_mcp.clone()).collect::<Vec<_>>();
+    allowed_mcps.sort();
+    allowed_mcps.dedup();
+
+    let delegated = Claims {
+        sub: claims.sub.clone(),
+        role: claims.role.clone(),
+        exp,
+        iat: now.timestamp() as usize,
+        iat_ms: Some(now.timestamp_millis()),
+        jti: Uuid::new_v4().to_string(),
+        allowed_mcps,
+        task_id: Some(req.task_id.clone()),
+        scope: Some(req.scope.clone()),
     };
 
-    let claims = match validate_token(&token, &state) {
-        Ok(c) => c,
+    let token = match state.keys.read().unwrap().sign(&delegated) {
+        Ok(t) => t,
         Err(e) => {
-            warn!("Token validation failed: {}", e);
-            return HttpResponse::Unauthorized().json(serde_json::json!({
+            error!("{}", e);
+            return HttpResponse::InternalServerError().json(serde_json::json!({
                 "error": e,
-                "code": "INVALID_TOKEN"
+                "code": "SIGNING_FAILED"
             }));
         }
     };
 
-    // Authorization check: can this agent call this MCP with this operation?
-    let authorized = state.mcp_registry.is_authorized(
-        &claims.role,
-        &body.target_mcp,
-        &body.operation,
-    );
-
-    // Record in audit log regardless of outcome
-    let audit_entry = AuditEntry {
-        timestamp: Utc::now().to_rfc3339(),
-        agent_id: claims.sub.clone(),
-        target_mcp: body.target_mcp.clone(),
-        operation: body.operation.clone(),
-        authorized,
-        request_id: request_id.clone(),
+    state.record_issued(&delegated, "delegated");
+    info!("Delegated token issued: agent={} task={} ops={}", claims.sub, req.task_id, req.scope.len());
+
+    HttpResponse::Ok().json(DelegateResponse {
+        access_token: token,
+        token_type: "Bearer".to_string(),
+        expires_in: exp as i64 - now.timestamp(),
+        jti: delegated.jti,
+        task_id: req.task_id.clone(),
+        scope: req.scope.clone(),
+    })
+}
+
+// ─── Token Revocation ─────────────────────────────────────────────────────────
+
+async fn revoke_token(
+    state: Data<AppState>,
+    http_req: HttpRequest,
+    body: web::Json<RevokeRequest>,
+) -> HttpResponse {
+    let claims = match authenticate(&http_req, &state) {
+        Ok(c) => c,
+        Err(failure) => return failure.into_response(),
     };
 
-    {
-        let mut log = state.audit_log.write().unwrap();
-        log.push(audit_entry.clone());
-        // Keep only last 10000 entries in memory
-        if log.len() > 10000 {
-            log.drain(0..1000);
+    let issued_before = match body.issued_before.as_deref().map(chrono::DateTime::parse_from_rfc3339) {
+        Some(Ok(t)) => Some(t.timestamp_millis().max(0)),
+        Some(Err(e)) => {
+            return HttpResponse::BadRequest().json(serde_json::json!({
+                "error": format!("Invalid issued_before timestamp: {}", e),
+                "code": "INVALID_REQUEST"
+            }));
         }
-    }
-
-    if !authorized {
-        warn!("AuthZ DENIED: agent={} role={:?} mcp={} op={}",
-              claims.sub, claims.role, body.target_mcp, body.operation);
-        return HttpResponse::Forbidden().json(McpProxyResponse {
-            success: false,
-            request_id,
-            agent_id: claims.sub,
-            target_mcp: body.target_mcp.clone(),
-            operation: body.operation.clone(),
-            result: None,
-            error: Some(format!(
-                "Role {:?} is not authorized to call {} on {}",
-                claims.role, body.operation, body.target_mcp
-            )),
-            audit_trail: audit_entry,
-        });
-    }
+        None => None,
+    };
 
-    // Forward to MCP server
-    let mcp_url = match state.mcp_registry.get_server_url(&body.target_mcp) {
-        Some(url) => url,
-        None => {
-            return HttpResponse::NotFound().json(McpProxyResponse {
-                success: false,
-                request_id,
-                agent_id: claims.sub,
-                target_mcp: body.target_mcp.clone(),
-                operation: body.operation.clone(),
-                result: None,
-                error: Some(format!("MCP server '{}' not registered", body.target_mcp)),
-                audit_trail: audit_entry,
-            });
+    let now = Utc::now();
+    let now_ms = now.timestamp_millis();
+    let now = now.timestamp() as usize;
+    // Task tokens act for their task only, so they too may only revoke their own tokens
+    let is_orchestrator = claims.role == AgentRole::Orchestrator && !claims.is_delegated();
+
+    // Non-orchestrators may only revoke their own tokens
+    let revocation = match (&body.jti, &body.agent_id, &body.task_id, issued_before) {
+        (Some(jti), None, None, None) => {
+            if !is_orchestrator && *jti != claims.jti {
+                return forbidden_revocation(&claims);
+            }
+            // A token this replica did not issue has an unknown exp; a full TTL
+            // from now is an upper bound
+            let exp = if *jti == claims.jti {
+                claims.exp
+            } else {
+                state.issued_tokens.read().unwrap().get(jti)
+                    .map_or(now + TOKEN_TTL_SECS as usize, |issued| issued.exp)
+            };
+            Revocation::Jti { jti: jti.clone(), exp }
+        }
+        (None, agent_id, Some(task_id), None) => {
+            let agent_id = agent_id.as_ref().unwrap_or(&claims.sub);
+            if !is_orchestrator && *agent_id != claims.sub {
+                return forbidden_revocation(&claims);
+            }
+            // Covers every token issued up to and including this millisecond
+            Revocation::Task { agent_id: agent_id.clone(), task_id: task_id.clone(), issued_before_ms: now_ms + 1 }
+        }
+        (None, Some(agent_id), None, cutoff) => {
+            if !is_orchestrator && *agent_id != claims.sub {
+                return forbidden_revocation(&claims);
+            }
+            Revocation::Agent { agent_id: agent_id.clone(), issued_before_ms: cutoff.unwrap_or(now_ms + 1) }
+        }
+        (None, None, None, Some(cutoff)) => {
+            if !is_orchestrator {
+                return forbidden_revocation(&claims);
+            }
+            Revocation::All { issued_before_ms: cutoff }
+        }
+        _ => {
+            return HttpResponse::BadRequest().json(serde_json::json!({
+                "error": "Specify exactly one of: jti, agent_id (optionally with issued_before), \
+                          task_id (optionally with agent_id), or issued_before",
+                "code": "INVALID_REQUEST"
+            }));
         }
     };
 
-    info!("AuthZ OK: agent={} -> mcp={} op={} req_id={}",
+    info!("Revocation by {}: jti={:?} agent_id={:?} task_id={:?} issued_before={:?}",
+          claims.sub, body.jti, body.agent_id, body.task_id, body.issued_before);
+    if let Err(e) = share_revocation(&state, revocation).await {
+        error!("Revocation not shared with other replicas: {}", e);
+        return HttpResponse::ServiceUnavailable().json(serde_json::json!({
+            "error": "Revocation applies on this gateway replica only; the revocation list could not be saved",
+            "code": "REVOCATION_NOT_SHARED"
+        }));
+    }
+
+    HttpResponse::Ok().json(serde_json::json!({
+        "revoked": true,
+        "jti": body.jti,
+        "agent_id": body.agent_id,
+        "task_id": body.task_id,
+        "issued_before": body.issued_before,
+    }))
+}
+
+fn forbidden_revocation(claims: &Claims) -> HttpResponse {
+    warn!("Revocation DENIED: agent={} role={:?} attempted to revoke foreign tokens",
+          claims.sub, claims.role);
+    HttpResponse::Forbidden().json(serde_json::json!({
+        "error": "Only Orchestrator role may revoke tokens of other agents",
+        "code": "REVOCATION_DENIED"
+    }))
+}
+
+/// Periodically drop revocations, refresh tokens and retired keys that have
+/// expired anyway, and rate limit state that no longer limits anything, and
+/// expire calls that waited too long for approval
+async fn prune_expired(state: Data<AppState>) {
+    let mut interval = tokio::time::interval(
+        std::time::Duration::from_secs(REVOCATION_PRUNE_INTERVAL_SECS));
+    loop {
+        interval.tick().await;
+        let now_ms = Utc::now().timestamp_millis();
+        let now = now_ms / 1000;
+        if let Err(e) = state.revoked_tokens.write().unwrap().prune(now_ms) {
+            error!("Revocation list not pruned: {}", e);
+        }
+        state.issued_tokens.write().unwrap().retain(|_, claims| claims.exp > now as usize);
+        let refresh_tokens = state.refresh_tokens.clone();
+        if let Err(e) = web::block(move || refresh_tokens.prune(now)).await
+            .unwrap_or_else(|e| Err(RefreshError::Storage(e.to_string())))
+        {
+            error!("Refresh tokens not pruned: {}", e);
+        }
+        state.keys.write().unwrap().prune(now);
+        state.limiter.prune(RATE_LIMIT_IDLE_SECS);
+        expire_approvals(&state).await;
+    }
+}
+
+/// Public signing keys, so MCP servers can verify gateway tokens themselves
+async fn jwks(state: Data<AppState>) -> HttpResponse {
+    HttpResponse::Ok().json(state.keys.read().unwrap().jwks())
+}
+
+// ─── Agent Administration ─────────────────────────────────────────────────────
+
+/// GET /admin/agents - every registered agent with its role and key metadata
+async fn list_agents(state: Data<AppState>, http_req: HttpRequest) -> HttpResponse {
+    if let Err(failure) = require_admin(&http_req, &state) {
+        return failure.into_response();
+    }
+    let agents = state.agents.read().unwrap().list(Utc::now());
+    HttpResponse::Ok().json(serde_json::json!({ "agents": agents }))
+}
+
+async fn get_agent(state: Data<AppState>, http_req: HttpRequest, path: web::Path<String>) -> HttpResponse {
+    if let Err(failure) = require_admin(&http_req, &state) {
+        return failure.into_response();
+    }
+    let agent_id = path.into_inner();
+    match state.agents.read().unwrap().get(&agent_id, Utc::now()) {
+        Some(agent) => HttpResponse::Ok().json(agent),
+        None => agent_error(AgentError::NotFound(format!("Agent '{}'", agent_id))),
+    }
+}
+
+/// POST /admin/agents - register an agent; the response holds its first API key
+async fn register_agent(
+    state: Data<AppState>,
+    http_req: HttpRequest,
+    req: web::Json<RegisterAgentRequest>,
+) -> HttpResponse {
+    let admin = match require_admin(&http_req, &state) {
+        Ok(claims) => claims,
+        Err(failure) => return failure.into_response(),
+    };
+    let req = req.into_inner();
+    let registered = state.agents.write().unwrap()
+        .register(&req.agent_id, req.role.clone(), req.description, req.key_ttl_secs, Utc::now());
+    match registered {
+        Ok(key) => {
+            info!("🪪 Agent {} registered with role {:?} by {}", req.agent_id, req.role, admin.sub);
+            audit_admin(&state, &admin, "register_agent", format!("{}/{}", req.agent_id, key.key_id)).await;
+            HttpResponse::Created().json(serde_json::json!({
+                "agent_id": req.agent_id,
+                "role": req.role,
+                "api_key": key,
+            }))
+        }
+        Err(e) => agent_error(e),
+    }
+}
+
+/// DELETE /admin/agents/{agent_id} - deregister an agent and revoke its tokens
+async fn remove_agent(state: Data<AppState>, http_req: HttpRequest, path: web::Path<String>) -> HttpResponse {
+    let agent_id = path.into_inner();
+    let admin = match require_admin_of(&http_req, &state, &agent_id) {
+        Ok(claims) => claims,
+        Err(failure) => return failure.into_response(),
+    };
+    if let Err(e) = state.agents.write().unwrap().remove(&agent_id) {
+        return agent_error(e);
+    }
+    revoke_agent_tokens(&state, &agent_id).await;
+    info!("🪪 Agent {} deregistered by {}", agent_id, admin.sub);
+    audit_admin(&state, &admin, "remove_agent", agent_id.clone()).await;
+    HttpResponse::Ok().json(serde_json::json!({ "agent_id": agent_id, "removed": true }))
+}
+
+/// PUT /admin/agents/{agent_id}/role - the agent's tokens are revoked, so the
+/// new role applies from its next login
+async fn assign_role(
+    state: Data<AppState>,
+    http_req: HttpRequest,
+    path: web::Path<String>,
+    req: web::Json<AssignRoleRequest>,
+) -> HttpResponse {
+    let agent_id = path.into_inner();
+    let admin = match require_admin_of(&http_req, &state, &agent_id) {
+        Ok(claims) => claims,
+        Err(failure) => return failure.into_response(),
+    };
+    let previous = match state.agents.write().unwrap().set_role(&agent_id, req.role.clone()) {
+        Ok(previous) => previous,
+        Err(e) => return agent_error(e),
+    };
+    if previous != req.role {
+        revoke_agent_tokens(&state, &agent_id).await;
+    }
+    info!("🪪 Agent {} role {:?} -> {:?} by {}", agent_id, previous, req.role, admin.sub);
+    audit_admin(&state, &admin, "assign_role", format!("{}/{}", agent_id, req.role.as_str())).await;
+    HttpResponse::Ok().json(serde_json::json!({
+        "agent_id": agent_id,
+        "role": req.role,
+        "previous_role": previous,
+    }))
+}
+
+/// POST /admin/agents/{agent_id}/keys - issue an additional API key
+async fn issue_agent_key(
+    state: Data<AppState>,
+    http_req: HttpRequest,
+    path: web::Path<String>,
+    req: web::Json<IssueKeyRequest>,
+) -> HttpResponse {
+    let agent_id = path.into_inner();
+    let admin = match require_admin(&http_req, &state) {
+        Ok(claims) => claims,
+        Err(failure) => return failure.into_response(),
+    };
+    if req.grace_secs.is_some() {
+        return agent_error(AgentError::Invalid("grace_secs only applies to keys/rotate".to_string()));
+    }
+    let issued = state.agents.write().unwrap().issue_key(&agent_id, req.ttl_secs, Utc::now());
+    match issued {
+        Ok(key) => {
+            info!("🪪 API key {} issued to {} by {}", key.key_id, agent_id, admin.sub);
+            audit_admin(&state, &admin, "issue_key", format!("{}/{}", agent_id, key.key_id)).await;
+            HttpResponse::Created().json(key)
+        }
+        Err(e) => agent_error(e),
+    }
+}
+
+/// POST /admin/agents/{agent_id}/keys/rotate - issue a new API key and retire
+/// the others after `grace_secs` (at once by default)
+async fn rotate_agent_keys(
+    state: Data<AppState>,
+    http_req: HttpRequest,
+    path: web::Path<String>,
+    req: web::Json<IssueKeyRequest>,
+) -> HttpResponse {
+    let agent_id = path.into_inner();
+    let admin = match require_admin(&http_req, &state) {
+        Ok(claims) => claims,
+        Err(failure) => return failure.into_response(),
+    };
+    let grace_secs = req.grace_secs.unwrap_or(0);
+    let rotated = state.agents.write().unwrap().rotate_keys(&agent_id, req.ttl_secs, Some(grace_secs), Utc::now());
+    match rotated {
+        Ok(key) => {
+            info!("🪪 API keys of {} rotated to {} by {} (grace {}s)", agent_id, key.key_id, admin.sub, grace_secs);
+            audit_admin(&state, &admin, "rotate_keys", format!("{}/{}", agent_id, key.key_id)).await;
+            HttpResponse::Created().json(key)
+        }
+        Err(e) => agent_error(e),
+    }
+}
+
+/// DELETE /admin/agents/{agent_id}/keys/{key_id} - the agent can no longer log
+/// in with this key; tokens it already holds stay valid until revoked
+async fn revoke_agent_key(
+    state: Data<AppState>,
+    http_req: HttpRequest,
+    path: web::Path<(String, String)>,
+) -> HttpResponse {
+    let (agent_id, key_id) = path.into_inner();
+    let admin = match require_admin(&http_req, &state) {
+        Ok(claims) => claims,
+        Err(failure) => return failure.into_response(),
+    };
+    let revoked = state.agents.write().unwrap().revoke_key(&agent_id, &key_id, Utc::now());
+    match revoked {
+        Ok(()) => {
+            info!("🪪 API key {} of {} revoked by {}", key_id, agent_id, admin.sub);
+            audit_admin(&state, &admin, "revoke_key", format!("{}/{}", agent_id, key_id)).await;
+            HttpResponse::Ok().json(serde_json::json!({
+                "agent_id": agent_id,
+                "key_id": key_id,
+                "revoked": true,
+            }))
+        }
+        Err(e) => agent_error(e),
+    }
+}
+
+/// Only orchestrators administer agents, and not with a task token
+fn require_admin(http_req: &HttpRequest, state: &AppState) -> Result<Claims, AuthFailure> {
+    let claims = authenticate(http_req, state)?;
+    if claims.is_delegated() {
+        return Err(AuthFailure::Forbidden("Delegated tokens cannot administer agents".to_string()));
+    }
+    if claims.role != AgentRole::Orchestrator {
+        return Err(AuthFailure::Forbidden("Agent administration requires Orchestrator role".to_string()));
+    }
+    Ok(claims)
+}
+
+/// As require_admin, for changes that would lock the caller out if made to itself
+fn require_admin_of(http_req: &HttpRequest, state: &AppState, agent_id: &str) -> Result<Claims, AuthFailure> {
+    let claims = require_admin(http_req, state)?;
+    if claims.sub == agent_id {
+        return Err(AuthFailure::Forbidden("Agents cannot change their own role or deregister themselves".to_string()));
+    }
+    Ok(claims)
+}
+
+/// Record an admin change in the audit trail. The change is already saved, so
+/// a failed write is logged rather than failing the call, which would also
+/// lose a newly issued key.
+async fn audit_admin(state: &AppState, admin: &Claims, action: &str, subject: String) {
+    if let Err(e) = state.audit.append(AuditEntry::admin(admin, action, subject.clone())).await {
+        error!("Audit write failed for admin action {} on {} by {}: {}", action, subject, admin.sub, e);
+    }
+}
+
+/// Revoke every access and refresh token the agent holds
+async fn revoke_agent_tokens(state: &AppState, agent_id: &str) {
+    let revocation = Revocation::Agent {
+        agent_id: agent_id.to_string(),
+        issued_before_ms: Utc::now().timestamp_millis() + 1,
+    };
+    if let Err(e) = share_revocation(state, revocation).await {
+        error!("Revocation of {}'s tokens not shared with other replicas: {}", agent_id, e);
+    }
+}
+
+/// Revoke on this replica, then, on the blocking pool and without holding the
+/// revocation list's lock, merge the revocation into the shared file and revoke
+/// the refresh tokens it covers. Fails if the revocation could not be shared.
+async fn share_revocation(state: &AppState, revocation: Revocation) -> std::io::Result<()> {
+    let pending = state.revoked_tokens.write().unwrap().revoke(&revocation);
+    let refresh_tokens = state.refresh_tokens.clone();
+    let saved = web::block(move || {
+        let refreshed = match &revocation {
+            Revocation::Agent { agent_id, issued_before_ms } => refresh_tokens.revoke_agent(agent_id, *issued_before_ms),
+            Revocation::All { issued_before_ms } => refresh_tokens.revoke_all(*issued_before_ms),
+            _ => Ok(()),
+        };
+        if let Err(e) = refreshed {
+            error!("Refresh tokens not revoked: {}", e);
+        }
+        pending.write()
+    }).await.map_err(std::io::Error::other)??;
+    state.revoked_tokens.write().unwrap().saved(saved);
+    Ok(())
+}
+
+fn agent_error(e: AgentError) -> HttpResponse {
+    let (mut response, code) = match &e {
+        AgentError::NotFound(_) => (HttpResponse::NotFound(), "AGENT_NOT_FOUND"),
+        AgentError::Exists(_) => (HttpResponse::Conflict(), "AGENT_EXISTS"),
+        AgentError::Invalid(_) => (HttpResponse::BadRequest(), "INVALID_REQUEST"),
+        AgentError::Storage(_) => {
+            error!("{}", e);
+            (HttpResponse::ServiceUnavailable(), "AGENT_REGISTRY_UNAVAILABLE")
+        }
+    };
+    response.json(serde_json::json!({
+        "error": e.to_string(),
+        "code": code
+    }))
+}
+
+// ─── Config Reload ────────────────────────────────────────────────────────────
+
+/// Reload the policy, signing keys and agent registry on SIGHUP, or when any
+/// of them changes on disk
+async fn watch_config(state: Data<AppState>) {
+    let policy_path = state.policy_path.clone();
+    let keys_dir = state.keys.read().unwrap().dir().map(Path::to_path_buf);
+    let agents_path = state.agents.read().unwrap().path().to_path_buf();
+    let revocations_path = state.revoked_tokens.read().unwrap().path().to_path_buf();
+    let mut hangup = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::hangup())
+        .map_err(|e| error!("Cannot listen for SIGHUP, relying on file polling only: {}", e))
+        .ok();
+    let mut poll = tokio::time::interval(std::time::Duration::from_secs(CONFIG_POLL_INTERVAL_SECS));
+    let mut policy_modified = policy_path.as_deref().and_then(policy::modified);
+    let mut keys_modified = keys_dir.as_deref().and_then(keys::modified);
+    let mut agents_modified = agents::modified(&agents_path);
+    let mut revocations_modified = agents::modified(&revocations_path);
+
+    loop {
+        let sighup = async {
+            match hangup.as_mut() {
+                Some(signal) => signal.recv().await,
+                None => std::future::pending().await,
+            }
+        };
+
+        tokio::select! {
+            _ = sighup => {
+                info!("SIGHUP received, reloading policy, signing keys and agents");
+                state.reload_policy();
+                state.reload_keys();
+                state.reload_agents();
+            }
+            _ = poll.tick() => {
+                let modified = policy_path.as_deref().and_then(policy::modified);
+                if modified != policy_modified {
+                    policy_modified = modified;
+                    info!("Policy file changed, reloading");
+                    state.reload_policy();
+                }
+                let modified = keys_dir.as_deref().and_then(keys::modified);
+                if modified != keys_modified {
+                    keys_modified = modified;
+                    info!("Keys directory changed, reloading");
+                    state.reload_keys();
+                }
+                let modified = agents::modified(&agents_path);
+                if modified != agents_modified {
+                    agents_modified = modified;
+                    state.reload_agents();
+                }
+                let modified = agents::modified(&revocations_path);
+                if modified != revocations_modified {
+                    revocations_modified = modified;
+                    if let Err(e) = state.revoked_tokens.write().unwrap().reload() {
+                        error!("Revocation list reload failed, keeping current revocations: {}", e);
+                    }
+                }
+            }
+        }
+    }
+}
+
+// ─── MCP Proxy with AuthZ ─────────────────────────────────────────────────────
+
+async fn proxy_mcp_request(
+    state: Data<AppState>,
+    http_req: HttpRequest,
+    body: web::Json<McpProxyRequest>,
+) -> HttpResponse {
+    let request_id = Uuid::new_v4().to_string();
+
+    let claims = match authenticate(&http_req, &state) {
+        Ok(c) => c,
+        Err(failure) => return failure.into_response(),
+    };
+
+    // Authorization check: can this agent call this MCP with this operation?
+    // The registry snapshot stays valid for this request across policy reloads.
+    let registry = state.registry();
+    let (audit_entry, denial) = match admit_call(
+        &state, &registry, &claims,
+        McpCall { target_mcp: &body.target_mcp, operation: &body.operation, payload: &body.payload, transport: Transport::Rest },
+        &request_id,
+    ).await {
+        Ok(admission) => admission,
+        Err(e) => {
+            error!("Audit write failed for req_id={}: {}", request_id, e);
+            return HttpResponse::ServiceUnavailable().json(serde_json::json!({
+                "error": "Audit log unavailable",
+                "code": "AUDIT_UNAVAILABLE"
+            }));
+        }
+    };
+
+    if let Some(denial) = denial {
+        let mut response = match &denial {
+            Denial::Forbidden(_) => HttpResponse::Forbidden(),
+            Denial::InvalidPayload(e) => {
+                let mut error = payload_error_body(e);
+                error["request_id"] = serde_json::json!(request_id);
+                error["audit_trail"] = serde_json::json!(audit_entry);
+                return HttpResponse::BadRequest().json(error);
+            }
+            Denial::Throttled(throttle) => {
+                let mut response = HttpResponse::TooManyRequests();
+                response.insert_header(("Retry-After", throttle.retry_after_secs.to_string()));
+                response
+            }
+            Denial::AwaitingApproval(approval) => {
+                return HttpResponse::Accepted().json(serde_json::json!({
+                    "success": false,
+                    "status": "pending_approval",
+                    "request_id": request_id,
+                    "agent_id": claims.sub,
+                    "target_mcp": body.target_mcp,
+                    "operation": body.operation,
+                    "result": null,
+                    "error": awaiting_approval_message(approval),
+                    "approval_id": approval.approval_id,
+                    "approvers": approval.approvers,
+                    "expires_at": approval.expires_at,
+                    "audit_trail": audit_entry
+                }));
+            }
+        };
+        return response.json(McpProxyResponse {
+            success: false,
+            request_id,
+            agent_id: claims.sub,
+            target_mcp: body.target_mcp.clone(),
+            operation: body.operation.clone(),
+            result: None,
+            error: audit_entry.denial_reason.clone(),
+            audit_trail: audit_entry,
+        });
+    }
+
+    // Forward to MCP server
+    let idempotent = registry.is_idempotent(&body.target_mcp, &body.operation);
+    let upstream = match registry.upstream(&body.target_mcp, idempotent) {
+        Some(upstream) => upstream,
+        None => {
+            return HttpResponse::NotFound().json(McpProxyResponse {
+                success: false,
+                request_id,
+                agent_id: claims.sub,
+                target_mcp: body.target_mcp.clone(),
+                operation: body.operation.clone(),
+                result: None,
+                error: Some(format!("MCP server '{}' not registered", body.target_mcp)),
+                audit_trail: audit_entry,
+            });
+        }
+    };
+
+    info!("AuthZ OK: agent={} -> mcp={} op={} req_id={}",
           claims.sub, body.target_mcp, body.operation, request_id);
 
-    // In production this proxies to actual MCP; for demo returns success
-    let mcp_result = call_mcp_server(&mcp_url, &body.operation, &body.payload, &request_id).await;
+    let mcp_result = call_mcp_server(
+        &state, &upstream, &claims, &body.operation, &body.operation, &body.payload, &request_id,
+    ).await;
 
     match mcp_result {
         Ok(result) => HttpResponse::Ok().json(McpProxyResponse {
@@ -405,48 +1552,732 @@ async fn proxy_mcp_request(
         }),
         Err(e) => {
             error!("MCP call failed: {}", e);
-            HttpResponse::InternalServerError().json(McpProxyResponse {
+            let mut response = match &e {
+                CallError::CircuitOpen { retry_after_secs, .. } => {
+                    let mut response = HttpResponse::ServiceUnavailable();
+                    response.insert_header(("Retry-After", retry_after_secs.to_string()));
+                    response
+                }
+                CallError::Failed(_) => HttpResponse::InternalServerError(),
+            };
+            response.json(McpProxyResponse {
                 success: false,
                 request_id,
                 agent_id: claims.sub,
                 target_mcp: body.target_mcp.clone(),
                 operation: body.operation.clone(),
                 result: None,
-                error: Some(e),
+                error: Some(e.to_string()),
                 audit_trail: audit_entry,
             })
         }
     }
 }
 
-async fn call_mcp_server(
-    url: &str,
+/// Why an authenticated MCP call was not forwarded. Calls awaiting approval
+/// are authorized; they are only held back until an approver decides.
+enum Denial {
+    Forbidden(String),
+    InvalidPayload(PayloadError),
+    Throttled(Throttle),
+    AwaitingApproval(Box<Approval>),
+}
+
+/// An MCP call as it reached the gateway, on either transport
+#[derive(Clone, Copy)]
+struct McpCall<'a> {
+    target_mcp: &'a str,
+    operation: &'a str,
+    payload: &'a serde_json::Value,
+    transport: Transport, // How an approved call is made, should it need approval
+}
+
+/// Authorize a call, check its payload and the resources it names, charge it
+/// against its rate limits and quota, park it if it needs approval, and audit
+/// the outcome. Returns the audit entry, and why the call was not forwarded.
+async fn admit_call(
+    state: &AppState,
+    registry: &McpRegistry,
+    claims: &Claims,
+    call: McpCall<'_>,
+    request_id: &str,
+) -> std::io::Result<(AuditEntry, Option<Denial>)> {
+    let McpCall { target_mcp, operation, payload, transport } = call;
+    let denial = if !registry.is_authorized(&claims.role, target_mcp, operation) {
+        warn!("AuthZ DENIED: agent={} role={:?} mcp={} op={}",
+              claims.sub, claims.role, target_mcp, operation);
+        Some(Denial::Forbidden(format!(
+            "Role {:?} is not authorized to call {} on {}", claims.role, operation, target_mcp)))
+    } else if let Some(Err(reason)) = claims.scope.as_deref().map(|scope| delegation::check_scope(
+        scope, claims.task_id.as_deref().unwrap_or_default(), target_mcp, operation, payload,
+    )) {
+        warn!("AuthZ DENIED: agent={} task={:?} mcp={} op={}: {}",
+              claims.sub, claims.task_id, target_mcp, operation, reason);
+        Some(Denial::Forbidden(reason))
+    } else if let Err(e) = registry.check_payload(target_mcp, operation, payload) {
+        warn!("Payload REJECTED: agent={} mcp={} op={}: {}", claims.sub, target_mcp, operation, e);
+        Some(Denial::InvalidPayload(e))
+    } else if let Err(reason) = registry.check_resources(target_mcp, operation, payload) {
+        warn!("AuthZ DENIED: agent={} mcp={} op={}: {}", claims.sub, target_mcp, operation, reason);
+        Some(Denial::Forbidden(reason))
+    } else {
+        let approval = registry.approval_for(target_mcp, operation, payload);
+        let pending = approval
+            .map(|_| state.approvals.pending_for(&claims.sub).map_err(|e| std::io::Error::other(e.to_string())))
+            .transpose()?;
+        if pending.is_some_and(|n| n >= MAX_PENDING_PER_AGENT) {
+            warn!("THROTTLED: agent={} mcp={} op={}: too many calls awaiting approval", claims.sub, target_mcp, operation);
+            Some(Denial::Throttled(Throttle {
+                reason: format!("{} already has {} calls awaiting approval", claims.sub, MAX_PENDING_PER_AGENT),
+                retry_after_secs: REVOCATION_PRUNE_INTERVAL_SECS,
+            }))
+        } else {
+            let (buckets, quota) = registry.limits_for(claims, target_mcp, operation);
+            match (state.limiter.check(&buckets, quota.as_ref()), approval) {
+                (Err(throttle), _) => {
+                    warn!("THROTTLED: agent={} mcp={} op={}: {}", claims.sub, target_mcp, operation, throttle.reason);
+                    Some(Denial::Throttled(throttle))
+                }
+                (Ok(()), Some(approval)) => {
+                    let now = Utc::now();
+                    info!("Approval REQUIRED: agent={} mcp={} op={} approvers={:?}",
+                          claims.sub, target_mcp, operation, approval.approvers);
+                    Some(Denial::AwaitingApproval(Box::new(Approval {
+                        approval_id: Uuid::new_v4().to_string(),
+                        request_id: request_id.to_string(),
+                        agent_id: claims.sub.clone(),
+                        task_id: claims.task_id.clone(),
+                        target_mcp: target_mcp.to_string(),
+                        operation: operation.to_string(),
+                        payload: payload.clone(),
+                        transport,
+                        approvers: approval.approvers.clone(),
+                        status: ApprovalStatus::Pending,
+                        requested_at: now,
+                        expires_at: now + Duration::seconds(approval.timeout_secs as i64),
+                        decided_by: None,
+                        decided_at: None,
+                        comment: None,
+                        result: None,
+                        error: None,
+                        claims: claims.clone(),
+                    })))
+                }
+                (Ok(()), None) => None,
+            }
+        }
+    };
+
+    let denial_reason = denial.as_ref().and_then(|d| match d {
+        Denial::Forbidden(reason) => Some(reason.clone()),
+        Denial::InvalidPayload(e) => Some(e.to_string()),
+        Denial::Throttled(throttle) => Some(throttle.reason.clone()),
+        Denial::AwaitingApproval(_) => None,
+    });
+
+    let (mcp_label, operation_label) = registry.metric_labels(target_mcp, operation);
+    let (decision, reason_label) = match &denial {
+        None => (Decision::Allowed, ""),
+        Some(Denial::AwaitingApproval(_)) => (Decision::Pending, ""),
+        Some(Denial::Forbidden(_)) => (Decision::Denied, "forbidden"),
+        Some(Denial::InvalidPayload(_)) => (Decision::Denied, "invalid_payload"),
+        Some(Denial::Throttled(_)) => (Decision::Denied, "throttled"),
+    };
+    state.metrics.authz_decision(claims.role.as_str(), mcp_label, operation_label, decision, reason_label);
+
+    // Record in audit log regardless of outcome; no call proceeds unaudited.
+    // A parked call is not authorized yet; the approval's decision is.
+    let mut entry = AuditEntry::new(claims, target_mcp, operation, request_id, denial_reason);
+    if let Some(Denial::AwaitingApproval(approval)) = &denial {
+        entry = entry.with_approval(approval, ApprovalStatus::Pending);
+        entry.authorized = false;
+    }
+    let entry = state.audit.append(entry).await?;
+
+    // Parked only once audited, so no approver sees a call the log does not
+    if let Some(Denial::AwaitingApproval(approval)) = &denial {
+        state.approvals.insert(approval.as_ref().clone()).map_err(|e| std::io::Error::other(e.to_string()))?;
+        state.metrics.approval(mcp_label, operation_label, ApprovalStatus::Pending);
+    }
+    Ok((entry, denial))
+}
+
+fn awaiting_approval_message(approval: &Approval) -> String {
+    format!("{} on {} is awaiting approval by {} (approval {})",
+            approval.operation, approval.target_mcp, approval.approvers.join(" or "), approval.approval_id)
+}
+
+/// Structured body for a payload rejected by `McpRegistry::check_payload`
+fn payload_error_body(e: &PayloadError) -> serde_json::Value {
+    match e {
+        PayloadError::TooLarge { size, max } => serde_json::json!({
+            "error": e.to_string(),
+            "code": "PAYLOAD_TOO_LARGE",
+            "size_bytes": size,
+            "max_bytes": max
+        }),
+        PayloadError::SchemaViolations(violations) => serde_json::json!({
+            "error": e.to_string(),
+            "code": "INVALID_PAYLOAD",
+            "violations": violations
+        }),
+    }
+}
+
+/// Short-lived, single-use proof for the MCP server that the gateway
+/// authorized this call with the body hashing to `payload_sha256`
+fn sign_assertion(
+    state: &AppState,
+    claims: &Claims,
+    target_mcp: &str,
     operation: &str,
+    request_id: &str,
+    payload_sha256: &str,
+) -> Result<String, String> {
+    let now = Utc::now();
+    let assertion = GatewayAssertion {
+        iss: ASSERTION_ISSUER.to_string(),
+        aud: target_mcp.to_string(),
+        sub: claims.sub.clone(),
+        role: claims.role.as_str().to_string(),
+        operation: operation.to_string(),
+        request_id: request_id.to_string(),
+        payload_sha256: payload_sha256.to_string(),
+        iat: now.timestamp() as usize,
+        exp: (now + Duration::seconds(ASSERTION_TTL_SECS)).timestamp() as usize,
+        jti: Uuid::new_v4().to_string(),
+    };
+    state.keys.read().unwrap().sign(&assertion)
+}
+
+/// Forward a call to a registered MCP server: fail fast while its circuit is
+/// open, retry idempotent calls that could not get through, and record latency,
+/// failures and breaker changes. Every attempt carries a fresh assertion over
+/// the exact bytes sent.
+async fn call_mcp_server(
+    state: &AppState,
+    upstream: &Upstream,
+    claims: &Claims,
+    operation: &str, // The operation authorized
+    route: &str,     // The operation for REST calls, "mcp" for JSON-RPC
     payload: &serde_json::Value,
     request_id: &str,
-) -> Result<serde_json::Value, String> {
-    let client = reqwest::Client::builder()
-        .timeout(std::time::Duration::from_secs(120))
-        .build()
-        .map_err(|e| e.to_string())?;
-
-    let response = client
-        .post(format!("{}/{}", url, operation))
+) -> Result<serde_json::Value, CallError> {
+    let mcp = upstream.mcp.as_str();
+    let body = serde_json::to_vec(payload)
+        .map_err(|e| CallError::Failed(format!("Failed to encode payload: {}", e)))?;
+    let payload_sha256 = assertions::payload_sha256(&body);
+    let mut attempt = 1;
+    loop {
+        if let Err(retry_after_secs) = state.breakers.admit(mcp, &upstream.breaker) {
+            state.metrics.upstream_error(mcp, "circuit_open");
+            return Err(CallError::CircuitOpen { mcp: mcp.to_string(), retry_after_secs });
+        }
+        let assertion = sign_assertion(state, claims, mcp, operation, request_id, &payload_sha256)
+            .map_err(CallError::Failed)?;
+
+        let attributes = vec![
+            KeyValue::new("mcp.server", upstream.mcp.clone()),
+            KeyValue::new("mcp.route", route.to_string()),
+            KeyValue::new("gateway.request_id", request_id.to_string()),
+            KeyValue::new("attempt", attempt as i64),
+        ];
+        let outcome = telemetry::in_span(
+            format!("{} {}", mcp, route),
+            attributes,
+            send_to_mcp(state, upstream, route, &body, request_id, &assertion),
+        ).await;
+        let changed = state.breakers.record(mcp, &upstream.breaker, outcome.healthy);
+        if let Some(breaker) = changed {
+            match breaker {
+                BreakerState::Open => warn!("⛔ Circuit for {} opened", mcp),
+                _ => info!("Circuit for {} is {}", mcp, breaker.as_str()),
+            }
+            state.metrics.circuit_state(mcp, breaker);
+        }
+        let tripped = changed == Some(BreakerState::Open);
+
+        match outcome.result {
+            Err(e) if outcome.retryable && !tripped && attempt < upstream.retry.max_attempts => {
+                attempt += 1;
+                warn!("{} req_id={}: {}; retrying ({}/{})",
+                      mcp, request_id, e, attempt, upstream.retry.max_attempts);
+                state.metrics.upstream_retry(mcp);
+                tokio::time::sleep(upstream.backoff(attempt)).await;
+            }
+            result => return result.map_err(CallError::Failed),
+        }
+    }
+}
+
+/// One attempt at a forwarded call
+struct Attempt {
+    result: Result<serde_json::Value, String>,
+    healthy: bool,   // The server answered like a working server, even if with an error
+    retryable: bool, // The call may not have reached the server, or it asked for a retry
+}
+
+impl telemetry::Outcome for Attempt {
+    fn error(&self) -> Option<String> {
+        telemetry::Outcome::error(&self.result)
+    }
+}
+
+async fn send_to_mcp(
+    state: &AppState,
+    upstream: &Upstream,
+    route: &str,
+    body: &[u8],
+    request_id: &str,
+    assertion: &str,
+) -> Attempt {
+    let mcp = upstream.mcp.as_str();
+    let started = std::time::Instant::now();
+    let response = telemetry::inject(state.http_client.post(format!("{}/{}", upstream.url, route)))
+        .timeout(upstream.timeout)
         .header("X-Request-ID", request_id)
         .header("X-Gateway", "agent-gateway/1.0")
-        .json(payload)
+        .header(ASSERTION_HEADER, assertion)
+        .header("Content-Type", "application/json")
+        .body(body.to_vec())
         .send()
-        .await
-        .map_err(|e| format!("MCP unreachable: {}", e))?;
+        .await;
+    state.metrics.upstream_call(mcp, started.elapsed().as_secs_f64());
+
+    let response = match response {
+        Ok(response) => response,
+        // A call that timed out may still be running; don't start another
+        Err(e) if e.is_timeout() => {
+            state.metrics.upstream_error(mcp, "timeout");
+            let error = format!("MCP timed out after {}s", upstream.timeout.as_secs());
+            return Attempt { result: Err(error), healthy: false, retryable: false };
+        }
+        Err(e) => {
+            state.metrics.upstream_error(mcp, "unreachable");
+            return Attempt { result: Err(format!("MCP unreachable: {}", e)), healthy: false, retryable: true };
+        }
+    };
 
-    if response.status().is_success() {
-        response.json::<serde_json::Value>()
-            .await
-            .map_err(|e| format!("Invalid MCP response: {}", e))
+    let status = response.status();
+    if status.is_success() {
+        return match response.json::<serde_json::Value>().await {
+            Ok(value) => Attempt { result: Ok(value), healthy: true, retryable: false },
+            Err(e) => {
+                state.metrics.upstream_error(mcp, "invalid_response");
+                Attempt { result: Err(format!("Invalid MCP response: {}", e)), healthy: false, retryable: false }
+            }
+        };
+    }
+
+    state.metrics.upstream_error(mcp, "status");
+    let text = response.text().await.unwrap_or_default();
+    let unavailable = matches!(status.as_u16(), 502..=504);
+    Attempt {
+        result: Err(format!("MCP returned {}: {}", status, text)),
+        healthy: !unavailable,
+        retryable: unavailable,
+    }
+}
+
+// ─── MCP JSON-RPC Proxy ───────────────────────────────────────────────────────
+
+/// POST /mcp/{target_mcp} - MCP streamable HTTP endpoint for one MCP server.
+/// tools/call is authorized per tool and audited like /mcp/invoke, and
+/// tools/list only shows the tools the caller's role may call. initialize and
+/// tools/list are audited too, as authorized calls of that method.
+async fn proxy_mcp_jsonrpc(
+    state: Data<AppState>,
+    http_req: HttpRequest,
+    target_mcp: web::Path<String>,
+    body: web::Bytes,
+) -> HttpResponse {
+    let claims = match authenticate(&http_req, &state) {
+        Ok(c) => c,
+        Err(failure) => return failure.into_response(),
+    };
+
+    let message: serde_json::Value = match serde_json::from_slice(&body) {
+        Ok(m) => m,
+        Err(e) => {
+            return HttpResponse::BadRequest().json(jsonrpc_error(
+                serde_json::Value::Null, PARSE_ERROR, format!("Parse error: {}", e)));
+        }
+    };
+    let id = message.get("id").cloned().unwrap_or(serde_json::Value::Null);
+    let Some(method) = message.get("method").and_then(|m| m.as_str()) else {
+        return HttpResponse::BadRequest().json(jsonrpc_error(
+            id, INVALID_REQUEST, "Expected a single JSON-RPC request".to_string()));
+    };
+    if message.get("id").is_none() {
+        // Notifications carry nothing the stateless MCP servers need
+        return HttpResponse::Accepted().finish();
+    }
+
+    let registry = state.registry();
+    // initialize and tools/list only read, so they are always safe to retry
+    let idempotent = method != "tools/call" || message.pointer("/params/name")
+        .and_then(|n| n.as_str())
+        .is_some_and(|tool| registry.is_idempotent(&target_mcp, tool));
+    let Some(upstream) = registry.upstream(&target_mcp, idempotent) else {
+        return HttpResponse::NotFound().json(jsonrpc_error(
+            id, INVALID_REQUEST, format!("MCP server '{}' not registered", target_mcp)));
+    };
+    let request_id = Uuid::new_v4().to_string();
+
+    let operation = match method {
+        "ping" => {
+            return HttpResponse::Ok().json(serde_json::json!({ "jsonrpc": "2.0", "id": id, "result": {} }));
+        }
+        "initialize" | "tools/list" => {
+            let entry = AuditEntry::new(&claims, &target_mcp, method, &request_id, None);
+            if let Err(e) = state.audit.append(entry).await {
+                error!("Audit write failed for req_id={}: {}", request_id, e);
+                return HttpResponse::ServiceUnavailable().json(jsonrpc_error(
+                    id, INTERNAL_ERROR, "Audit log unavailable".to_string()));
+            }
+            method.to_string()
+        }
+        "tools/call" => {
+            let Some(tool) = message.pointer("/params/name").and_then(|n| n.as_str()) else {
+                return HttpResponse::Ok().json(jsonrpc_error(
+                    id, INVALID_PARAMS, "tools/call requires a tool name".to_string()));
+            };
+            let arguments = message.pointer("/params/arguments").cloned().unwrap_or(serde_json::json!({}));
+            let (audit_entry, denial) = match admit_call(
+                &state, &registry, &claims,
+                McpCall { target_mcp: &target_mcp, operation: tool, payload: &arguments, transport: Transport::JsonRpc },
+                &request_id,
+            ).await {
+                Ok(admission) => admission,
+                Err(e) => {
+                    error!("Audit write failed for req_id={}: {}", request_id, e);
+                    return HttpResponse::ServiceUnavailable().json(jsonrpc_error(
+                        id, INTERNAL_ERROR, "Audit log unavailable".to_string()));
+                }
+            };
+
+            if let Some(denial) = denial {
+                let reason = match &denial {
+                    Denial::AwaitingApproval(approval) => awaiting_approval_message(approval),
+                    _ => audit_entry.denial_reason.clone().unwrap_or_default(),
+                };
+                let mut data = serde_json::json!({});
+                let (mut response, code) = match &denial {
+                    Denial::Forbidden(_) => (HttpResponse::Ok(), JSONRPC_FORBIDDEN),
+                    Denial::InvalidPayload(e) => {
+                        data = payload_error_body(e);
+                        (HttpResponse::BadRequest(), INVALID_PARAMS)
+                    }
+                    Denial::Throttled(throttle) => {
+                        let mut response = HttpResponse::TooManyRequests();
+                        response.insert_header(("Retry-After", throttle.retry_after_secs.to_string()));
+                        (response, JSONRPC_THROTTLED)
+                    }
+                    Denial::AwaitingApproval(approval) => {
+                        data = serde_json::json!({
+                            "approval_id": approval.approval_id,
+                            "approvers": approval.approvers,
+                            "expires_at": approval.expires_at
+                        });
+                        (HttpResponse::Accepted(), JSONRPC_APPROVAL_REQUIRED)
+                    }
+                };
+                data["request_id"] = serde_json::json!(request_id);
+                data["audit_seq"] = serde_json::json!(audit_entry.seq);
+                let mut error = jsonrpc_error(id, code, reason);
+                error["error"]["data"] = data;
+                return response.json(error);
+            }
+
+            info!("AuthZ OK: agent={} -> mcp={} tool={} req_id={}",
+                  claims.sub, target_mcp, tool, request_id);
+            tool.to_string()
+        }
+        _ => {
+            return HttpResponse::Ok().json(jsonrpc_error(
+                id, METHOD_NOT_FOUND, format!("Method not found: {}", method)));
+        }
+    };
+
+    let reply = call_mcp_server(&state, &upstream, &claims, &operation, "mcp", &message, &request_id).await;
+    let mut reply = match reply {
+        Ok(r) => r,
+        Err(e) => {
+            error!("MCP call failed: {}", e);
+            let mut response = match &e {
+                CallError::CircuitOpen { retry_after_secs, .. } => {
+                    let mut response = HttpResponse::ServiceUnavailable();
+                    response.insert_header(("Retry-After", retry_after_secs.to_string()));
+                    response
+                }
+                CallError::Failed(_) => HttpResponse::BadGateway(),
+            };
+            return response.json(jsonrpc_error(id, INTERNAL_ERROR, e.to_string()));
+        }
+    };
+
+    if method == "tools/list" {
+        if let Some(tools) = reply.pointer_mut("/result/tools").and_then(|t| t.as_array_mut()) {
+            tools.retain(|tool| {
+                tool.get("name")
+                    .and_then(|n| n.as_str())
+                    .is_some_and(|name| {
+                        registry.is_authorized(&claims.role, &target_mcp, name)
+                            && delegation::scope_covers(claims.scope.as_deref(), &target_mcp, name)
+                    })
+            });
+        }
+    }
+
+    HttpResponse::Ok()
+        .insert_header(("X-Request-ID", request_id))
+        .json(reply)
+}
+
+// ─── Approvals ────────────────────────────────────────────────────────────────
+
+/// GET /approvals - calls parked for approval that the caller made or may
+/// decide on; orchestrators see all of them
+async fn list_approvals(
+    state: Data<AppState>,
+    http_req: HttpRequest,
+    query: web::Query<ApprovalQuery>,
+) -> HttpResponse {
+    let claims = match authenticate(&http_req, &state) {
+        Ok(c) => c,
+        Err(failure) => return failure.into_response(),
+    };
+    match state.approvals.list(query.status, |approval| can_view_approval(&claims, approval)) {
+        Ok(approvals) => HttpResponse::Ok().json(serde_json::json!({ "approvals": approvals })),
+        Err(e) => approval_error(e),
+    }
+}
+
+async fn get_approval(state: Data<AppState>, http_req: HttpRequest, path: web::Path<String>) -> HttpResponse {
+    let claims = match authenticate(&http_req, &state) {
+        Ok(c) => c,
+        Err(failure) => return failure.into_response(),
+    };
+    match state.approvals.get(&path) {
+        Ok(Some(approval)) if can_view_approval(&claims, &approval) => HttpResponse::Ok().json(approval),
+        Ok(_) => approval_error(ApprovalError::NotFound),
+        Err(e) => approval_error(e),
+    }
+}
+
+/// POST /approvals/{approval_id}/approve - make the parked call on behalf of
+/// the agent that requested it, and answer with its result
+async fn approve_call(
+    state: Data<AppState>,
+    http_req: HttpRequest,
+    path: web::Path<String>,
+    req: web::Json<DecisionRequest>,
+) -> HttpResponse {
+    let approver = match require_approver(&http_req, &state) {
+        Ok(claims) => claims,
+        Err(failure) => return failure.into_response(),
+    };
+    let decided = state.approvals.decide(&path, &approver.sub, true, req.into_inner().comment, Utc::now());
+    let approval = match decided {
+        Ok(approval) => approval,
+        Err(e) => return approval_error(e),
+    };
+    let approval_id = approval.approval_id.as_str();
+    let registry = state.registry();
+    let (mcp_label, operation_label) = registry.metric_labels(&approval.target_mcp, &approval.operation);
+    state.metrics.approval(mcp_label, operation_label, ApprovalStatus::Approved);
+
+    // The requester's token or the policy may have changed while the call waited
+    let requester = &approval.claims;
+    let denial = if state.revoked_tokens.read().unwrap().is_revoked(requester)
+        || !state.agents.read().unwrap().is_enrolled(&requester.sub, &requester.role, Utc::now())
+    {
+        Some(format!("{}'s access was revoked while the call awaited approval", requester.sub))
+    } else if !registry.is_authorized(&requester.role, &approval.target_mcp, &approval.operation) {
+        Some(format!("Role {:?} is no longer authorized to call {} on {}",
+                     requester.role, approval.operation, approval.target_mcp))
     } else {
-        let status = response.status();
-        let text = response.text().await.unwrap_or_default();
-        Err(format!("MCP returned {}: {}", status, text))
+        registry.check_resources(&approval.target_mcp, &approval.operation, &approval.payload).err()
+    };
+
+    let entry = AuditEntry::new(
+        requester, &approval.target_mcp, &approval.operation, &approval.request_id, denial.clone(),
+    ).with_approval(&approval, ApprovalStatus::Approved);
+    let audit_entry = match state.audit.append(entry).await {
+        Ok(entry) => entry,
+        Err(e) => {
+            error!("Audit write failed for approval {}: {}", approval_id, e);
+            finish_approval(&state, approval_id, Err("Audit log unavailable".to_string()));
+            state.metrics.approval(mcp_label, operation_label, ApprovalStatus::Failed);
+            return HttpResponse::ServiceUnavailable().json(serde_json::json!({
+                "error": "Audit log unavailable",
+                "code": "AUDIT_UNAVAILABLE"
+            }));
+        }
+    };
+    info!("✅ Approval {} approved by {}: agent={} mcp={} op={} req_id={}",
+          approval_id, approver.sub, requester.sub, approval.target_mcp, approval.operation, approval.request_id);
+
+    let outcome = match (denial, registry.upstream(&approval.target_mcp, false)) {
+        (Some(reason), _) => {
+            warn!("AuthZ DENIED: approval {}: {}", approval_id, reason);
+            Err((HttpResponse::Forbidden(), reason))
+        }
+        (None, None) => Err((HttpResponse::NotFound(), format!("MCP server '{}' not registered", approval.target_mcp))),
+        (None, Some(upstream)) => {
+            // Made the way it was parked, so the result is what the caller expects
+            let (route, body) = match approval.transport {
+                Transport::Rest => (approval.operation.as_str(), approval.payload.clone()),
+                Transport::JsonRpc => ("mcp", serde_json::json!({
+                    "jsonrpc": "2.0",
+                    "id": approval.request_id,
+                    "method": "tools/call",
+                    "params": { "name": approval.operation, "arguments": approval.payload }
+                })),
+            };
+            let result = call_mcp_server(
+                &state, &upstream, requester, &approval.operation, route, &body, &approval.request_id,
+            ).await;
+            let result = result.and_then(|reply| match reply.get("error") {
+                Some(error) if approval.transport == Transport::JsonRpc => {
+                    Err(CallError::Failed(format!("MCP returned JSON-RPC error: {}", error)))
+                }
+                _ => Ok(reply),
+            });
+            result.map_err(|e| {
+                error!("MCP call failed: {}", e);
+                let response = match &e {
+                    CallError::CircuitOpen { retry_after_secs, .. } => {
+                        let mut response = HttpResponse::ServiceUnavailable();
+                        response.insert_header(("Retry-After", retry_after_secs.to_string()));
+                        response
+                    }
+                    CallError::Failed(_) => HttpResponse::InternalServerError(),
+                };
+                (response, e.to_string())
+            })
+        }
+    };
+
+    let (mut response, outcome) = match outcome {
+        Ok(result) => (HttpResponse::Ok(), Ok(result)),
+        Err((response, error)) => (response, Err(error)),
+    };
+    let success = outcome.is_ok();
+    let approval = finish_approval(&state, approval_id, outcome);
+    let status = if success { ApprovalStatus::Completed } else { ApprovalStatus::Failed };
+    state.metrics.approval(mcp_label, operation_label, status);
+    response.json(serde_json::json!({
+        "success": success,
+        "approval": approval,
+        "audit_trail": audit_entry
+    }))
+}
+
+/// Record what became of an approved call; the approval as updated, if it could be
+fn finish_approval(state: &AppState, approval_id: &str, outcome: Result<serde_json::Value, String>) -> Option<Approval> {
+    state.approvals.finish(approval_id, outcome)
+        .map_err(|e| error!("Outcome of approval {} not recorded: {}", approval_id, e))
+        .ok()
+}
+
+/// POST /approvals/{approval_id}/reject - the parked call is never made
+async fn reject_call(
+    state: Data<AppState>,
+    http_req: HttpRequest,
+    path: web::Path<String>,
+    req: web::Json<DecisionRequest>,
+) -> HttpResponse {
+    let approver = match require_approver(&http_req, &state) {
+        Ok(claims) => claims,
+        Err(failure) => return failure.into_response(),
+    };
+    let decided = state.approvals.decide(&path, &approver.sub, false, req.into_inner().comment, Utc::now());
+    let approval = match decided {
+        Ok(approval) => approval,
+        Err(e) => return approval_error(e),
+    };
+    let (mcp_label, operation_label) = state.registry().metric_labels(&approval.target_mcp, &approval.operation);
+    state.metrics.approval(mcp_label, operation_label, ApprovalStatus::Rejected);
+    info!("⛔ Approval {} rejected by {}: agent={} mcp={} op={} req_id={}",
+          approval.approval_id, approver.sub, approval.agent_id, approval.target_mcp, approval.operation,
+          approval.request_id);
+
+    let reason = match &approval.comment {
+        Some(comment) => format!("Rejected by {}: {}", approver.sub, comment),
+        None => format!("Rejected by {}", approver.sub),
+    };
+    let entry = AuditEntry::new(
+        &approval.claims, &approval.target_mcp, &approval.operation, &approval.request_id, Some(reason),
+    ).with_approval(&approval, ApprovalStatus::Rejected);
+    match state.audit.append(entry).await {
+        Ok(audit_entry) => HttpResponse::Ok().json(serde_json::json!({
+            "success": true,
+            "approval": approval,
+            "audit_trail": audit_entry
+        })),
+        // The call stays rejected; only the record of who rejected it is missing
+        Err(e) => {
+            error!("Audit write failed for approval {}: {}", approval.approval_id, e);
+            HttpResponse::ServiceUnavailable().json(serde_json::json!({
+                "error": "Audit log unavailable",
+                "code": "AUDIT_UNAVAILABLE"
+            }))
+        }
+    }
+}
+
+/// Approvers decide with their own login token, never a task token
+fn require_approver(http_req: &HttpRequest, state: &AppState) -> Result<Claims, AuthFailure> {
+    let claims = authenticate(http_req, state)?;
+    if claims.is_delegated() {
+        return Err(AuthFailure::Forbidden("Delegated tokens cannot decide on approvals".to_string()));
+    }
+    Ok(claims)
+}
+
+/// The requester, its approvers and orchestrators may see an approval
+fn can_view_approval(claims: &Claims, approval: &Approval) -> bool {
+    approval.involves(&claims.sub) || (claims.role == AgentRole::Orchestrator && !claims.is_delegated())
+}
+
+fn approval_error(e: ApprovalError) -> HttpResponse {
+    let (mut response, code) = match &e {
+        ApprovalError::NotFound => (HttpResponse::NotFound(), "APPROVAL_NOT_FOUND"),
+        ApprovalError::Invalid(_) => (HttpResponse::BadRequest(), "INVALID_REQUEST"),
+        ApprovalError::NotApprover | ApprovalError::OwnCall => (HttpResponse::Forbidden(), "APPROVAL_DENIED"),
+        ApprovalError::Decided(_) | ApprovalError::Expired => (HttpResponse::Conflict(), "APPROVAL_CLOSED"),
+        ApprovalError::Storage(_) => {
+            error!("{}", e);
+            (HttpResponse::ServiceUnavailable(), "APPROVALS_UNAVAILABLE")
+        }
+    };
+    response.json(serde_json::json!({
+        "error": e.to_string(),
+        "code": code
+    }))
+}
+
+/// Audit parked calls nobody decided on in time
+async fn expire_approvals(state: &AppState) {
+    let expired = match state.approvals.prune(Utc::now()) {
+        Ok(expired) if !expired.is_empty() => expired,
+        Ok(_) => return,
+        Err(e) => return error!("Approvals not pruned: {}", e),
+    };
+    let registry = state.registry();
+    for approval in expired {
+        warn!("⌛ Approval {} expired: agent={} mcp={} op={} req_id={}",
+              approval.approval_id, approval.agent_id, approval.target_mcp, approval.operation, approval.request_id);
+        let (mcp_label, operation_label) = registry.metric_labels(&approval.target_mcp, &approval.operation);
+        state.metrics.approval(mcp_label, operation_label, ApprovalStatus::Expired);
+        let entry = AuditEntry::new(
+            &approval.claims, &approval.target_mcp, &approval.operation, &approval.request_id,
+            Some(format!("No approver decided before {}", approval.expires_at.to_rfc3339())),
+        ).with_approval(&approval, ApprovalStatus::Expired);
+        if let Err(e) = state.audit.append(entry).await {
+            error!("Audit write failed for approval {}: {}", approval.approval_id, e);
+        }
     }
 }
 
@@ -455,43 +2286,310 @@ async fn call_mcp_server(
 async fn get_audit_log(
     state: Data<AppState>,
     http_req: HttpRequest,
+    query: web::Query<AuditQuery>,
 ) -> HttpResponse {
-    let token = match extract_bearer_token(&http_req) {
-        Some(t) => t,
-        None => return HttpResponse::Unauthorized().finish(),
+    if let Err(failure) = require_audit_access(&http_req, &state) {
+        return failure.into_response();
+    }
+
+    let filter = match AuditFilter::from_query(&query) {
+        Ok(f) => f,
+        Err(e) => {
+            return HttpResponse::BadRequest().json(serde_json::json!({
+                "error": e,
+                "code": "INVALID_REQUEST"
+            }));
+        }
     };
 
-    let claims = match validate_token(&token, &state) {
-        Ok(c) => c,
-        Err(_) => return HttpResponse::Unauthorized().finish(),
+    let (content_type, extension) = match query.format {
+        ExportFormat::Json => {
+            let limit = query.limit.unwrap_or(DEFAULT_PAGE_SIZE).clamp(1, MAX_PAGE_SIZE);
+            return match state.audit.page(&filter, limit).await {
+                Ok(page) => HttpResponse::Ok().json(serde_json::json!({
+                    "total_entries": state.audit.len(),
+                    "entries": page.entries,
+                    "next_cursor": page.next_cursor,
+                    "has_more": page.has_more,
+                })),
+                Err(e) => audit_read_error(e),
+            };
+        }
+        ExportFormat::Ndjson => ("application/x-ndjson", "ndjson"),
+        ExportFormat::Csv => ("text/csv; charset=utf-8", "csv"),
     };
 
-    // Only orchestrators can view audit logs
-    if claims.role != AgentRole::Orchestrator {
-        return HttpResponse::Forbidden().json(serde_json::json!({
-            "error": "Audit log access requires Orchestrator role"
-        }));
+    match state.audit.export(filter, query.format).await {
+        Ok(body) => HttpResponse::Ok()
+            .content_type(content_type)
+            .insert_header((
+                "Content-Disposition",
+                format!("attachment; filename=\"audit-log.{}\"", extension),
+            ))
+            .streaming(body),
+        Err(e) => audit_read_error(e),
     }
+}
 
-    let log = state.audit_log.read().unwrap();
-    HttpResponse::Ok().json(serde_json::json!({
-        "total_entries": log.len(),
-        "entries": *log
+fn audit_read_error(e: std::io::Error) -> HttpResponse {
+    error!("Audit log read failed: {}", e);
+    HttpResponse::InternalServerError().json(serde_json::json!({
+        "error": format!("Audit log read failed: {}", e)
     }))
 }
 
+/// Recompute the hash chain and report the first broken link, if any
+async fn verify_audit_log(
+    state: Data<AppState>,
+    http_req: HttpRequest,
+) -> HttpResponse {
+    if let Err(failure) = require_audit_access(&http_req, &state) {
+        return failure.into_response();
+    }
+
+    match state.audit.verify().await {
+        Ok(report) => {
+            if let Some(broken) = &report.first_broken {
+                warn!("Audit chain broken at line {}: {}", broken.line, broken.reason);
+            }
+            HttpResponse::Ok().json(report)
+        }
+        Err(e) => audit_read_error(e),
+    }
+}
+
+/// Only orchestrators can view audit logs, and not with a task token
+fn require_audit_access(http_req: &HttpRequest, state: &AppState) -> Result<Claims, AuthFailure> {
+    let claims = authenticate(http_req, state)?;
+    if claims.is_delegated() {
+        return Err(AuthFailure::Forbidden("Delegated tokens cannot access the audit log".to_string()));
+    }
+    if claims.role != AgentRole::Orchestrator {
+        return Err(AuthFailure::Forbidden("Audit log access requires Orchestrator role".to_string()));
+    }
+    Ok(claims)
+}
+
 async fn health_check(state: Data<AppState>) -> HttpResponse {
-    let revoked_count = state.revoked_tokens.read().unwrap().len();
     HttpResponse::Ok().json(HealthResponse {
         status: "healthy".to_string(),
         version: env!("CARGO_PKG_VERSION").to_string(),
-        registered_mcps: state.mcp_registry.list_servers(),
-        active_tokens: revoked_count, // tokens issued minus revoked
+        registered_mcps: state.registry().list_servers(),
+        active_tokens: state.active_tokens(),
+        revoked_tokens: state.revoked_tokens.read().unwrap().len(),
+        circuit_breakers: state.registry().breaker_states(&state.breakers),
     })
 }
 
+/// GET /ready - probe every registered MCP server's /ready concurrently and
+/// answer 503 unless all of them are ready. Open like /health, but only an
+/// authenticated caller sees upstream URLs, versions and checks.
+async fn readiness_check(state: Data<AppState>, http_req: HttpRequest) -> HttpResponse {
+    let body = fleet_readiness(&state).await;
+    let mut response = if body.status == "ready" { HttpResponse::Ok() } else { HttpResponse::ServiceUnavailable() };
+    if extract_bearer_token(&http_req).is_some() && authenticate(&http_req, &state).is_ok() {
+        return response.json(body);
+    }
+    let upstreams: Vec<_> = body.upstreams.iter()
+        .map(|u| serde_json::json!({ "name": u.name, "status": u.status }))
+        .collect();
+    response.json(serde_json::json!({
+        "status": body.status,
+        "version": body.version,
+        "upstreams": upstreams,
+    }))
+}
+
+/// The last probe round if it is recent enough, else a new one. Callers that
+/// arrive during a round wait for it rather than starting their own.
+async fn fleet_readiness(state: &AppState) -> ReadinessResponse {
+    let mut last = state.last_ready.lock().await;
+    if let Some((at, body)) = last.as_ref() {
+        if at.elapsed() < std::time::Duration::from_secs(READY_CACHE_SECS) {
+            return body.clone();
+        }
+    }
+    let body = probe_fleet(state).await;
+    *last = Some((std::time::Instant::now(), body.clone()));
+    body
+}
+
+async fn probe_fleet(state: &AppState) -> ReadinessResponse {
+    let registry = state.registry();
+    let mut names = registry.list_servers();
+    names.sort();
+    let probes = names.into_iter()
+        .filter_map(|name| registry.get_server_url(&name).map(|url| (name, url)))
+        .map(|(name, url)| probe_upstream(&state.http_client, name, url));
+    let upstreams = futures_util::future::join_all(probes).await;
+
+    let ready = upstreams.iter().all(|u| u.status == "ready");
+    for upstream in upstreams.iter().filter(|u| u.status != "ready") {
+        warn!("MCP server {} is {}: {}", upstream.name, upstream.status,
+              upstream.error.as_deref().unwrap_or("dependency checks failed"));
+    }
+    ReadinessResponse {
+        status: if ready { "ready" } else { "not_ready" }.to_string(),
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        upstreams,
+    }
+}
+
+/// Ask one MCP server whether it is ready; a 503 still carries its checks
+async fn probe_upstream(client: &reqwest::Client, name: String, url: String) -> UpstreamStatus {
+    let started = std::time::Instant::now();
+    let response = client.get(format!("{}/ready", url))
+        .timeout(std::time::Duration::from_secs(UPSTREAM_PROBE_TIMEOUT_SECS))
+        .send()
+        .await;
+    let latency_ms = started.elapsed().as_millis() as u64;
+
+    let mut upstream = UpstreamStatus {
+        name,
+        url,
+        status: "unreachable".to_string(),
+        version: None,
+        latency_ms,
+        checks: Vec::new(),
+        error: None,
+    };
+    let response = match response {
+        Ok(response) => response,
+        Err(e) => {
+            upstream.error = Some(format!("MCP unreachable: {}", e));
+            return upstream;
+        }
+    };
+
+    let status = response.status();
+    upstream.status = "not_ready".to_string();
+    let body = match response.json::<serde_json::Value>().await {
+        Ok(body) => body,
+        Err(e) => {
+            upstream.error = Some(format!("MCP returned {} without a readiness report: {}", status, e));
+            return upstream;
+        }
+    };
+    upstream.version = body.get("version").and_then(|v| v.as_str()).map(String::from);
+    upstream.checks = body.get("checks")
+        .and_then(|c| serde_json::from_value(c.clone()).ok())
+        .unwrap_or_default();
+    if status.is_success() {
+        upstream.status = "ready".to_string();
+    } else {
+        upstream.error = Some(format!("MCP returned {}", status));
+    }
+    upstream
+}
+
+/// GET /metrics - Prometheus scrape endpoint, open like /health
+async fn metrics(state: Data<AppState>) -> HttpResponse {
+    state.metrics.active_tokens.set(state.active_tokens() as i64);
+    state.metrics.revoked_tokens.set(state.revoked_tokens.read().unwrap().len() as i64);
+    // Open circuits turn half-open with time alone, so read them fresh
+    for (mcp, breaker) in state.registry().breaker_states(&state.breakers) {
+        state.metrics.circuit_state(&mcp, breaker);
+    }
+    match state.metrics.render() {
+        Ok(body) => HttpResponse::Ok()
+            .content_type("text/plain; version=0.0.4; charset=utf-8")
+            .body(body),
+        Err(e) => {
+            error!("Metrics encoding failed: {}", e);
+            HttpResponse::InternalServerError().json(serde_json::json!({
+                "error": format!("Metrics encoding failed: {}", e)
+            }))
+        }
+    }
+}
+
 // ─── Helpers ──────────────────────────────────────────────────────────────────
 
+/// Why a caller was turned away before its request was handled
+pub enum AuthFailure {
+    MissingToken,
+    InvalidToken(String),
+    Certificate(String),
+    Forbidden(String),
+}
+
+impl AuthFailure {
+    pub fn into_response(self) -> HttpResponse {
+        match self {
+            AuthFailure::MissingToken => HttpResponse::Unauthorized().json(serde_json::json!({
+                "error": "Missing Authorization header",
+                "code": "NO_TOKEN"
+            })),
+            AuthFailure::InvalidToken(e) => HttpResponse::Unauthorized().json(serde_json::json!({
+                "error": e,
+                "code": "INVALID_TOKEN"
+            })),
+            AuthFailure::Certificate(e) => HttpResponse::Unauthorized().json(serde_json::json!({
+                "error": e,
+                "code": "CLIENT_CERT_REJECTED"
+            })),
+            AuthFailure::Forbidden(e) => HttpResponse::Forbidden().json(serde_json::json!({
+                "error": e,
+                "code": "FORBIDDEN"
+            })),
+        }
+    }
+}
+
+/// Extract and validate the caller's Bearer token, and over mTLS check that
+/// it belongs to the agent the client certificate identifies
+fn authenticate(req: &HttpRequest, state: &AppState) -> Result<Claims, AuthFailure> {
+    let token = extract_bearer_token(req).ok_or(AuthFailure::MissingToken)?;
+    let claims = validate_token(&token, state).map_err(|e| {
+        warn!("Token validation failed: {}", e);
+        AuthFailure::InvalidToken(e)
+    })?;
+    require_certificate_for(req, state, &claims.sub)?;
+    Ok(claims)
+}
+
+/// Agent identified by the request's client certificate; `None` without mTLS
+fn certificate_agent(req: &HttpRequest, state: &AppState) -> Result<Option<String>, AuthFailure> {
+    if !state.verify_client_certs {
+        return Ok(None);
+    }
+    let cert = tls::peer_certificate(req)
+        .ok_or_else(|| AuthFailure::Certificate("A verified client certificate is required".to_string()))?;
+    state.registry().certificate_agent(&cert.names)
+        .map(Some)
+        .ok_or_else(|| {
+            warn!("Client certificate {:?} is not mapped to an agent", cert.names);
+            AuthFailure::Certificate(format!("Client certificate {:?} is not mapped to an agent", cert.names))
+        })
+}
+
+/// Over mTLS, reject requests whose client certificate is not `agent_id`'s
+fn require_certificate_for(req: &HttpRequest, state: &AppState, agent_id: &str) -> Result<(), AuthFailure> {
+    match certificate_agent(req, state)? {
+        Some(agent) if agent != agent_id => {
+            warn!("Identity mismatch: certificate agent={} credential agent={}", agent, agent_id);
+            Err(AuthFailure::Certificate(format!("Client certificate does not belong to agent {}", agent_id)))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Structured response for a request body that is not the expected JSON
+fn json_body_error(err: actix_web::error::JsonPayloadError, _req: &HttpRequest) -> actix_web::Error {
+    use actix_web::error::JsonPayloadError;
+    let mut response = match err {
+        JsonPayloadError::Overflow { .. } | JsonPayloadError::OverflowKnownLength { .. } => {
+            HttpResponse::PayloadTooLarge()
+        }
+        _ => HttpResponse::BadRequest(),
+    };
+    let response = response.json(serde_json::json!({
+        "error": err.to_string(),
+        "code": "INVALID_REQUEST"
+    }));
+    actix_web::error::InternalError::from_response(err, response).into()
+}
+
 fn extract_bearer_token(req: &HttpRequest) -> Option<String> {
     req.headers()
         .get("Authorization")
@@ -501,17 +2599,11 @@ fn extract_bearer_token(req: &HttpRequest) -> Option<String> {
 }
 
 fn validate_token(token: &str, state: &AppState) -> Result<Claims, String> {
-    let token_data = decode::<Claims>(
-        token,
-        &DecodingKey::from_secret(state.jwt_secret.as_bytes()),
-        &Validation::new(Algorithm::HS256),
-    ).map_err(|e| format!("Token decode failed: {}", e))?;
-
-    let claims = token_data.claims;
+    let claims: Claims = state.keys.read().unwrap().verify(token)?;
 
     // Check revocation list
     let revoked = state.revoked_tokens.read().unwrap();
-    if revoked.contains(&claims.jti) {
+    if revoked.is_revoked(&claims) {
         return Err("Token has been revoked".to_string());
     }
 
@@ -533,26 +2625,69 @@ impl McpRegistry {
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
     env_logger::init_from_env(env_logger::Env::new().default_filter_or("info"));
+    let telemetry = telemetry::init("agent-gateway");
 
-    let state = Data::new(AppState::new());
+    let tls = TlsSettings::from_env();
+    let state = match AppState::new(tls.as_ref()) {
+        Ok(state) => Data::new(state),
+        Err(e) => {
+            error!("❌ Agent Gateway failed to start: {}", e);
+            std::process::exit(1);
+        }
+    };
     let bind_addr = std::env::var("BIND_ADDR").unwrap_or("0.0.0.0:8090".to_string());
 
     info!("🔐 Agent Gateway starting on {}", bind_addr);
-    info!("📋 Registered MCP servers: {:?}", state.mcp_registry.list_servers());
+    info!("📋 Registered MCP servers: {:?}", state.registry().list_servers());
 
-    HttpServer::new(move || {
+    actix_web::rt::spawn(prune_expired(state.clone()));
+    actix_web::rt::spawn(watch_config(state.clone()));
+
+    let http = HttpServer::new(move || {
         App::new()
             .app_data(state.clone())
+            // Bodies up to the largest payload cap the policy may set
+            .app_data(web::JsonConfig::default().limit(MAX_REQUEST_BYTES).error_handler(json_body_error))
+            .app_data(web::PayloadConfig::new(MAX_REQUEST_BYTES))
+            .wrap(middleware::from_fn(telemetry::trace_requests))
             .wrap(middleware::Logger::default())
             // Auth endpoints
             .route("/auth/token", web::post().to(issue_token))
+            .route("/auth/refresh", web::post().to(refresh_token))
+            .route("/auth/delegate", web::post().to(delegate_token))
+            .route("/auth/revoke", web::post().to(revoke_token))
+            .route("/.well-known/jwks.json", web::get().to(jwks))
+            // Agent and API key administration (Orchestrator only)
+            .route("/admin/agents", web::get().to(list_agents))
+            .route("/admin/agents", web::post().to(register_agent))
+            .route("/admin/agents/{agent_id}", web::get().to(get_agent))
+            .route("/admin/agents/{agent_id}", web::delete().to(remove_agent))
+            .route("/admin/agents/{agent_id}/role", web::put().to(assign_role))
+            .route("/admin/agents/{agent_id}/keys", web::post().to(issue_agent_key))
+            .route("/admin/agents/{agent_id}/keys/rotate", web::post().to(rotate_agent_keys))
+            .route("/admin/agents/{agent_id}/keys/{key_id}", web::delete().to(revoke_agent_key))
             // MCP proxy endpoint (requires Bearer token)
             .route("/mcp/invoke", web::post().to(proxy_mcp_request))
+            .route("/mcp/{target_mcp}", web::post().to(proxy_mcp_jsonrpc))
+            // Human sign-off for calls the policy parks
+            .route("/approvals", web::get().to(list_approvals))
+            .route("/approvals/{approval_id}", web::get().to(get_approval))
+            .route("/approvals/{approval_id}/approve", web::post().to(approve_call))
+            .route("/approvals/{approval_id}/reject", web::post().to(reject_call))
             // Audit and monitoring
             .route("/audit/log", web::get().to(get_audit_log))
+            .route("/audit/verify", web::get().to(verify_audit_log))
             .route("/health", web::get().to(health_check))
+            .route("/ready", web::get().to(readiness_check))
+            .route("/metrics", web::get().to(metrics))
     })
-    .bind(&bind_addr)?
-    .run()
-    .await
+    .on_connect(tls::on_connect);
+
+    let http = match &tls {
+        Some(tls) => http.bind_rustls_0_23(&bind_addr, tls.server_config()?)?,
+        None => http.bind(&bind_addr)?,
+    };
+    let result = http.run().await;
+    telemetry.shutdown();
+    result
 }
@@ -0,0 +1,148 @@
+// Metrics - Prometheus counters and histograms for the gateway
+//
+// Served in the Prometheus text format at GET /metrics. Counters cover token
+// issuance, every authorization decision and upstream failures and retries; a
+// histogram per MCP server records how long forwarded calls take, a gauge per
+// server shows its circuit breaker, and a counter follows calls parked for
+// approval. MCP server and operation labels come from the policy, never
+// verbatim from a request, so a caller probing made-up names cannot grow the
+// label space.
+
+use prometheus::{Encoder, HistogramOpts, HistogramVec, IntCounterVec, IntGauge, IntGaugeVec, Opts, Registry, TextEncoder};
+
+use crate::approvals::ApprovalStatus;
+use crate::upstream::BreakerState;
+
+/// Label used for MCP servers and operations the policy does not declare
+pub const UNKNOWN_LABEL: &str = "unknown";
+
+/// Upstream call duration buckets, in seconds; compiles and translations take minutes
+const LATENCY_BUCKETS: &[f64] = &[0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0, 120.0];
+
+/// What admission decided for a call
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Decision {
+    Allowed,
+    Denied,
+    Pending, // Parked until an approver decides
+}
+
+impl Decision {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Decision::Allowed => "allowed",
+            Decision::Denied => "denied",
+            Decision::Pending => "pending_approval",
+        }
+    }
+}
+
+pub struct Metrics {
+    registry: Registry,
+    tokens_issued: IntCounterVec,     // kind (login | refresh | delegated), role
+    authz_decisions: IntCounterVec,   // role, mcp, operation, decision, reason
+    upstream_duration: HistogramVec,  // mcp
+    upstream_errors: IntCounterVec,   // mcp, kind (unreachable | timeout | status | invalid_response | circuit_open)
+    upstream_retries: IntCounterVec,  // mcp
+    circuit_state: IntGaugeVec,       // mcp; 0 closed, 1 half-open, 2 open
+    approvals: IntCounterVec,         // mcp, operation, status
+    pub active_tokens: IntGauge,      // Set from the issued-token list when scraped
+    pub revoked_tokens: IntGauge,     // Likewise, from the revocation list
+}
+
+impl Metrics {
+    pub fn new() -> Result<Self, prometheus::Error> {
+        let tokens_issued = IntCounterVec::new(
+            Opts::new("gateway_tokens_issued_total", "Access tokens issued"),
+            &["kind", "role"],
+        )?;
+        let authz_decisions = IntCounterVec::new(
+            Opts::new("gateway_authz_decisions_total", "MCP calls allowed or denied at the gateway"),
+            &["role", "mcp", "operation", "decision", "reason"],
+        )?;
+        let upstream_duration = HistogramVec::new(
+            HistogramOpts::new("gateway_upstream_request_duration_seconds", "Duration of calls forwarded to MCP servers")
+                .buckets(LATENCY_BUCKETS.to_vec()),
+            &["mcp"],
+        )?;
+        let upstream_errors = IntCounterVec::new(
+            Opts::new("gateway_upstream_errors_total", "Forwarded calls that failed"),
+            &["mcp", "kind"],
+        )?;
+        let upstream_retries = IntCounterVec::new(
+            Opts::new("gateway_upstream_retries_total", "Forwarded calls retried after a failed attempt"),
+            &["mcp"],
+        )?;
+        let circuit_state = IntGaugeVec::new(
+            Opts::new("gateway_circuit_breaker_state", "Circuit breaker per MCP server: 0 closed, 1 half-open, 2 open"),
+            &["mcp"],
+        )?;
+        let approvals = IntCounterVec::new(
+            Opts::new("gateway_approvals_total", "Calls parked for approval, and what became of them"),
+            &["mcp", "operation", "status"],
+        )?;
+        let active_tokens = IntGauge::new("gateway_active_tokens", "Issued access tokens not yet expired or revoked")?;
+        let revoked_tokens = IntGauge::new("gateway_revoked_tokens", "Individually revoked tokens not yet expired")?;
+
+        let registry = Registry::new();
+        registry.register(Box::new(tokens_issued.clone()))?;
+        registry.register(Box::new(authz_decisions.clone()))?;
+        registry.register(Box::new(upstream_duration.clone()))?;
+        registry.register(Box::new(upstream_errors.clone()))?;
+        registry.register(Box::new(upstream_retries.clone()))?;
+        registry.register(Box::new(circuit_state.clone()))?;
+        registry.register(Box::new(approvals.clone()))?;
+        registry.register(Box::new(active_tokens.clone()))?;
+        registry.register(Box::new(revoked_tokens.clone()))?;
+
+        Ok(Metrics {
+            registry,
+            tokens_issued,
+            authz_decisions,
+            upstream_duration,
+            upstream_errors,
+            upstream_retries,
+            circuit_state,
+            approvals,
+            active_tokens,
+            revoked_tokens,
+        })
+    }
+
+    pub fn token_issued(&self, kind: &str, role: &str) {
+        self.tokens_issued.with_label_values(&[kind, role]).inc();
+    }
+
+    /// Count an authorization decision; `reason` is empty unless it was denied
+    pub fn authz_decision(&self, role: &str, mcp: &str, operation: &str, decision: Decision, reason: &str) {
+        self.authz_decisions.with_label_values(&[role, mcp, operation, decision.as_str(), reason]).inc();
+    }
+
+    pub fn upstream_call(&self, mcp: &str, seconds: f64) {
+        self.upstream_duration.with_label_values(&[mcp]).observe(seconds);
+    }
+
+    pub fn upstream_error(&self, mcp: &str, kind: &str) {
+        self.upstream_errors.with_label_values(&[mcp, kind]).inc();
+    }
+
+    pub fn upstream_retry(&self, mcp: &str) {
+        self.upstream_retries.with_label_values(&[mcp]).inc();
+    }
+
+    pub fn circuit_state(&self, mcp: &str, state: BreakerState) {
+        self.circuit_state.with_label_values(&[mcp]).set(state.level());
+    }
+
+    /// Count a call entering `status`: pending when parked, then the decision and outcome
+    pub fn approval(&self, mcp: &str, operation: &str, status: ApprovalStatus) {
+        self.approvals.with_label_values(&[mcp, operation, status.as_str()]).inc();
+    }
+
+    /// Everything registered, in the Prometheus text exposition format
+    pub fn render(&self) -> Result<String, prometheus::Error> {
+        let mut buffer = Vec::new();
+        TextEncoder::new().encode(&self.registry.gather(), &mut buffer)?;
+        Ok(String::from_utf8_lossy(&buffer).into_owned())
+    }
+}
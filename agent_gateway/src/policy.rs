@@ -0,0 +1,456 @@
+// RBAC Policy - declarative MCP registry and role grants
+//
+// The policy file names every MCP server, the operations it exposes and which
+// roles may call them. It is parsed strictly (unknown keys are errors) and
+// then validated as a whole, so a bad edit is reported in full at startup or
+// rejected on reload while the previous policy stays in force.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::path::Path;
+use std::time::SystemTime;
+
+use crate::AgentRole;
+
+/// Policy compiled into the binary, used when POLICY_PATH is not set
+pub const DEFAULT_POLICY: &str = include_str!("../policy.yaml");
+
+/// Payload size cap for operations that do not set `max_payload_bytes`
+pub const DEFAULT_MAX_PAYLOAD_BYTES: usize = 1024 * 1024;
+
+/// Largest request body the gateway reads; no `max_payload_bytes` may exceed it
+pub const MAX_REQUEST_BYTES: usize = 16 * 1024 * 1024;
+
+/// Upstream call timeout for servers that do not set `timeout_secs`
+pub const DEFAULT_UPSTREAM_TIMEOUT_SECS: u64 = 120;
+
+/// Longest `timeout_secs` a server may set
+pub const MAX_UPSTREAM_TIMEOUT_SECS: u64 = 600;
+
+/// Most attempts `retry.max_attempts` may allow
+pub const MAX_RETRY_ATTEMPTS: u32 = 5;
+
+/// Longest `retry.backoff_ms` a server may set
+pub const MAX_RETRY_BACKOFF_MS: u64 = 10_000;
+
+/// How long a call waits for sign-off when `approval.timeout_secs` is not set
+pub const DEFAULT_APPROVAL_TIMEOUT_SECS: u64 = 3600;
+
+/// Longest `approval.timeout_secs` an operation may set
+pub const MAX_APPROVAL_TIMEOUT_SECS: u64 = 7 * 24 * 3600;
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct Policy {
+    pub servers: BTreeMap<String, ServerPolicy>,
+    #[serde(default)]
+    pub limits: LimitsPolicy,
+    #[serde(default)]
+    pub client_certs: BTreeMap<String, String>, // Client certificate CN / DNS name -> agent_id
+}
+
+/// Per-agent token buckets, applied to every MCP call an agent makes
+#[derive(Debug, Deserialize, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct LimitsPolicy {
+    pub agent_default: Option<RateLimit>,    // Agents without their own entry
+    #[serde(default)]
+    pub agents: BTreeMap<String, RateLimit>, // agent_id -> limit
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct RateLimit {
+    pub per_minute: u32,
+    pub burst: Option<u32>, // Bucket size; defaults to per_minute
+}
+
+/// Retries for idempotent operations that could not reach the server
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct RetryPolicy {
+    pub max_attempts: u32, // Including the first
+    pub backoff_ms: u64,   // Before the second attempt, doubling for each one after
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy { max_attempts: 3, backoff_ms: 200 }
+    }
+}
+
+/// When to stop sending calls to a server that keeps failing
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct CircuitBreakerPolicy {
+    pub failure_threshold: u32, // Consecutive failures that open the circuit
+    pub open_secs: u64,         // How long calls fail fast before one is let through
+}
+
+impl Default for CircuitBreakerPolicy {
+    fn default() -> Self {
+        CircuitBreakerPolicy { failure_threshold: 5, open_secs: 30 }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ServerPolicy {
+    pub url: String,
+    pub url_env: Option<String>,               // Environment variable overriding `url`
+    pub max_payload_bytes: Option<usize>,      // Default for this server's operations
+    pub timeout_secs: Option<u64>,             // Per call; defaults to DEFAULT_UPSTREAM_TIMEOUT_SECS
+    #[serde(default)]
+    pub retry: RetryPolicy,
+    #[serde(default)]
+    pub circuit_breaker: CircuitBreakerPolicy,
+    #[serde(default)]
+    pub operations: BTreeMap<String, OperationPolicy>,
+    #[serde(default)]
+    pub grants: HashMap<AgentRole, Vec<String>>, // role -> allowed operations
+}
+
+#[derive(Debug, Deserialize, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct OperationPolicy {
+    pub description: Option<String>,
+    pub schema: Option<serde_json::Value>,         // JSON Schema the payload must match
+    pub max_payload_bytes: Option<usize>,          // Serialized payload size cap
+    #[serde(default)]
+    pub idempotent: bool,                          // Safe to retry when the server was unreachable
+    #[serde(default)]
+    pub resources: BTreeMap<String, Vec<String>>,  // Payload field -> allowed values (see `resource_allows`)
+    #[serde(default)]
+    pub rate_limit: HashMap<AgentRole, RateLimit>, // Shared by all agents with the role
+    #[serde(default)]
+    pub daily_quota: HashMap<AgentRole, u32>,      // Calls per agent per UTC day
+    pub approval: Option<ApprovalPolicy>,          // Calls a person must sign off before they run
+}
+
+/// Human sign-off for an operation. Matching calls are parked until one of
+/// the `approvers` approves or rejects them, or `timeout_secs` passes.
+#[derive(Debug, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ApprovalPolicy {
+    pub approvers: Vec<String>,               // agent_ids allowed to decide
+    #[serde(default)]
+    pub when: BTreeMap<String, Vec<String>>,  // Payload field -> values needing approval; empty = every call
+    #[serde(default = "default_approval_timeout")]
+    pub timeout_secs: u64,
+}
+
+fn default_approval_timeout() -> u64 {
+    DEFAULT_APPROVAL_TIMEOUT_SECS
+}
+
+#[derive(Debug)]
+pub enum PolicyError {
+    Io { source: String, error: std::io::Error },
+    Parse { source: String, message: String },
+    Invalid { source: String, problems: Vec<String> },
+}
+
+impl fmt::Display for PolicyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PolicyError::Io { source, error } => write!(f, "cannot read policy {}: {}", source, error),
+            PolicyError::Parse { source, message } => write!(f, "cannot parse policy {}: {}", source, message),
+            PolicyError::Invalid { source, problems } => {
+                write!(f, "policy {} is invalid:", source)?;
+                for problem in problems {
+                    write!(f, "\n  - {}", problem)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for PolicyError {}
+
+impl Policy {
+    /// Load and validate the policy at `path`, or the built-in policy if `None`
+    pub fn load(path: Option<&Path>) -> Result<Policy, PolicyError> {
+        match path {
+            Some(path) => {
+                let source = path.display().to_string();
+                let text = std::fs::read_to_string(path)
+                    .map_err(|error| PolicyError::Io { source: source.clone(), error })?;
+                Policy::parse(&text, &source)
+            }
+            None => Policy::parse(DEFAULT_POLICY, "built-in policy"),
+        }
+    }
+
+    pub fn parse(text: &str, source: &str) -> Result<Policy, PolicyError> {
+        let policy: Policy = serde_yaml::from_str(text).map_err(|e| PolicyError::Parse {
+            source: source.to_string(),
+            message: e.to_string(),
+        })?;
+
+        let problems = policy.validate();
+        if !problems.is_empty() {
+            return Err(PolicyError::Invalid { source: source.to_string(), problems });
+        }
+        Ok(policy)
+    }
+
+    /// Every problem found in the policy; empty when it is usable
+    pub fn validate(&self) -> Vec<String> {
+        let mut problems = Vec::new();
+
+        if self.servers.is_empty() {
+            problems.push("no MCP servers declared under `servers`".to_string());
+        }
+
+        for (name, server) in &self.servers {
+            if !is_identifier(name) {
+                problems.push(format!("servers.{}: name must be lowercase letters, digits and '_'", name));
+            }
+            if name == "invoke" {
+                problems.push("servers.invoke: name is reserved for /mcp/invoke".to_string());
+            }
+
+            if let Some(var) = &server.url_env {
+                if var.trim().is_empty() {
+                    problems.push(format!("servers.{}.url_env: must not be empty", name));
+                }
+            }
+            let url = server.resolved_url();
+            let host = url.strip_prefix("http://").or_else(|| url.strip_prefix("https://"));
+            if host.is_none_or(|h| h.is_empty() || h.starts_with('/')) {
+                problems.push(format!("servers.{}.url: '{}' is not an http(s) URL", name, url));
+            }
+
+            validate_payload_cap(&format!("servers.{}.max_payload_bytes", name), server.max_payload_bytes, &mut problems);
+
+            if server.timeout_secs.is_some_and(|t| t == 0 || t > MAX_UPSTREAM_TIMEOUT_SECS) {
+                problems.push(format!(
+                    "servers.{}.timeout_secs: must be between 1 and {}", name, MAX_UPSTREAM_TIMEOUT_SECS));
+            }
+            if server.retry.max_attempts == 0 || server.retry.max_attempts > MAX_RETRY_ATTEMPTS {
+                problems.push(format!(
+                    "servers.{}.retry.max_attempts: must be between 1 and {}", name, MAX_RETRY_ATTEMPTS));
+            }
+            if server.retry.backoff_ms > MAX_RETRY_BACKOFF_MS {
+                problems.push(format!(
+                    "servers.{}.retry.backoff_ms: must not exceed {}", name, MAX_RETRY_BACKOFF_MS));
+            }
+            if server.circuit_breaker.failure_threshold == 0 {
+                problems.push(format!("servers.{}.circuit_breaker.failure_threshold: must be at least 1", name));
+            }
+            if server.circuit_breaker.open_secs == 0 {
+                problems.push(format!("servers.{}.circuit_breaker.open_secs: must be at least 1", name));
+            }
+
+            if server.operations.is_empty() {
+                problems.push(format!("servers.{}.operations: at least one operation is required", name));
+            }
+            for (op, operation) in &server.operations {
+                if !is_identifier(op) {
+                    problems.push(format!(
+                        "servers.{}.operations.{}: name must be lowercase letters, digits and '_'", name, op));
+                }
+                if let Some(schema) = &operation.schema {
+                    if let Err(e) = jsonschema::validator_for(schema) {
+                        problems.push(format!("servers.{}.operations.{}.schema: {}", name, op, e));
+                    }
+                }
+                validate_payload_cap(
+                    &format!("servers.{}.operations.{}.max_payload_bytes", name, op),
+                    operation.max_payload_bytes, &mut problems);
+                for (field, patterns) in &operation.resources {
+                    let at = format!("servers.{}.operations.{}.resources.{}", name, op, field);
+                    if patterns.is_empty() {
+                        problems.push(format!("{}: list at least one allowed value (remove the grant to block the operation)", at));
+                    }
+                    validate_patterns(&at, patterns, &mut problems);
+                }
+                if let Some(approval) = &operation.approval {
+                    let at = format!("servers.{}.operations.{}.approval", name, op);
+                    if approval.approvers.is_empty() {
+                        problems.push(format!("{}.approvers: list at least one approving agent", at));
+                    }
+                    for approver in &approval.approvers {
+                        if !is_identifier(approver) {
+                            problems.push(format!(
+                                "{}.approvers: '{}' must be lowercase letters, digits and '_'", at, approver));
+                        }
+                    }
+                    for (field, patterns) in &approval.when {
+                        let at = format!("{}.when.{}", at, field);
+                        if patterns.is_empty() {
+                            problems.push(format!("{}: list at least one value (remove `when` to require approval for every call)", at));
+                        }
+                        validate_patterns(&at, patterns, &mut problems);
+                    }
+                    if approval.timeout_secs == 0 || approval.timeout_secs > MAX_APPROVAL_TIMEOUT_SECS {
+                        problems.push(format!(
+                            "{}.timeout_secs: must be between 1 and {}", at, MAX_APPROVAL_TIMEOUT_SECS));
+                    }
+                }
+
+                let granted = |role: &AgentRole| server.grants.get(role).is_some_and(|ops| ops.contains(op));
+                for (role, limit) in &operation.rate_limit {
+                    let at = format!("servers.{}.operations.{}.rate_limit.{}", name, op, role.as_str());
+                    validate_rate_limit(&at, limit, &mut problems);
+                    if !granted(role) {
+                        problems.push(format!("{}: role is not granted this operation", at));
+                    }
+                }
+                for (role, quota) in &operation.daily_quota {
+                    let at = format!("servers.{}.operations.{}.daily_quota.{}", name, op, role.as_str());
+                    if *quota == 0 {
+                        problems.push(format!("{}: must be at least 1 (remove the grant to block a role)", at));
+                    }
+                    if !granted(role) {
+                        problems.push(format!("{}: role is not granted this operation", at));
+                    }
+                }
+            }
+
+            for (role, ops) in &server.grants {
+                let role = role.as_str();
+                let mut seen = HashSet::new();
+                for op in ops {
+                    if !server.operations.contains_key(op) {
+                        problems.push(format!(
+                            "servers.{}.grants.{}: unknown operation '{}'", name, role, op));
+                    }
+                    if !seen.insert(op) {
+                        problems.push(format!(
+                            "servers.{}.grants.{}: operation '{}' listed twice", name, role, op));
+                    }
+                }
+            }
+        }
+
+        for (name, agent_id) in &self.client_certs {
+            if name.trim().is_empty() {
+                problems.push("client_certs: certificate name must not be empty".to_string());
+            }
+            if !is_identifier(agent_id) {
+                problems.push(format!(
+                    "client_certs.{}: agent_id '{}' must be lowercase letters, digits and '_'", name, agent_id));
+            }
+        }
+
+        if let Some(limit) = &self.limits.agent_default {
+            validate_rate_limit("limits.agent_default", limit, &mut problems);
+        }
+        for (agent_id, limit) in &self.limits.agents {
+            validate_rate_limit(&format!("limits.agents.{}", agent_id), limit, &mut problems);
+        }
+
+        problems
+    }
+}
+
+impl RateLimit {
+    /// Most calls allowed back to back
+    pub fn capacity(&self) -> u32 {
+        self.burst.unwrap_or(self.per_minute)
+    }
+
+    pub fn per_second(&self) -> f64 {
+        self.per_minute as f64 / 60.0
+    }
+}
+
+/// Whether `value` matches a resource pattern: the exact value, or any value
+/// starting with the prefix when the pattern ends in `*`. Values with `..`
+/// path segments never match a prefix, so they cannot climb out of it.
+pub fn resource_allows(pattern: &str, value: &str) -> bool {
+    match pattern.strip_suffix('*') {
+        Some(prefix) => value.starts_with(prefix) && !value.split('/').any(|segment| segment == ".."),
+        None => value == pattern,
+    }
+}
+
+fn validate_patterns(at: &str, patterns: &[String], problems: &mut Vec<String>) {
+    for pattern in patterns {
+        if pattern.is_empty() || pattern == "*" || pattern.strip_suffix('*').unwrap_or(pattern).contains('*') {
+            problems.push(format!(
+                "{}: '{}' must be a value, or a non-empty prefix followed by a single trailing '*'", at, pattern));
+        }
+    }
+}
+
+fn validate_payload_cap(at: &str, max_bytes: Option<usize>, problems: &mut Vec<String>) {
+    match max_bytes {
+        Some(0) => problems.push(format!("{}: must be at least 1", at)),
+        Some(n) if n > MAX_REQUEST_BYTES => {
+            problems.push(format!("{}: must not exceed {} (the gateway's request size limit)", at, MAX_REQUEST_BYTES));
+        }
+        _ => {}
+    }
+}
+
+fn validate_rate_limit(at: &str, limit: &RateLimit, problems: &mut Vec<String>) {
+    if limit.per_minute == 0 {
+        problems.push(format!("{}.per_minute: must be at least 1", at));
+    }
+    if limit.burst == Some(0) {
+        problems.push(format!("{}.burst: must be at least 1", at));
+    }
+}
+
+impl ServerPolicy {
+    /// `url`, unless the `url_env` variable is set
+    pub fn resolved_url(&self) -> String {
+        self.url_env
+            .as_ref()
+            .and_then(|var| std::env::var(var).ok())
+            .unwrap_or_else(|| self.url.clone())
+    }
+}
+
+/// Last-modified time of the policy file, for change detection
+pub fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+fn is_identifier(name: &str) -> bool {
+    !name.is_empty()
+        && name.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exact_pattern_matches_only_itself() {
+        assert!(resource_allows("legacy-code", "legacy-code"));
+        assert!(!resource_allows("legacy-code", "legacy-code-2"));
+        assert!(!resource_allows("legacy-code", "legacy"));
+        assert!(!resource_allows("legacy-code", ""));
+    }
+
+    #[test]
+    fn prefix_pattern_matches_values_under_it() {
+        assert!(resource_allows("jobs/*", "jobs/job-1/PAYROLL.cbl"));
+        assert!(resource_allows("jobs/*", "jobs/"));
+        assert!(!resource_allows("jobs/*", "jobs"));
+        assert!(!resource_allows("jobs/*", "other/jobs/x"));
+    }
+
+    #[test]
+    fn dot_dot_segments_cannot_climb_out_of_a_prefix() {
+        assert!(!resource_allows("jobs/*", "jobs/../secrets/key.pem"));
+        assert!(!resource_allows("jobs/*", "jobs/job-1/.."));
+        assert!(resource_allows("jobs/*", "jobs/job-1/..hidden"));
+        assert!(resource_allows("jobs/*", "jobs/v1..2"));
+    }
+
+    #[test]
+    fn only_a_single_trailing_star_is_a_valid_pattern() {
+        let patterns = ["jobs/*", "legacy-code", "", "*", "jo*bs/*", "jobs/**"]
+            .map(String::from);
+        let mut problems = Vec::new();
+        validate_patterns("resources.key", &patterns, &mut problems);
+        assert_eq!(problems.len(), 4);
+        assert!(problems.iter().all(|p| p.starts_with("resources.key: ")));
+    }
+}
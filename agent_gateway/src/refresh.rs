@@ -0,0 +1,278 @@
+// Refresh Tokens - single-use credentials for renewing access tokens
+//
+// Every access token is issued with an opaque refresh token. Redeeming it at
+// /auth/refresh returns a new access token and a new refresh token, and
+// retires the old one. The tokens descending from one API key login form a
+// family; a retired token presented again means it was copied, so the whole
+// family is revoked. Only SHA-256 hashes are kept, in REFRESH_TOKENS_PATH,
+// which gateway replicas share like the agent registry: every change is made
+// under its lock to the file as re-read, so a token issued by one replica can
+// be redeemed at another, and only once across all of them.
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use log::info;
+use ring::rand::{SecureRandom, SystemRandom};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::agents;
+use crate::AgentRole;
+
+/// Lifetime of a refresh token, in seconds
+pub const REFRESH_TOKEN_TTL_SECS: i64 = 24 * 3600;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct RefreshGrant {
+    pub agent_id: String,
+    pub role: AgentRole,
+    pub family: String, // Shared by every token rotated from the same login
+    pub issued_at_ms: i64,
+    pub expires_at: i64,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(tag = "state", rename_all = "snake_case")]
+enum Slot {
+    Live(RefreshGrant),
+    Rotated(RefreshGrant), // Already redeemed; kept to detect reuse until it expires
+}
+
+#[derive(Serialize, Deserialize, Default)]
+struct RefreshFile {
+    tokens: HashMap<String, Slot>, // SHA-256 of the token -> grant
+}
+
+#[derive(Debug)]
+pub enum RefreshError {
+    Unknown,
+    Expired,
+    Reused { agent_id: String },
+    Storage(String),
+}
+
+impl fmt::Display for RefreshError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RefreshError::Unknown => write!(f, "Unknown or revoked refresh token"),
+            RefreshError::Expired => write!(f, "Refresh token has expired"),
+            RefreshError::Reused { .. } => {
+                write!(f, "Refresh token was already used; every refresh token from this login is revoked")
+            }
+            RefreshError::Storage(e) => write!(f, "Refresh tokens could not be read or saved: {}", e),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct RefreshStore {
+    path: PathBuf,
+}
+
+impl RefreshStore {
+    /// Use the refresh tokens kept at `path`, which need not exist yet
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)?;
+        }
+        let live = load(&path)?.tokens.values().filter(|slot| matches!(slot, Slot::Live(_))).count();
+        info!("🔁 Refresh tokens {} opened, {} live", path.display(), live);
+        Ok(RefreshStore { path })
+    }
+
+    /// New refresh token for `agent_id`, continuing `family` after a rotation
+    pub fn issue(&self, agent_id: &str, role: &AgentRole, family: Option<String>, now: DateTime<Utc>) -> Result<String, String> {
+        let token = random_token()?;
+        let family = match family {
+            Some(f) => f,
+            None => random_token()?,
+        };
+        self.update(|tokens| {
+            tokens.insert(hash(&token), Slot::Live(RefreshGrant {
+                agent_id: agent_id.to_string(),
+                role: role.clone(),
+                family,
+                issued_at_ms: now.timestamp_millis(),
+                expires_at: now.timestamp() + REFRESH_TOKEN_TTL_SECS,
+            }));
+            Ok((true, ()))
+        }).map_err(|e| e.to_string())?;
+        Ok(token)
+    }
+
+    /// Agent `token` was issued to, without redeeming it
+    pub fn holder(&self, token: &str) -> Result<Option<String>, RefreshError> {
+        let file = load(&self.path).map_err(storage)?;
+        Ok(file.tokens.get(&hash(token)).map(|slot| slot.grant().agent_id.clone()))
+    }
+
+    /// Retire `token` and return its grant, so the caller can issue the next one
+    pub fn redeem(&self, token: &str, now: i64) -> Result<RefreshGrant, RefreshError> {
+        let key = hash(token);
+        // A reused token revokes its family, which must be saved even though
+        // the redemption fails, so the outcome travels in the Ok value
+        self.update(|tokens| {
+            let grant = match tokens.remove(&key) {
+                None => return Ok((false, Err(RefreshError::Unknown))),
+                Some(Slot::Rotated(grant)) => {
+                    tokens.retain(|_, slot| slot.grant().family != grant.family);
+                    return Ok((true, Err(RefreshError::Reused { agent_id: grant.agent_id })));
+                }
+                Some(Slot::Live(grant)) => grant,
+            };
+            if grant.expires_at <= now {
+                return Ok((true, Err(RefreshError::Expired)));
+            }
+            tokens.insert(key, Slot::Rotated(grant.clone()));
+            Ok((true, Ok(grant)))
+        })?
+    }
+
+    /// Revoke the agent's refresh tokens issued before `issued_before_ms`
+    pub fn revoke_agent(&self, agent_id: &str, issued_before_ms: i64) -> Result<(), RefreshError> {
+        self.remove(|grant| grant.agent_id == agent_id && grant.issued_at_ms < issued_before_ms)
+    }
+
+    /// Revoke every refresh token issued before `issued_before_ms`
+    pub fn revoke_all(&self, issued_before_ms: i64) -> Result<(), RefreshError> {
+        self.remove(|grant| grant.issued_at_ms < issued_before_ms)
+    }
+
+    /// Drop expired tokens, retired ones included
+    pub fn prune(&self, now: i64) -> Result<(), RefreshError> {
+        self.remove(|grant| grant.expires_at <= now)
+    }
+
+    fn remove(&self, doomed: impl Fn(&RefreshGrant) -> bool) -> Result<(), RefreshError> {
+        self.update(|tokens| {
+            let before = tokens.len();
+            tokens.retain(|_, slot| !doomed(slot.grant()));
+            Ok((tokens.len() != before, ()))
+        })
+    }
+
+    /// Under the lock, re-read the tokens, change them, and save them if the
+    /// change says they changed
+    fn update<T>(&self, change: impl FnOnce(&mut HashMap<String, Slot>) -> Result<(bool, T), RefreshError>) -> Result<T, RefreshError> {
+        let _lock = agents::lock(&self.path).map_err(storage)?;
+        let mut file = load(&self.path).map_err(storage)?;
+        let (changed, result) = change(&mut file.tokens)?;
+        if changed {
+            let json = serde_json::to_vec(&file).map_err(|e| storage(io::Error::other(e)))?;
+            agents::replace_file(&self.path, &json).map_err(storage)?;
+        }
+        Ok(result)
+    }
+}
+
+impl Slot {
+    fn grant(&self) -> &RefreshGrant {
+        match self {
+            Slot::Live(grant) | Slot::Rotated(grant) => grant,
+        }
+    }
+}
+
+fn load(path: &Path) -> io::Result<RefreshFile> {
+    match fs::read(path) {
+        Ok(bytes) => serde_json::from_slice(&bytes)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path.display(), e))),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(RefreshFile::default()),
+        Err(e) => Err(e),
+    }
+}
+
+fn storage(e: io::Error) -> RefreshError {
+    RefreshError::Storage(e.to_string())
+}
+
+fn random_token() -> Result<String, String> {
+    let mut bytes = [0u8; 32];
+    SystemRandom::new()
+        .fill(&mut bytes)
+        .map_err(|_| "No randomness available for refresh token".to_string())?;
+    Ok(URL_SAFE_NO_PAD.encode(bytes))
+}
+
+fn hash(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    /// Store in a directory of its own, for the lock and temporary files next to it
+    fn temp_store() -> (PathBuf, RefreshStore) {
+        let dir = std::env::temp_dir().join(format!("refresh-test-{}", uuid::Uuid::new_v4()));
+        let store = RefreshStore::open(dir.join("refresh_tokens.json")).unwrap();
+        (dir, store)
+    }
+
+    #[test]
+    fn redeemed_token_rotates_within_its_family() {
+        let (dir, store) = temp_store();
+        let now = Utc::now();
+        let first = store.issue("green_agent", &AgentRole::Orchestrator, None, now).unwrap();
+        assert_eq!(store.holder(&first).unwrap().as_deref(), Some("green_agent"));
+
+        let grant = store.redeem(&first, now.timestamp()).unwrap();
+        assert_eq!((grant.agent_id.as_str(), &grant.role), ("green_agent", &AgentRole::Orchestrator));
+        let second = store.issue(&grant.agent_id, &grant.role, Some(grant.family.clone()), now).unwrap();
+        assert_eq!(store.redeem(&second, now.timestamp()).unwrap().family, grant.family);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn reused_token_revokes_its_whole_family() {
+        let (dir, store) = temp_store();
+        let now = Utc::now();
+        let first = store.issue("green_agent", &AgentRole::Orchestrator, None, now).unwrap();
+        let other_login = store.issue("green_agent", &AgentRole::Orchestrator, None, now).unwrap();
+        let family = store.redeem(&first, now.timestamp()).unwrap().family;
+        let second = store.issue("green_agent", &AgentRole::Orchestrator, Some(family), now).unwrap();
+
+        assert!(matches!(store.redeem(&first, now.timestamp()),
+                         Err(RefreshError::Reused { agent_id }) if agent_id == "green_agent"));
+        assert!(matches!(store.redeem(&second, now.timestamp()), Err(RefreshError::Unknown)));
+        assert!(matches!(store.redeem(&first, now.timestamp()), Err(RefreshError::Unknown)));
+        assert!(store.redeem(&other_login, now.timestamp()).is_ok());
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn token_is_redeemed_once_across_replicas() {
+        let (dir, first) = temp_store();
+        let second = RefreshStore::open(dir.join("refresh_tokens.json")).unwrap();
+        let now = Utc::now();
+        let token = first.issue("purple_agent", &AgentRole::Modernizer, None, now).unwrap();
+
+        assert!(second.redeem(&token, now.timestamp()).is_ok());
+        assert!(matches!(first.redeem(&token, now.timestamp()), Err(RefreshError::Reused { .. })));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn expired_and_revoked_tokens_are_refused() {
+        let (dir, store) = temp_store();
+        let now = Utc::now();
+        let expired = store.issue("green_agent", &AgentRole::Orchestrator, None, now).unwrap();
+        assert!(matches!(store.redeem(&expired, now.timestamp() + REFRESH_TOKEN_TTL_SECS), Err(RefreshError::Expired)));
+
+        let before = store.issue("purple_agent", &AgentRole::Modernizer, None, now).unwrap();
+        let after = store.issue("purple_agent", &AgentRole::Modernizer, None, now + Duration::milliseconds(1)).unwrap();
+        store.revoke_agent("purple_agent", now.timestamp_millis() + 1).unwrap();
+        assert!(matches!(store.redeem(&before, now.timestamp()), Err(RefreshError::Unknown)));
+        assert!(store.redeem(&after, now.timestamp()).is_ok());
+        assert!(matches!(store.redeem("never-issued", now.timestamp()), Err(RefreshError::Unknown)));
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
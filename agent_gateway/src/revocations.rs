@@ -0,0 +1,343 @@
+// Revocations - access tokens that stop working before they expire
+//
+// Tokens are revoked one at a time by jti, or by a cutoff: every token of an
+// agent, of one of its tasks, or of everyone issued before a point in time.
+// Cutoffs are kept in milliseconds and compared with a token's `iat_ms`, so a
+// token issued in the same second as a revocation but after it stays valid.
+// The list is kept in REVOCATIONS_PATH, which gateway replicas share like the
+// agent registry: a revocation takes effect here at once, is then merged
+// into the file under its lock (off the request workers, see `PendingSave`),
+// and the other replicas merge the file in when they see it change. Every entry
+// carries the time after which it can no longer match a live credential, so
+// the list only ever holds what is still relevant.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::agents;
+use crate::refresh::REFRESH_TOKEN_TTL_SECS;
+use crate::Claims;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+struct Entries {
+    jtis: HashMap<String, usize>,                          // jti -> exp of the revoked token
+    agent_cutoffs: HashMap<String, i64>,                   // agent_id -> tokens issued before this (ms) are revoked
+    task_cutoffs: HashMap<String, HashMap<String, i64>>,   // agent_id -> task_id -> likewise, delegated tokens only
+    issued_before: Option<i64>,                            // tokens issued before this (ms) are revoked for everyone
+}
+
+impl Entries {
+    fn size(&self) -> usize {
+        self.jtis.len()
+            + self.agent_cutoffs.len()
+            + self.task_cutoffs.values().map(HashMap::len).sum::<usize>()
+            + usize::from(self.issued_before.is_some())
+    }
+
+    /// Take in `other`'s revocations; merging never un-revokes anything
+    fn merge(&mut self, other: Entries) {
+        for (jti, exp) in other.jtis {
+            let entry = self.jtis.entry(jti).or_insert(exp);
+            *entry = (*entry).max(exp);
+        }
+        for (agent_id, cutoff) in other.agent_cutoffs {
+            later(self.agent_cutoffs.entry(agent_id).or_insert(cutoff), cutoff);
+        }
+        for (agent_id, tasks) in other.task_cutoffs {
+            let mine = self.task_cutoffs.entry(agent_id).or_default();
+            for (task_id, cutoff) in tasks {
+                later(mine.entry(task_id).or_insert(cutoff), cutoff);
+            }
+        }
+        if let Some(cutoff) = other.issued_before {
+            later(self.issued_before.get_or_insert(cutoff), cutoff);
+        }
+    }
+}
+
+/// One revocation request
+#[derive(Debug, Clone)]
+pub enum Revocation {
+    Jti { jti: String, exp: usize },
+    Agent { agent_id: String, issued_before_ms: i64 },
+    Task { agent_id: String, task_id: String, issued_before_ms: i64 },
+    All { issued_before_ms: i64 }, // Every agent's tokens
+}
+
+impl Revocation {
+    fn apply(&self, entries: &mut Entries) {
+        match self {
+            Revocation::Jti { jti, exp } => {
+                let entry = entries.jtis.entry(jti.clone()).or_insert(*exp);
+                *entry = (*entry).max(*exp);
+            }
+            Revocation::Agent { agent_id, issued_before_ms } => {
+                later(entries.agent_cutoffs.entry(agent_id.clone()).or_insert(0), *issued_before_ms);
+            }
+            Revocation::Task { agent_id, task_id, issued_before_ms } => {
+                let tasks = entries.task_cutoffs.entry(agent_id.clone()).or_default();
+                later(tasks.entry(task_id.clone()).or_insert(0), *issued_before_ms);
+            }
+            Revocation::All { issued_before_ms } => later(entries.issued_before.get_or_insert(0), *issued_before_ms),
+        }
+    }
+}
+
+pub struct RevocationList {
+    path: PathBuf,
+    entries: Entries,
+}
+
+/// This replica's revocations, to be merged into the shared file. Writing
+/// takes the file lock and syncs, so callers do it on the blocking pool
+/// rather than under the list's own lock.
+pub struct PendingSave {
+    path: PathBuf,
+    entries: Entries,
+}
+
+/// The file as saved, with the other replicas' revocations in it
+pub struct Saved(Entries);
+
+impl PendingSave {
+    pub fn write(self) -> io::Result<Saved> {
+        let _lock = agents::lock(&self.path)?;
+        let mut entries = load(&self.path)?;
+        entries.merge(self.entries);
+        let json = serde_json::to_vec(&entries).map_err(io::Error::other)?;
+        agents::replace_file(&self.path, &json)?;
+        Ok(Saved(entries))
+    }
+}
+
+impl RevocationList {
+    /// Load the revocations kept at `path`, which need not exist yet
+    pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)?;
+        }
+        let entries = load(&path)?;
+        Ok(RevocationList { path, entries })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Merge in the file, e.g. after another replica revoked tokens
+    pub fn reload(&mut self) -> io::Result<()> {
+        let entries = load(&self.path)?;
+        self.entries.merge(entries);
+        Ok(())
+    }
+
+    /// Revoke on this replica now; the revocation holds here even if saving
+    /// the returned `PendingSave` fails
+    pub fn revoke(&mut self, revocation: &Revocation) -> PendingSave {
+        revocation.apply(&mut self.entries);
+        PendingSave { path: self.path.clone(), entries: self.entries.clone() }
+    }
+
+    /// Take in the file as a `PendingSave` wrote it
+    pub fn saved(&mut self, saved: Saved) {
+        self.entries.merge(saved.0);
+    }
+
+    pub fn is_revoked(&self, claims: &Claims) -> bool {
+        let issued_at = claims.issued_at_ms();
+        self.entries.jtis.contains_key(&claims.jti)
+            || self.revokes_login(&claims.sub, issued_at)
+            || claims.task_id.as_ref().is_some_and(|task_id| {
+                self.entries.task_cutoffs.get(&claims.sub)
+                    .and_then(|tasks| tasks.get(task_id))
+                    .is_some_and(|c| issued_at < *c)
+            })
+    }
+
+    /// Whether a credential the agent got at `issued_at_ms`, such as a refresh
+    /// token, falls under an agent-wide or global cutoff
+    pub fn revokes_login(&self, agent_id: &str, issued_at_ms: i64) -> bool {
+        self.entries.agent_cutoffs.get(agent_id).is_some_and(|c| issued_at_ms < *c)
+            || self.entries.issued_before.is_some_and(|c| issued_at_ms < c)
+    }
+
+    /// Drop entries that can no longer match an unexpired token. Cutoffs also
+    /// cover refresh tokens, the longest-lived credential.
+    pub fn prune(&mut self, now_ms: i64) -> io::Result<()> {
+        let now = (now_ms / 1000) as usize;
+        let oldest_live = now_ms - REFRESH_TOKEN_TTL_SECS * 1000;
+        let prune = |entries: &mut Entries| {
+            entries.jtis.retain(|_, exp| *exp >= now);
+            entries.agent_cutoffs.retain(|_, cutoff| *cutoff > oldest_live);
+            entries.task_cutoffs.retain(|_, tasks| {
+                tasks.retain(|_, cutoff| *cutoff > oldest_live);
+                !tasks.is_empty()
+            });
+            if entries.issued_before.is_some_and(|c| c <= oldest_live) {
+                entries.issued_before = None;
+            }
+        };
+        // Rewriting an unchanged file would only make the other replicas reload it
+        let before = self.entries.size();
+        prune(&mut self.entries);
+        if self.entries.size() == before {
+            return Ok(());
+        }
+        self.update(prune)
+    }
+
+    /// Individually revoked tokens not yet expired
+    pub fn len(&self) -> usize {
+        self.entries.jtis.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.jtis.is_empty()
+    }
+
+    /// Apply a change here, then under the lock merge it into the file. The
+    /// change holds on this replica even if the file cannot be written.
+    fn update(&mut self, change: impl Fn(&mut Entries)) -> io::Result<()> {
+        change(&mut self.entries);
+        let _lock = agents::lock(&self.path)?;
+        let mut entries = load(&self.path)?;
+        entries.merge(self.entries.clone());
+        change(&mut entries);
+        let json = serde_json::to_vec(&entries).map_err(io::Error::other)?;
+        agents::replace_file(&self.path, &json)?;
+        self.entries = entries;
+        Ok(())
+    }
+}
+
+fn later(cutoff: &mut i64, other: i64) {
+    *cutoff = (*cutoff).max(other);
+}
+
+fn load(path: &Path) -> io::Result<Entries> {
+    match fs::read(path) {
+        Ok(bytes) => serde_json::from_slice(&bytes)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path.display(), e))),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Entries::default()),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AgentRole;
+
+    const T0: i64 = 1_800_000_000_000; // ms
+
+    fn temp_list() -> (PathBuf, RevocationList) {
+        // A directory of its own, for the lock and temporary files next to the list
+        let path = std::env::temp_dir().join(format!("revocations-test-{}", uuid::Uuid::new_v4())).join("revocations.json");
+        let list = RevocationList::open(&path).unwrap();
+        (path, list)
+    }
+
+    /// Revoke and save, as the gateway does in two steps around web::block
+    fn revoke(list: &mut RevocationList, revocation: Revocation) {
+        let saved = list.revoke(&revocation).write().unwrap();
+        list.saved(saved);
+    }
+
+    fn token(agent_id: &str, task_id: Option<&str>, iat_ms: i64) -> Claims {
+        Claims {
+            sub: agent_id.to_string(),
+            role: AgentRole::Orchestrator,
+            exp: (iat_ms / 1000) as usize + 3600,
+            iat: (iat_ms / 1000) as usize,
+            iat_ms: Some(iat_ms),
+            jti: format!("{}-{}", agent_id, iat_ms),
+            allowed_mcps: Vec::new(),
+            task_id: task_id.map(String::from),
+            scope: None,
+        }
+    }
+
+    #[test]
+    fn agent_cutoff_spares_tokens_issued_after_it() {
+        let (path, mut list) = temp_list();
+        revoke(&mut list, Revocation::Agent { agent_id: "green_agent".to_string(), issued_before_ms: T0 });
+
+        assert!(list.is_revoked(&token("green_agent", None, T0 - 1)));
+        assert!(list.is_revoked(&token("green_agent", Some("job-1"), T0 - 1)));
+        assert!(!list.is_revoked(&token("green_agent", None, T0)));
+        assert!(!list.is_revoked(&token("purple_agent", None, T0 - 1)));
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn cutoffs_within_one_second_use_milliseconds() {
+        let (path, mut list) = temp_list();
+        revoke(&mut list, Revocation::All { issued_before_ms: T0 + 500 });
+
+        assert!(list.is_revoked(&token("purple_agent", None, T0 + 499)));
+        assert!(!list.is_revoked(&token("purple_agent", None, T0 + 500)));
+        // Without iat_ms a token counts as issued at the end of its second
+        let mut legacy = token("purple_agent", None, T0);
+        legacy.iat_ms = None;
+        assert!(!list.is_revoked(&legacy));
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn task_cutoff_only_covers_that_task() {
+        let (path, mut list) = temp_list();
+        revoke(&mut list, Revocation::Task { agent_id: "green_agent".to_string(), task_id: "job-1".to_string(), issued_before_ms: T0 });
+
+        assert!(list.is_revoked(&token("green_agent", Some("job-1"), T0 - 1)));
+        assert!(!list.is_revoked(&token("green_agent", Some("job-2"), T0 - 1)));
+        assert!(!list.is_revoked(&token("green_agent", None, T0 - 1)));
+        assert!(!list.revokes_login("green_agent", T0 - 1));
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn later_cutoff_wins_and_none_is_undone() {
+        let (path, mut list) = temp_list();
+        revoke(&mut list, Revocation::Agent { agent_id: "green_agent".to_string(), issued_before_ms: T0 + 1000 });
+        revoke(&mut list, Revocation::Agent { agent_id: "green_agent".to_string(), issued_before_ms: T0 });
+
+        assert!(list.revokes_login("green_agent", T0 + 999));
+        assert!(!list.revokes_login("green_agent", T0 + 1000));
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn revocations_reach_other_replicas_through_the_file() {
+        let (path, mut first) = temp_list();
+        let mut second = RevocationList::open(&path).unwrap();
+        let revoked = token("purple_agent", None, T0);
+        revoke(&mut first, Revocation::Jti { jti: revoked.jti.clone(), exp: revoked.exp });
+        revoke(&mut second, Revocation::Agent { agent_id: "green_agent".to_string(), issued_before_ms: T0 });
+
+        assert!(second.is_revoked(&revoked));
+        first.reload().unwrap();
+        assert!(first.revokes_login("green_agent", T0 - 1));
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+
+    #[test]
+    fn prune_drops_what_can_no_longer_match() {
+        let (path, mut list) = temp_list();
+        let now = T0 + REFRESH_TOKEN_TTL_SECS * 1000 + 1;
+        revoke(&mut list, Revocation::Jti { jti: "expired".to_string(), exp: (T0 / 1000) as usize });
+        revoke(&mut list, Revocation::Jti { jti: "live".to_string(), exp: (now / 1000) as usize + 60 });
+        revoke(&mut list, Revocation::Agent { agent_id: "green_agent".to_string(), issued_before_ms: T0 });
+        revoke(&mut list, Revocation::Agent { agent_id: "purple_agent".to_string(), issued_before_ms: now });
+        list.prune(now).unwrap();
+
+        assert_eq!(list.len(), 1);
+        assert!(!list.revokes_login("green_agent", T0 - 1));
+        assert!(list.revokes_login("purple_agent", now - 1));
+        assert_eq!(RevocationList::open(&path).unwrap().len(), 1);
+        fs::remove_dir_all(path.parent().unwrap()).unwrap();
+    }
+}
@@ -0,0 +1,163 @@
+// Upstream Calls - timeouts, retries and circuit breakers for MCP servers
+//
+// Every forwarded call goes through one shared connection pool with the
+// target server's timeout from the policy. Calls to idempotent operations are
+// retried with exponential backoff when the server could not be reached or
+// answered 502/503/504; anything else is tried exactly once. A circuit
+// breaker per server counts consecutive failures: once it opens, calls fail
+// fast with 503 until `open_secs` have passed, then a single trial call is let
+// through and its outcome closes or reopens the circuit. A 500 from an MCP
+// server is a tool reporting its own failure (a COBOL compile error, say) and
+// counts as a healthy answer.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::policy::{CircuitBreakerPolicy, RetryPolicy};
+
+/// Where and how one forwarded call is made
+#[derive(Debug, Clone)]
+pub struct Upstream {
+    pub mcp: String,
+    pub url: String,
+    pub timeout: Duration,
+    pub retry: RetryPolicy, // max_attempts is 1 for operations that are not idempotent
+    pub breaker: CircuitBreakerPolicy,
+}
+
+impl Upstream {
+    /// Wait before attempt number `attempt` (2 for the first retry)
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let doublings = attempt.saturating_sub(2).min(16);
+        Duration::from_millis(self.retry.backoff_ms.saturating_mul(1 << doublings))
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum BreakerState {
+    Closed,   // Calls go through
+    Open,     // Calls fail fast
+    HalfOpen, // One trial call decides
+}
+
+impl BreakerState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            BreakerState::Closed => "closed",
+            BreakerState::Open => "open",
+            BreakerState::HalfOpen => "half_open",
+        }
+    }
+
+    /// Gauge value: 0 closed, 1 half-open, 2 open
+    pub fn level(&self) -> i64 {
+        match self {
+            BreakerState::Closed => 0,
+            BreakerState::HalfOpen => 1,
+            BreakerState::Open => 2,
+        }
+    }
+}
+
+/// Why a forwarded call produced no result
+#[derive(Debug)]
+pub enum CallError {
+    CircuitOpen { mcp: String, retry_after_secs: u64 },
+    Failed(String),
+}
+
+impl fmt::Display for CallError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CallError::CircuitOpen { mcp, retry_after_secs } => write!(
+                f, "MCP server '{}' is failing; circuit open, retry in {}s", mcp, retry_after_secs),
+            CallError::Failed(message) => f.write_str(message),
+        }
+    }
+}
+
+#[derive(Default)]
+struct Breaker {
+    consecutive_failures: u32,
+    opened_at: Option<Instant>, // Set while open or half-open
+    trial_at: Option<Instant>,  // When the current half-open trial call started
+}
+
+impl Breaker {
+    fn state(&self, policy: &CircuitBreakerPolicy) -> BreakerState {
+        match self.opened_at {
+            None => BreakerState::Closed,
+            Some(at) if at.elapsed() < Duration::from_secs(policy.open_secs) => BreakerState::Open,
+            Some(_) => BreakerState::HalfOpen,
+        }
+    }
+}
+
+/// Circuit breaker state per MCP server, kept across policy reloads
+#[derive(Default)]
+pub struct CircuitBreakers {
+    breakers: Mutex<HashMap<String, Breaker>>,
+}
+
+impl CircuitBreakers {
+    /// Let a call to `mcp` through, or say how many seconds until one may be tried.
+    /// A trial call that never reports back gives way to another after `open_secs`.
+    pub fn admit(&self, mcp: &str, policy: &CircuitBreakerPolicy) -> Result<(), u64> {
+        let mut breakers = self.breakers.lock().unwrap();
+        let Some(breaker) = breakers.get_mut(mcp) else { return Ok(()) };
+        let open_for = Duration::from_secs(policy.open_secs);
+
+        match breaker.state(policy) {
+            BreakerState::Closed => Ok(()),
+            BreakerState::Open => {
+                let elapsed = breaker.opened_at.map_or(open_for, |at| at.elapsed());
+                Err(open_for.saturating_sub(elapsed).as_secs().max(1))
+            }
+            BreakerState::HalfOpen => match breaker.trial_at {
+                Some(at) if at.elapsed() < open_for => {
+                    Err(open_for.saturating_sub(at.elapsed()).as_secs().max(1))
+                }
+                _ => {
+                    breaker.trial_at = Some(Instant::now());
+                    Ok(())
+                }
+            },
+        }
+    }
+
+    /// Record how a call went; returns the new state if this changed it
+    pub fn record(&self, mcp: &str, policy: &CircuitBreakerPolicy, success: bool) -> Option<BreakerState> {
+        let mut breakers = self.breakers.lock().unwrap();
+        let breaker = breakers.entry(mcp.to_string()).or_default();
+        let before = breaker.state(policy);
+
+        if success {
+            *breaker = Breaker::default();
+        } else {
+            breaker.consecutive_failures = breaker.consecutive_failures.saturating_add(1);
+            let reopen = before == BreakerState::HalfOpen;
+            if reopen || (before == BreakerState::Closed && breaker.consecutive_failures >= policy.failure_threshold) {
+                breaker.opened_at = Some(Instant::now());
+                breaker.trial_at = None;
+            }
+        }
+
+        let after = breaker.state(policy);
+        (after != before).then_some(after)
+    }
+
+    pub fn state(&self, mcp: &str, policy: &CircuitBreakerPolicy) -> BreakerState {
+        self.breakers.lock().unwrap()
+            .get(mcp)
+            .map_or(BreakerState::Closed, |b| b.state(policy))
+    }
+
+    /// Forget servers the policy no longer declares
+    pub fn retain(&self, keep: impl Fn(&str) -> bool) {
+        self.breakers.lock().unwrap().retain(|mcp, _| keep(mcp));
+    }
+}
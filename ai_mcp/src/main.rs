@@ -4,20 +4,35 @@
 //   POST /translate_cobol      - Translate COBOL source to Rust
 //   POST /translate_assembler  - Translate Assembler to Rust
 //   POST /explain_code         - Explain COBOL code in plain English
+//   POST /mcp                  - The same operations as MCP tools (JSON-RPC)
 //   GET  /health               - Health check
+//   GET  /ready                - Readiness: the Claude API accepts our key and serves the model
+// Run with --stdio to serve MCP over stdin/stdout instead of HTTP.
 
 use actix_web::{web, App, HttpServer, HttpResponse, middleware};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use log::{info, error};
+use mcp_common::auth::{require_assertion, AssertionVerifier};
+use mcp_common::health::{self, Check};
+use mcp_common::mcp::{self, McpServer};
+use mcp_common::telemetry::{self, KeyValue};
+use mcp_common::tls::{self, TlsSettings};
 
 const CLAUDE_API_URL: &str = "https://api.anthropic.com/v1/messages";
 const CLAUDE_MODEL: &str = "claude-opus-4-6";
+const CLAUDE_MODELS_URL: &str = "https://api.anthropic.com/v1/models";
+
+/// How long a credential check result is reused, so readiness probes don't hit the API every time
+const CREDENTIAL_CHECK_TTL: std::time::Duration = std::time::Duration::from_secs(60);
 
 // ─── Request/Response Types ───────────────────────────────────────────────────
 
-#[derive(Deserialize)]
+#[derive(Deserialize, JsonSchema)]
 pub struct TranslateRequest {
+    /// Legacy source code to translate
     pub source: String,
+    /// Extra instructions or background appended to the prompt
     pub context: Option<String>,
 }
 
@@ -27,11 +42,21 @@ pub struct TranslateResponse {
     pub rust_code: Option<String>,
     pub explanation: Option<String>,
     pub model_used: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub usage: Option<Usage>, // Tokens the translation cost
     pub error: Option<String>,
 }
 
-#[derive(Deserialize)]
+/// Tokens billed for one Claude call
+#[derive(Serialize, Deserialize, Clone, Copy, Default)]
+pub struct Usage {
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+}
+
+#[derive(Deserialize, JsonSchema)]
 pub struct ExplainRequest {
+    /// COBOL source code to explain
     pub source: String,
 }
 
@@ -59,6 +84,14 @@ struct ClaudeMessage {
 #[derive(Deserialize)]
 struct ClaudeResponse {
     content: Vec<ClaudeContent>,
+    #[serde(default)]
+    usage: Usage,
+}
+
+/// Claude's answer text and what it cost
+struct Reply {
+    text: String,
+    usage: Usage,
 }
 
 #[derive(Deserialize)]
@@ -73,6 +106,7 @@ struct ClaudeContent {
 pub struct AppState {
     pub claude_api_key: String,
     pub http_client: reqwest::Client,
+    pub credential_check: tokio::sync::Mutex<Option<(std::time::Instant, Check)>>, // Last /ready result
 }
 
 // ─── Handlers ─────────────────────────────────────────────────────────────────
@@ -80,8 +114,8 @@ pub struct AppState {
 /// Translate COBOL source to idiomatic Rust using Claude
 async fn translate_cobol(
     state: web::Data<AppState>,
-    body: web::Json<TranslateRequest>,
-) -> HttpResponse {
+    body: TranslateRequest,
+) -> Result<TranslateResponse, TranslateResponse> {
     info!("Translating COBOL to Rust ({} chars)", body.source.len());
 
     let prompt = format!(
@@ -116,9 +150,9 @@ Return ONLY the Rust source code."#,
     );
 
     match call_claude(&state, &prompt).await {
-        Ok(rust_code) => {
+        Ok(reply) => {
             // Clean up any markdown code blocks if present
-            let clean_code = rust_code
+            let clean_code = reply.text
                 .trim()
                 .trim_start_matches("```rust")
                 .trim_start_matches("```")
@@ -126,22 +160,25 @@ Return ONLY the Rust source code."#,
                 .trim()
                 .to_string();
 
-            info!("Translation complete: {} chars of Rust generated", clean_code.len());
-            HttpResponse::Ok().json(TranslateResponse {
+            info!("Translation complete: {} chars of Rust generated ({} input / {} output tokens)",
+                  clean_code.len(), reply.usage.input_tokens, reply.usage.output_tokens);
+            Ok(TranslateResponse {
                 success: true,
                 rust_code: Some(clean_code),
                 explanation: None,
                 model_used: CLAUDE_MODEL.to_string(),
+                usage: Some(reply.usage),
                 error: None,
             })
         }
         Err(e) => {
             error!("Claude translation failed: {}", e);
-            HttpResponse::InternalServerError().json(TranslateResponse {
+            Err(TranslateResponse {
                 success: false,
                 rust_code: None,
                 explanation: None,
                 model_used: CLAUDE_MODEL.to_string(),
+                usage: None,
                 error: Some(e),
             })
         }
@@ -151,8 +188,8 @@ Return ONLY the Rust source code."#,
 /// Translate Assembler source to Rust
 async fn translate_assembler(
     state: web::Data<AppState>,
-    body: web::Json<TranslateRequest>,
-) -> HttpResponse {
+    body: TranslateRequest,
+) -> Result<TranslateResponse, TranslateResponse> {
     info!("Translating Assembler to Rust ({} chars)", body.source.len());
 
     let prompt = format!(
@@ -179,8 +216,8 @@ Return ONLY the Rust source code."#,
     );
 
     match call_claude(&state, &prompt).await {
-        Ok(rust_code) => {
-            let clean_code = rust_code
+        Ok(reply) => {
+            let clean_code = reply.text
                 .trim()
                 .trim_start_matches("```rust")
                 .trim_start_matches("```")
@@ -188,19 +225,21 @@ Return ONLY the Rust source code."#,
                 .trim()
                 .to_string();
 
-            HttpResponse::Ok().json(TranslateResponse {
+            Ok(TranslateResponse {
                 success: true,
                 rust_code: Some(clean_code),
                 explanation: None,
                 model_used: CLAUDE_MODEL.to_string(),
+                usage: Some(reply.usage),
                 error: None,
             })
         }
-        Err(e) => HttpResponse::InternalServerError().json(TranslateResponse {
+        Err(e) => Err(TranslateResponse {
             success: false,
             rust_code: None,
             explanation: None,
             model_used: CLAUDE_MODEL.to_string(),
+            usage: None,
             error: Some(e),
         }),
     }
@@ -209,20 +248,20 @@ Return ONLY the Rust source code."#,
 /// Explain COBOL code in plain English
 async fn explain_code(
     state: web::Data<AppState>,
-    body: web::Json<ExplainRequest>,
-) -> HttpResponse {
+    body: ExplainRequest,
+) -> Result<ExplainResponse, ExplainResponse> {
     let prompt = format!(
         "Explain what this COBOL program does in plain English:\n\n{}",
         body.source
     );
 
     match call_claude(&state, &prompt).await {
-        Ok(explanation) => HttpResponse::Ok().json(ExplainResponse {
+        Ok(reply) => Ok(ExplainResponse {
             success: true,
-            explanation: Some(explanation),
+            explanation: Some(reply.text),
             error: None,
         }),
-        Err(e) => HttpResponse::InternalServerError().json(ExplainResponse {
+        Err(e) => Err(ExplainResponse {
             success: false,
             explanation: None,
             error: Some(e),
@@ -230,6 +269,44 @@ async fn explain_code(
     }
 }
 
+/// GET /ready - translations need a Claude key that works for our model
+async fn ready(state: web::Data<AppState>) -> HttpResponse {
+    let check = {
+        // Held across the API call so concurrent probes share one request
+        let mut cached = state.credential_check.lock().await;
+        match cached.as_ref() {
+            Some((at, check)) if at.elapsed() < CREDENTIAL_CHECK_TTL => check.clone(),
+            _ => {
+                let check = check_claude_credentials(&state).await;
+                *cached = Some((std::time::Instant::now(), check.clone()));
+                check
+            }
+        }
+    };
+    health::readiness("ai-translation-mcp", "1.0.0", vec![check])
+}
+
+/// Look up our model with our key; a 401/403/404 means translations would fail too
+async fn check_claude_credentials(state: &AppState) -> Check {
+    if state.claude_api_key.trim().is_empty() {
+        return Check::fail("claude_api", "CLAUDE_API_KEY is empty");
+    }
+
+    let response = state.http_client
+        .get(format!("{}/{}", CLAUDE_MODELS_URL, CLAUDE_MODEL))
+        .header("x-api-key", &state.claude_api_key)
+        .header("anthropic-version", "2023-06-01")
+        .timeout(health::CHECK_TIMEOUT)
+        .send()
+        .await;
+
+    match response {
+        Ok(r) if r.status().is_success() => Check::pass("claude_api", format!("{} available", CLAUDE_MODEL)),
+        Ok(r) => Check::fail("claude_api", format!("Claude API returned {} for {}", r.status(), CLAUDE_MODEL)),
+        Err(e) => Check::fail("claude_api", format!("Claude API unreachable: {}", e)),
+    }
+}
+
 async fn health() -> HttpResponse {
     HttpResponse::Ok().json(serde_json::json!({
         "status": "healthy",
@@ -239,9 +316,36 @@ async fn health() -> HttpResponse {
     }))
 }
 
+// ─── MCP Tools ────────────────────────────────────────────────────────────────
+
+fn mcp_server(state: &web::Data<AppState>) -> McpServer {
+    McpServer::new("ai-translation-mcp", "1.0.0")
+        .tool("translate_cobol", "Translate COBOL source to Rust", {
+            let state = state.clone();
+            move |req| translate_cobol(state.clone(), req)
+        })
+        .tool("translate_assembler", "Translate mainframe Assembler to Rust", {
+            let state = state.clone();
+            move |req| translate_assembler(state.clone(), req)
+        })
+        .tool("explain_code", "Explain COBOL code in plain English", {
+            let state = state.clone();
+            move |req| explain_code(state.clone(), req)
+        })
+}
+
 // ─── Claude API Helper ────────────────────────────────────────────────────────
 
-async fn call_claude(state: &AppState, prompt: &str) -> Result<String, String> {
+async fn call_claude(state: &AppState, prompt: &str) -> Result<Reply, String> {
+    let attributes = vec![
+        KeyValue::new("gen_ai.system", "anthropic"),
+        KeyValue::new("gen_ai.request.model", CLAUDE_MODEL),
+        KeyValue::new("gen_ai.prompt.chars", prompt.len() as i64),
+    ];
+    telemetry::in_span("claude messages", attributes, send_to_claude(state, prompt)).await
+}
+
+async fn send_to_claude(state: &AppState, prompt: &str) -> Result<Reply, String> {
     let request = ClaudeRequest {
         model: CLAUDE_MODEL.to_string(),
         max_tokens: 32768,
@@ -271,11 +375,12 @@ async fn call_claude(state: &AppState, prompt: &str) -> Result<String, String> {
         .await
         .map_err(|e| format!("Failed to parse Claude response: {}", e))?;
 
-    claude_response.content
+    let text = claude_response.content
         .into_iter()
         .find(|c| c.content_type == "text")
         .and_then(|c| c.text)
-        .ok_or("Empty response from Claude".to_string())
+        .ok_or("Empty response from Claude".to_string())?;
+    Ok(Reply { text, usage: claude_response.usage })
 }
 
 // ─── Main ─────────────────────────────────────────────────────────────────────
@@ -283,6 +388,7 @@ async fn call_claude(state: &AppState, prompt: &str) -> Result<String, String> {
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
     env_logger::init_from_env(env_logger::Env::new().default_filter_or("info"));
+    let telemetry = telemetry::init("ai-translation-mcp");
 
     let claude_api_key = std::env::var("CLAUDE_API_KEY")
         .expect("CLAUDE_API_KEY must be set");
@@ -293,21 +399,42 @@ async fn main() -> std::io::Result<()> {
             .timeout(std::time::Duration::from_secs(120))
             .build()
             .unwrap(),
+        credential_check: tokio::sync::Mutex::new(None),
     });
+    let server = web::Data::new(mcp_server(&state));
+
+    if std::env::args().any(|a| a == "--stdio") {
+        let result = server.serve_stdio().await;
+        telemetry.shutdown();
+        return result;
+    }
 
     let bind_addr = std::env::var("BIND_ADDR").unwrap_or("0.0.0.0:8082".to_string());
     info!("🤖 AI Translation MCP Service starting on {} using {}", bind_addr, CLAUDE_MODEL);
 
-    HttpServer::new(move || {
+    // Every operation must carry a gateway assertion addressed to this server
+    let verifier = web::Data::new(AssertionVerifier::from_env("ai_mcp")?);
+    let tls = TlsSettings::from_env();
+
+    let http = HttpServer::new(move || {
         App::new()
             .app_data(state.clone())
+            .app_data(server.clone())
+            .app_data(verifier.clone())
+            .wrap(middleware::from_fn(require_assertion))
+            .wrap(middleware::from_fn(telemetry::trace_requests))
             .wrap(middleware::Logger::default())
-            .route("/translate_cobol", web::post().to(translate_cobol))
-            .route("/translate_assembler", web::post().to(translate_assembler))
-            .route("/explain_code", web::post().to(explain_code))
             .route("/health", web::get().to(health))
+            .route("/ready", web::get().to(ready))
+            .configure(mcp::configure)
     })
-    .bind(&bind_addr)?
-    .run()
-    .await
+    .on_connect(tls::on_connect);
+
+    let http = match &tls {
+        Some(tls) => http.bind_rustls_0_23(&bind_addr, tls.server_config()?)?,
+        None => http.bind(&bind_addr)?,
+    };
+    let result = http.run().await;
+    telemetry.shutdown();
+    result
 }
@@ -4,21 +4,41 @@
 //   POST /compile          - Compile and execute COBOL source
 //   POST /execute          - Execute pre-compiled COBOL
 //   POST /validate_syntax  - Validate COBOL syntax only
+//   POST /mcp              - The same operations as MCP tools (JSON-RPC)
 //   GET  /health           - Health check
+//   GET  /ready            - Readiness: cobc runs and the scratch directory is writable
+// Run with --stdio to serve MCP over stdin/stdout instead of HTTP.
 
 use actix_web::{web, App, HttpServer, HttpResponse, middleware};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
 use std::process::Command;
 use std::fs;
 use uuid::Uuid;
 use log::{info, error};
+use mcp_common::auth::{require_assertion, AssertionVerifier};
+use mcp_common::health;
+use mcp_common::mcp::{self, McpServer};
+use mcp_common::run::{self, RunInput, RunOutput};
+use mcp_common::telemetry::{self, KeyValue};
+use mcp_common::tls::{self, TlsSettings};
 
 // ─── Request/Response Types ───────────────────────────────────────────────────
 
-#[derive(Deserialize)]
+#[derive(Deserialize, JsonSchema)]
 pub struct CompileRequest {
-    pub source: String,           // COBOL source code
-    pub input_data: Option<String>, // Optional stdin input
+    /// COBOL source code
+    pub source: String,
+    /// Optional stdin input
+    pub input_data: Option<String>,
+    /// Files to create in the program's working directory, by file name; shared by every case
+    #[serde(default)]
+    pub files: BTreeMap<String, String>,
+    /// Test cases to run the program on, instead of a single run with input_data
+    #[serde(default)]
+    pub cases: Vec<RunInput>,
 }
 
 #[derive(Serialize)]
@@ -27,10 +47,13 @@ pub struct CompileResponse {
     pub output: Option<String>,   // Execution output
     pub compile_log: Option<String>,
     pub error: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub cases: Vec<RunOutput>,    // One per case, in order
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, JsonSchema)]
 pub struct ValidateRequest {
+    /// COBOL source code
     pub source: String,
 }
 
@@ -45,20 +68,31 @@ pub struct ValidateResponse {
 
 /// Compile COBOL source and execute it, returning stdout output
 async fn compile(
-    body: web::Json<CompileRequest>,
-) -> HttpResponse {
+    body: CompileRequest,
+) -> Result<CompileResponse, CompileResponse> {
     let job_id = Uuid::new_v4().to_string();
     let work_dir = format!("/tmp/cobol_{}", job_id);
 
     info!("Compiling COBOL job: {}", job_id);
 
+    if let Err(e) = run::check_cases(&body.files, &body.cases) {
+        return Err(CompileResponse {
+            success: false,
+            output: None,
+            compile_log: None,
+            error: Some(e),
+            cases: vec![],
+        });
+    }
+
     // Create working directory
     if let Err(e) = fs::create_dir_all(&work_dir) {
-        return HttpResponse::InternalServerError().json(CompileResponse {
+        return Err(CompileResponse {
             success: false,
             output: None,
             compile_log: None,
             error: Some(format!("Failed to create work dir: {}", e)),
+            cases: vec![],
         });
     }
 
@@ -67,18 +101,21 @@ async fn compile(
 
     // Write COBOL source
     if let Err(e) = fs::write(&source_path, &body.source) {
-        return HttpResponse::InternalServerError().json(CompileResponse {
+        return Err(CompileResponse {
             success: false,
             output: None,
             compile_log: None,
             error: Some(format!("Failed to write source: {}", e)),
+            cases: vec![],
         });
     }
 
     // Compile with GnuCOBOL
-    let compile_result = Command::new("cobc")
-        .args(["-x", "-o", &binary_path, &source_path])
-        .output();
+    let compile_result = telemetry::in_span("cobc compile", vec![KeyValue::new("cobol.job_id", job_id.clone())], async {
+        Command::new("cobc")
+            .args(["-x", "-o", &binary_path, &source_path])
+            .output()
+    }).await;
 
     match compile_result {
         Ok(output) => {
@@ -87,92 +124,61 @@ async fn compile(
             if !output.status.success() {
                 error!("COBOL compile failed: {}", compile_log);
                 cleanup(&work_dir);
-                return HttpResponse::Ok().json(CompileResponse {
+                return Ok(CompileResponse {
                     success: false,
                     output: None,
                     compile_log: Some(compile_log),
                     error: Some("COBOL compilation failed".to_string()),
+                    cases: vec![],
                 });
             }
 
             info!("COBOL compiled successfully, executing...");
 
-            // Execute the compiled binary
-            let mut exec_cmd = Command::new(&binary_path);
-
-            // Provide input data if available
-            if let Some(input) = &body.input_data {
-                use std::process::Stdio;
-                use std::io::Write;
-
-                let mut child = exec_cmd
-                    .stdin(Stdio::piped())
-                    .stdout(Stdio::piped())
-                    .stderr(Stdio::piped())
-                    .spawn()
-                    .map_err(|e| e.to_string());
-
-                match child {
-                    Ok(mut c) => {
-                        if let Some(stdin) = c.stdin.take() {
-                            let mut stdin = stdin;
-                            let _ = stdin.write_all(input.as_bytes());
-                        }
-                        match c.wait_with_output() {
-                            Ok(exec_output) => {
-                                let stdout = String::from_utf8_lossy(&exec_output.stdout).to_string();
-                                info!("COBOL execution output: {}", stdout.trim());
-                                cleanup(&work_dir);
-                                return HttpResponse::Ok().json(CompileResponse {
-                                    success: true,
-                                    output: Some(stdout),
-                                    compile_log: Some(compile_log),
-                                    error: None,
-                                });
-                            }
-                            Err(e) => {
-                                cleanup(&work_dir);
-                                return HttpResponse::InternalServerError().json(CompileResponse {
-                                    success: false,
-                                    output: None,
-                                    compile_log: Some(compile_log),
-                                    error: Some(format!("Execution failed: {}", e)),
-                                });
-                            }
-                        }
-                    }
-                    Err(e) => {
-                        cleanup(&work_dir);
-                        return HttpResponse::InternalServerError().json(CompileResponse {
-                            success: false,
-                            output: None,
-                            compile_log: Some(compile_log),
-                            error: Some(format!("Failed to spawn process: {}", e)),
-                        });
-                    }
-                }
-            }
+            // One run with input_data, or one per test case
+            let single = body.cases.is_empty();
+            let cases = run::runs(body.input_data, body.files, body.cases);
+            let attributes = vec![
+                KeyValue::new("cobol.job_id", job_id.clone()),
+                KeyValue::new("cobol.cases", cases.len() as i64),
+            ];
+            let outputs = telemetry::in_span("cobol execute", attributes,
+                run::run_cases(Path::new(&binary_path), Path::new(&work_dir), &cases)).await;
+            cleanup(&work_dir);
 
-            // No input data - execute directly
-            match exec_cmd.output() {
-                Ok(exec_output) => {
-                    let stdout = String::from_utf8_lossy(&exec_output.stdout).to_string();
-                    info!("COBOL output: {}", stdout.trim());
-                    cleanup(&work_dir);
-                    HttpResponse::Ok().json(CompileResponse {
+            if !single {
+                info!("COBOL ran {} cases, {} failed", outputs.len(), outputs.iter().filter(|o| !o.success).count());
+                return Ok(CompileResponse {
+                    success: true,
+                    output: None,
+                    compile_log: Some(compile_log),
+                    error: None,
+                    cases: outputs,
+                });
+            }
+            match outputs.into_iter().next() {
+                Some(RunOutput { success: true, output, .. }) => {
+                    info!("COBOL output: {}", output.as_deref().unwrap_or("").trim());
+                    Ok(CompileResponse {
                         success: true,
-                        output: Some(stdout),
+                        output,
                         compile_log: Some(compile_log),
                         error: None,
+                        cases: vec![],
                     })
                 }
-                Err(e) => {
-                    cleanup(&work_dir);
-                    HttpResponse::InternalServerError().json(CompileResponse {
+                failed => {
+                    // Whatever it printed before failing helps to see why
+                    let (output, error) = failed.map_or((None, None), |o| {
+                        let error = o.failure();
+                        (o.output, error)
+                    });
+                    Err(CompileResponse {
                         success: false,
-                        output: None,
+                        output,
                         compile_log: Some(compile_log),
-                        error: Some(format!("Execution failed: {}", e)),
+                        error,
+                        cases: vec![],
                     })
                 }
             }
@@ -180,11 +186,12 @@ async fn compile(
         Err(e) => {
             error!("Failed to run cobc: {}", e);
             cleanup(&work_dir);
-            HttpResponse::InternalServerError().json(CompileResponse {
+            Err(CompileResponse {
                 success: false,
                 output: None,
                 compile_log: None,
                 error: Some(format!("cobc not found or failed: {}", e)),
+                cases: vec![],
             })
         }
     }
@@ -192,8 +199,8 @@ async fn compile(
 
 /// Validate COBOL syntax without executing
 async fn validate_syntax(
-    body: web::Json<ValidateRequest>,
-) -> HttpResponse {
+    body: ValidateRequest,
+) -> Result<ValidateResponse, ValidateResponse> {
     let job_id = Uuid::new_v4().to_string();
     let work_dir = format!("/tmp/cobol_validate_{}", job_id);
     let _ = fs::create_dir_all(&work_dir);
@@ -223,9 +230,9 @@ async fn validate_syntax(
                 .map(String::from)
                 .collect();
 
-            HttpResponse::Ok().json(ValidateResponse { valid, errors, warnings })
+            Ok(ValidateResponse { valid, errors, warnings })
         }
-        Err(e) => HttpResponse::InternalServerError().json(ValidateResponse {
+        Err(e) => Err(ValidateResponse {
             valid: false,
             errors: vec![e.to_string()],
             warnings: vec![],
@@ -244,28 +251,68 @@ async fn health() -> HttpResponse {
     }))
 }
 
+/// GET /ready - compiles need cobc and somewhere to put the job files
+async fn ready() -> HttpResponse {
+    let checks = vec![
+        health::command("gnucobol", "cobc", &["--version"]).await,
+        health::writable_dir("scratch", std::path::Path::new("/tmp")),
+    ];
+    health::readiness("cobol-mcp", "1.0.0", checks)
+}
+
 fn cleanup(dir: &str) {
     let _ = fs::remove_dir_all(dir);
 }
 
+// ─── MCP Tools ────────────────────────────────────────────────────────────────
+
+fn mcp_server() -> McpServer {
+    McpServer::new("cobol-mcp", "1.0.0")
+        .tool("compile", "Compile COBOL source with GnuCOBOL and execute it", compile)
+        .tool("execute", "Execute COBOL source (compiles first)", compile)
+        .tool("validate_syntax", "Validate COBOL syntax only", validate_syntax)
+}
+
 // ─── Main ─────────────────────────────────────────────────────────────────────
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
     env_logger::init_from_env(env_logger::Env::new().default_filter_or("info"));
+    let telemetry = telemetry::init("cobol-mcp");
+
+    let server = web::Data::new(mcp_server());
+
+    if std::env::args().any(|a| a == "--stdio") {
+        let result = server.serve_stdio().await;
+        telemetry.shutdown();
+        return result;
+    }
 
     let bind_addr = std::env::var("BIND_ADDR").unwrap_or("0.0.0.0:8083".to_string());
     info!("⚙️  COBOL MCP Service starting on {}", bind_addr);
 
-    HttpServer::new(|| {
+    // Every operation must carry a gateway assertion addressed to this server
+    let verifier = web::Data::new(AssertionVerifier::from_env("cobol_mcp")?);
+    let tls = TlsSettings::from_env();
+
+    let http = HttpServer::new(move || {
         App::new()
+            .app_data(server.clone())
+            .app_data(verifier.clone())
+            .wrap(middleware::from_fn(require_assertion))
+            .wrap(middleware::from_fn(telemetry::trace_requests))
             .wrap(middleware::Logger::default())
-            .route("/compile", web::post().to(compile))
-            .route("/execute", web::post().to(compile)) // same handler
-            .route("/validate_syntax", web::post().to(validate_syntax))
             .route("/health", web::get().to(health))
+            .route("/ready", web::get().to(ready))
+            .configure(mcp::configure)
     })
-    .bind(&bind_addr)?
-    .run()
-    .await
+    .on_connect(tls::on_connect);
+
+    let http = match &tls {
+        Some(tls) => http.bind_rustls_0_23(&bind_addr, tls.server_config()?)?,
+        None => http.bind(&bind_addr)?,
+    };
+    let result = http.run().await;
+    telemetry.shutdown();
+    result
 }
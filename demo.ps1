@@ -15,14 +15,24 @@ $Payload = @{
 # 3. Trigger the Green Agent
 Write-Host "Sending Modernization Task to Green Agent..." -ForegroundColor Cyan
 try {
-    $result = Invoke-RestMethod `
+    $job = Invoke-RestMethod `
         -Uri "http://127.0.0.1:8080/evaluate" `
         -Method Post `
         -Body $Payload `
         -ContentType "application/json"
-    Write-Host "Task accepted! Monitor your Docker logs for the ASCII Verification report." -ForegroundColor Green
+    Write-Host "Task accepted as job $($job.job_id); waiting for it to finish" -ForegroundColor Green
+
+    # 4. Poll the job until the pipeline is done
+    do {
+        Start-Sleep -Seconds 5
+        $status = Invoke-RestMethod -Uri "http://127.0.0.1:8080$($job.status_url)" -Method Get
+        $steps = ($status.steps | ForEach-Object { "$($_.name)=$($_.status)" }) -join " "
+        Write-Host "  job $($status.status): $steps"
+    } while ($status.status -eq "queued" -or $status.status -eq "running")
+
+    $result = $status.result
     Write-Host $result
-    Start-Process $result.rust_code_url
+    if ($result.rust_code_url) { Start-Process $result.rust_code_url }
 } catch {
     Write-Host "❌ Modernization pipeline failed!" -ForegroundColor Red
     Write-Host "Error: $($_.Exception.Message)" -ForegroundColor Yellow
@@ -62,15 +62,25 @@ Write-Host "✅ Namespace and RBAC created" -ForegroundColor Green
 Write-Host "`n📋 Step 4: Creating Kubernetes Secrets..." -ForegroundColor Yellow
 
 # Delete existing secrets (ignore errors if not exist)
-kubectl delete secret gateway-jwt-secret -n mainframe-modernization 2>$null
+kubectl delete secret gateway-jwt-keys -n mainframe-modernization 2>$null
 kubectl delete secret green-agent-credentials -n mainframe-modernization 2>$null
 kubectl delete secret purple-agent-credentials -n mainframe-modernization 2>$null
 kubectl delete secret ai-mcp-credentials -n mainframe-modernization 2>$null
 kubectl delete secret s3-mcp-credentials -n mainframe-modernization 2>$null
 
+# JWT signing keys: generate the first one if none exist yet
+$jwtKeysDir = "secrets/jwt-keys"
+if (!(Get-ChildItem "$jwtKeysDir/*.pem" -ErrorAction SilentlyContinue)) {
+    New-Item -ItemType Directory -Force -Path $jwtKeysDir | Out-Null
+    $kid = Get-Date -Format "yyyy-MM"
+    openssl genpkey -algorithm ed25519 -out "$jwtKeysDir/$kid.pem"
+    if ($LASTEXITCODE -ne 0) { Write-Host "❌ Failed to generate JWT signing key (is openssl installed?)" -ForegroundColor Red; exit 1 }
+    Write-Host "✅ Generated JWT signing key $kid" -ForegroundColor Green
+}
+
 # Create secrets with real values
-kubectl create secret generic gateway-jwt-secret `
-    --from-literal=jwt-secret="$env:JWT_SECRET" `
+kubectl create secret generic gateway-jwt-keys `
+    --from-file="$jwtKeysDir" `
     -n mainframe-modernization
 
 kubectl create secret generic green-agent-credentials `
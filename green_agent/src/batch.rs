@@ -0,0 +1,390 @@
+// Batch - modernizing every program under an S3 prefix
+//
+// POST /batches names a bucket and prefix rather than one source location.
+// The batch lists the prefix with s3_mcp/list_objects and queues a job per
+// `.cbl` key, running at most `max_concurrency` of them at a time (and never
+// more than MAX_CONCURRENT_JOBS overall). A `<program>.tests.json` manifest
+// next to a program becomes its test suite. When the last job has ended, a
+// portfolio report - each program's status, match result, attempts and Claude
+// cost, with totals - is saved to modernized/{batch_id}/portfolio-report.json.
+// GET /batches/{id} follows a batch and DELETE /batches/{id} cancels it along
+// with its jobs. Batches are kept in memory as long as jobs are.
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use tokio::sync::Notify;
+
+use crate::cost::{Pricing, Usage};
+use crate::diff::CompareRules;
+use crate::jobs::{Job, JobStatus, JOB_RETENTION_HOURS};
+use crate::suite::Coverage;
+
+/// Most programs one batch may modernize
+pub const MAX_BATCH_PROGRAMS: usize = 500;
+
+/// Most batches running at once
+pub const MAX_ACTIVE_BATCHES: usize = 5;
+
+/// Longest batch_id; job task ids are the batch_id with "-{n}" appended
+const MAX_BATCH_ID_LEN: usize = 64;
+
+/// A program's test suite manifest is its key with this in place of ".cbl"
+const SUITE_SUFFIX: &str = ".tests.json";
+
+/// Name of the report saved under modernized/{batch_id}/
+pub const REPORT_NAME: &str = "portfolio-report.json";
+
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
+pub struct BatchRequest {
+    pub batch_id: String,
+    pub bucket: String,
+    pub prefix: String,
+    #[serde(default)]
+    pub max_concurrency: Option<usize>,   // At most the agent's MAX_BATCH_CONCURRENCY
+    #[serde(default)]
+    pub max_repair_attempts: Option<u32>, // For every program
+    #[serde(default)]
+    pub comparison: Option<CompareRules>, // For every program
+}
+
+impl BatchRequest {
+    pub fn check(&self) -> Result<(), String> {
+        // The batch_id ends up in task ids and S3 keys
+        let plain = self.batch_id.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'));
+        if self.batch_id.is_empty() || self.batch_id.len() > MAX_BATCH_ID_LEN || !plain {
+            return Err(format!(
+                "batch_id must be 1 to {} letters, digits, '.', '_' or '-'", MAX_BATCH_ID_LEN));
+        }
+        if self.bucket.trim().is_empty() {
+            return Err("bucket must not be empty".to_string());
+        }
+        if self.max_concurrency == Some(0) {
+            return Err("max_concurrency must be at least 1".to_string());
+        }
+        match &self.comparison {
+            Some(rules) => rules.validate(),
+            None => Ok(()),
+        }
+    }
+}
+
+/// One program of a batch and the job modernizing it
+#[derive(Debug, Serialize, Clone)]
+pub struct Program {
+    pub key: String,
+    pub task_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub test_suite: Option<String>, // Manifest key
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub job_id: Option<String>,     // Once its job is queued
+    pub status: JobStatus,          // Of its job; queued until the job is
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,      // The job could not be queued
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct Batch {
+    pub batch_id: String,
+    pub bucket: String,
+    pub prefix: String,
+    pub status: JobStatus,
+    pub max_concurrency: usize,
+    pub created_at: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub finished_at: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration_ms: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trace_id: Option<String>,
+    pub programs: Vec<Program>, // Empty until the prefix is listed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub report: Option<PortfolioReport>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub report_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(skip)]
+    cancel: Arc<Notify>,
+}
+
+// ─── Portfolio Report ─────────────────────────────────────────────────────────
+
+/// How a program's modernization ended
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Verdict {
+    Matched,       // Outputs match; the Rust code was saved
+    Mismatch,      // Compiled, but some test case differs
+    CompileFailed, // No attempt compiled
+    Failed,        // The pipeline itself failed
+    Cancelled,     // Cancelled, or never started
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct ProgramReport {
+    pub key: String,
+    pub task_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub job_id: Option<String>,
+    pub verdict: Verdict,
+    pub status: String, // The job result's status line
+    pub match_confirmed: bool,
+    pub attempts: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tests: Option<Coverage>, // Of the accepted translation
+    pub usage: Usage,
+    pub cost_usd: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rust_code_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration_ms: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize, Clone, Default)]
+pub struct Summary {
+    pub programs: usize,
+    pub matched: usize,
+    pub mismatch: usize,
+    pub compile_failed: usize,
+    pub failed: usize,
+    pub cancelled: usize,
+    pub attempts: usize, // Translations over all programs
+    pub usage: Usage,
+    pub cost_usd: f64,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct PortfolioReport {
+    pub batch_id: String,
+    pub bucket: String,
+    pub prefix: String,
+    pub generated_at: DateTime<Utc>,
+    pub pricing: Pricing,
+    pub summary: Summary,
+    pub programs: Vec<ProgramReport>,
+}
+
+/// The `.cbl` programs among the listed keys, in key order, with their test suites
+pub fn programs(batch_id: &str, keys: &[String]) -> Vec<Program> {
+    let listed: HashSet<&str> = keys.iter().map(String::as_str).collect();
+    let mut sources: Vec<&String> = keys.iter().filter(|key| key.ends_with(".cbl")).collect();
+    sources.sort();
+    sources.into_iter().enumerate().map(|(i, key)| {
+        let manifest = format!("{}{}", key.trim_end_matches(".cbl"), SUITE_SUFFIX);
+        Program {
+            key: key.clone(),
+            task_id: format!("{}-{}", batch_id, i + 1),
+            test_suite: listed.contains(manifest.as_str()).then_some(manifest),
+            job_id: None,
+            status: JobStatus::Queued,
+            error: None,
+        }
+    }).collect()
+}
+
+/// Report on every program of `batch`, looking its jobs up with `job`
+pub fn report(batch: &Batch, job: impl Fn(&str) -> Option<Job>, pricing: &Pricing) -> PortfolioReport {
+    let programs: Vec<ProgramReport> = batch.programs.iter()
+        .map(|program| program_report(program, program.job_id.as_deref().and_then(&job), pricing))
+        .collect();
+
+    let mut summary = Summary { programs: programs.len(), ..Summary::default() };
+    for program in &programs {
+        match program.verdict {
+            Verdict::Matched => summary.matched += 1,
+            Verdict::Mismatch => summary.mismatch += 1,
+            Verdict::CompileFailed => summary.compile_failed += 1,
+            Verdict::Failed => summary.failed += 1,
+            Verdict::Cancelled => summary.cancelled += 1,
+        }
+        summary.attempts += program.attempts;
+        summary.usage += program.usage;
+    }
+    summary.cost_usd = pricing.cost(&summary.usage);
+
+    PortfolioReport {
+        batch_id: batch.batch_id.clone(),
+        bucket: batch.bucket.clone(),
+        prefix: batch.prefix.clone(),
+        generated_at: Utc::now(),
+        pricing: *pricing,
+        summary,
+        programs,
+    }
+}
+
+fn program_report(program: &Program, job: Option<Job>, pricing: &Pricing) -> ProgramReport {
+    let result = job.as_ref().and_then(|j| j.result.as_ref());
+    let verdict = match (job.as_ref().map(|j| j.status), result) {
+        (Some(JobStatus::Succeeded), Some(r)) if r.match_confirmed => Verdict::Matched,
+        (Some(JobStatus::Succeeded), Some(r)) if r.attempts.last().is_some_and(|a| !a.compiled) => Verdict::CompileFailed,
+        (Some(JobStatus::Succeeded), Some(_)) => Verdict::Mismatch,
+        (Some(JobStatus::Failed), _) => Verdict::Failed,
+        (None, _) if program.error.is_some() => Verdict::Failed,
+        _ => Verdict::Cancelled,
+    };
+    let usage = job.as_ref().map(|j| j.usage).unwrap_or_default();
+
+    ProgramReport {
+        key: program.key.clone(),
+        task_id: program.task_id.clone(),
+        job_id: program.job_id.clone(),
+        verdict,
+        status: result.map(|r| r.status.clone())
+            .or(program.error.as_ref().map(|e| format!("FAILED: {}", e)))
+            .unwrap_or("CANCELLED".to_string()),
+        match_confirmed: result.is_some_and(|r| r.match_confirmed),
+        attempts: result.map_or(0, |r| r.attempts.len()),
+        tests: result.and_then(|r| r.tests.as_ref()).map(|t| t.coverage.clone()),
+        usage,
+        cost_usd: pricing.cost(&usage),
+        rust_code_url: result.and_then(|r| r.rust_code_url.clone()),
+        duration_ms: job.as_ref().and_then(|j| j.duration_ms),
+        error: job.and_then(|j| j.error).or(program.error.clone()),
+    }
+}
+
+// ─── Batch Store ──────────────────────────────────────────────────────────────
+
+#[derive(Debug)]
+pub enum BatchError {
+    NotFound,
+    Finished(JobStatus),
+    TooMany,
+    BatchActive, // A batch with this id is still running
+}
+
+impl fmt::Display for BatchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BatchError::NotFound => write!(f, "Batch not found"),
+            BatchError::Finished(status) => write!(f, "Batch already {}", status.as_str()),
+            BatchError::TooMany => write!(f, "{} batches are already running", MAX_ACTIVE_BATCHES),
+            BatchError::BatchActive => write!(f, "A batch with this batch_id is still running"),
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct BatchStore {
+    batches: Mutex<HashMap<String, Batch>>,
+}
+
+impl BatchStore {
+    /// Start a batch; the returned signal fires when it is cancelled
+    pub fn create(
+        &self,
+        req: &BatchRequest,
+        max_concurrency: usize,
+        trace_id: Option<String>,
+    ) -> Result<(Batch, Arc<Notify>), BatchError> {
+        let now = Utc::now();
+        let mut batches = self.batches.lock().unwrap();
+        let retention = Duration::hours(JOB_RETENTION_HOURS);
+        batches.retain(|_, batch| batch.finished_at.is_none_or(|at| at + retention > now));
+        if batches.get(&req.batch_id).is_some_and(|b| !b.status.is_finished()) {
+            return Err(BatchError::BatchActive);
+        }
+        if batches.values().filter(|b| !b.status.is_finished()).count() >= MAX_ACTIVE_BATCHES {
+            return Err(BatchError::TooMany);
+        }
+
+        let cancel = Arc::new(Notify::new());
+        let batch = Batch {
+            batch_id: req.batch_id.clone(),
+            bucket: req.bucket.clone(),
+            prefix: req.prefix.clone(),
+            status: JobStatus::Running,
+            max_concurrency,
+            created_at: now,
+            finished_at: None,
+            duration_ms: None,
+            trace_id,
+            programs: Vec::new(),
+            report: None,
+            report_url: None,
+            error: None,
+            cancel: cancel.clone(),
+        };
+        batches.insert(batch.batch_id.clone(), batch.clone());
+        Ok((batch, cancel))
+    }
+
+    pub fn get(&self, batch_id: &str) -> Option<Batch> {
+        self.batches.lock().unwrap().get(batch_id).cloned()
+    }
+
+    /// Ask a running batch to stop starting jobs and cancel those it started
+    pub fn cancel(&self, batch_id: &str) -> Result<(), BatchError> {
+        let batches = self.batches.lock().unwrap();
+        let batch = batches.get(batch_id).ok_or(BatchError::NotFound)?;
+        if batch.status.is_finished() {
+            return Err(BatchError::Finished(batch.status));
+        }
+        batch.cancel.notify_one();
+        Ok(())
+    }
+
+    pub fn set_programs(&self, batch_id: &str, programs: Vec<Program>) {
+        self.update(batch_id, |batch| batch.programs = programs);
+    }
+
+    pub fn program_queued(&self, batch_id: &str, index: usize, job_id: &str) {
+        self.update_program(batch_id, index, |program| program.job_id = Some(job_id.to_string()));
+    }
+
+    pub fn program_finished(&self, batch_id: &str, index: usize, status: JobStatus) {
+        self.update_program(batch_id, index, |program| program.status = status);
+    }
+
+    pub fn program_failed(&self, batch_id: &str, index: usize, error: &str) {
+        self.update_program(batch_id, index, |program| {
+            program.status = JobStatus::Failed;
+            program.error = Some(error.to_string());
+        });
+    }
+
+    /// Record how the batch ended; programs it never started are marked cancelled
+    pub fn finish(
+        &self,
+        batch_id: &str,
+        status: JobStatus,
+        report: Option<PortfolioReport>,
+        report_url: Option<String>,
+        error: Option<String>,
+    ) {
+        self.update(batch_id, |batch| {
+            let now = Utc::now();
+            batch.status = status;
+            batch.finished_at = Some(now);
+            batch.duration_ms = Some((now - batch.created_at).num_milliseconds());
+            batch.report = report;
+            batch.report_url = report_url;
+            batch.error = error;
+            for program in &mut batch.programs {
+                if program.job_id.is_none() && !program.status.is_finished() {
+                    program.status = JobStatus::Cancelled;
+                }
+            }
+        });
+    }
+
+    fn update(&self, batch_id: &str, change: impl FnOnce(&mut Batch)) {
+        if let Some(batch) = self.batches.lock().unwrap().get_mut(batch_id) {
+            change(batch);
+        }
+    }
+
+    fn update_program(&self, batch_id: &str, index: usize, change: impl FnOnce(&mut Program)) {
+        self.update(batch_id, |batch| {
+            if let Some(program) = batch.programs.get_mut(index) {
+                change(program);
+            }
+        });
+    }
+}
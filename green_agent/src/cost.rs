@@ -0,0 +1,46 @@
+// Cost - what a run spent on Claude
+//
+// ai_mcp answers every translation with the tokens Claude billed for it. They
+// are kept per attempt and added up on the job as the run goes, so a job that
+// fails halfway still shows what it spent. Batch reports price them at
+// CLAUDE_INPUT_COST_PER_MTOK / CLAUDE_OUTPUT_COST_PER_MTOK (US dollars per
+// million tokens).
+
+use serde::{Deserialize, Serialize};
+use std::ops::AddAssign;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Usage {
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+}
+
+impl Usage {
+    /// The usage in an ai_mcp translate answer; none if it reported nothing
+    pub fn from_result(result: &serde_json::Value) -> Usage {
+        serde_json::from_value(result["usage"].clone()).unwrap_or_default()
+    }
+}
+
+impl AddAssign for Usage {
+    fn add_assign(&mut self, other: Usage) {
+        self.input_tokens += other.input_tokens;
+        self.output_tokens += other.output_tokens;
+    }
+}
+
+/// Claude prices, in US dollars per million tokens
+#[derive(Debug, Serialize, Clone, Copy, PartialEq)]
+pub struct Pricing {
+    pub input_per_mtok: f64,
+    pub output_per_mtok: f64,
+}
+
+impl Pricing {
+    /// Dollars spent on `usage`, to a hundredth of a cent
+    pub fn cost(&self, usage: &Usage) -> f64 {
+        let dollars = (usage.input_tokens as f64 * self.input_per_mtok
+            + usage.output_tokens as f64 * self.output_per_mtok) / 1_000_000.0;
+        (dollars * 10_000.0).round() / 10_000.0
+    }
+}
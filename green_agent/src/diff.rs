@@ -0,0 +1,383 @@
+// Diff - how the Rust output differs from the COBOL output
+//
+// Fixed-width mainframe reports are judged character by character: a lost
+// thousands separator or trailing space is a real difference. The outputs are
+// aligned line by line (longest common subsequence, so one missing line does
+// not make every later line differ), and each pair of differing lines is
+// broken into whitespace-separated fields to show which ones changed and at
+// which column. What counts as equal is set by the normalization rule:
+//
+//   exact            - lines must be identical, a CRLF line ending included
+//   trim_trailing    - trailing whitespace and trailing blank lines are ignored
+//   numeric_tolerant - fields are compared one by one, numbers by value within
+//                      `numeric_tolerance` ("0042.50" equals "42.5"); spacing
+//                      between fields is ignored
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Most differing lines kept in a diff; `differing_lines` still counts all of them
+const MAX_LINE_DIFFS: usize = 100;
+
+/// Most differing lines written out when a diff is shown as text
+const MAX_TEXT_LINES: usize = 40;
+
+/// Largest alignment table (expected lines x actual lines); longer outputs are compared line for line
+const MAX_ALIGN_CELLS: usize = 1_000_000;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum Normalization {
+    Exact,
+    TrimTrailing,
+    NumericTolerant,
+}
+
+impl Normalization {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Normalization::Exact => "exact",
+            Normalization::TrimTrailing => "trim_trailing",
+            Normalization::NumericTolerant => "numeric_tolerant",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "exact" => Some(Normalization::Exact),
+            "trim_trailing" => Some(Normalization::TrimTrailing),
+            "numeric_tolerant" => Some(Normalization::NumericTolerant),
+            _ => None,
+        }
+    }
+}
+
+/// How outputs are compared; set per run or from OUTPUT_NORMALIZATION / NUMERIC_TOLERANCE
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+pub struct CompareRules {
+    pub normalization: Normalization,
+    #[serde(default)]
+    pub numeric_tolerance: f64, // Only for numeric_tolerant
+}
+
+impl CompareRules {
+    pub fn validate(&self) -> Result<(), String> {
+        if !self.numeric_tolerance.is_finite() || self.numeric_tolerance < 0.0 {
+            return Err("numeric_tolerance must be a non-negative number".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// The differences between two outputs
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Hash)]
+pub struct OutputDiff {
+    pub normalization: Normalization,
+    pub matched: bool,
+    pub expected_lines: usize, // COBOL output
+    pub actual_lines: usize,   // Rust output
+    pub differing_lines: usize,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub lines: Vec<LineDiff>, // The first MAX_LINE_DIFFS of them
+}
+
+/// A line that differs, is missing from the Rust output, or is extra in it
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Hash)]
+pub struct LineDiff {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expected_line: Option<usize>, // 1-based; None when the Rust output has an extra line
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub actual_line: Option<usize>,   // 1-based; None when the Rust output lacks the line
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expected: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub actual: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub fields: Vec<FieldDiff>,
+}
+
+/// A field of a differing line that changed or moved
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Hash)]
+pub struct FieldDiff {
+    pub field: usize, // 1-based
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expected: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub actual: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expected_column: Option<usize>, // 1-based
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub actual_column: Option<usize>,
+}
+
+/// Compare the Rust output (`actual`) with the COBOL output (`expected`)
+pub fn compare(expected: &str, actual: &str, rules: &CompareRules) -> OutputDiff {
+    let expected_lines = split_lines(expected, rules.normalization);
+    let actual_lines = split_lines(actual, rules.normalization);
+    let pairs = align(&expected_lines, &actual_lines, |e, a| lines_equal(e, a, rules));
+
+    let lines = pairs.iter().take(MAX_LINE_DIFFS).map(|&(e, a)| {
+        let expected = e.map(|i| expected_lines[i]);
+        let actual = a.map(|j| actual_lines[j]);
+        LineDiff {
+            expected_line: e.map(|i| i + 1),
+            actual_line: a.map(|j| j + 1),
+            expected: expected.map(String::from),
+            actual: actual.map(String::from),
+            fields: match (expected, actual) {
+                (Some(expected), Some(actual)) => field_diffs(expected, actual, rules),
+                _ => Vec::new(),
+            },
+        }
+    }).collect();
+
+    OutputDiff {
+        normalization: rules.normalization,
+        matched: pairs.is_empty(),
+        expected_lines: expected_lines.len(),
+        actual_lines: actual_lines.len(),
+        differing_lines: pairs.len(),
+        lines,
+    }
+}
+
+/// Lines of an output. Exact comparison splits on '\n' alone, so a '\r'
+/// before it stays part of the line and CRLF output differs from LF.
+fn split_lines(output: &str, normalization: Normalization) -> Vec<&str> {
+    if normalization == Normalization::Exact {
+        return output.split_terminator('\n').collect();
+    }
+    let mut lines: Vec<&str> = output.lines().collect();
+    while lines.last().is_some_and(|l| l.trim().is_empty()) {
+        lines.pop();
+    }
+    lines
+}
+
+fn lines_equal(expected: &str, actual: &str, rules: &CompareRules) -> bool {
+    match rules.normalization {
+        Normalization::Exact => expected == actual,
+        Normalization::TrimTrailing => expected.trim_end() == actual.trim_end(),
+        Normalization::NumericTolerant => {
+            let expected = fields(expected);
+            let actual = fields(actual);
+            expected.len() == actual.len()
+                && expected.iter().zip(&actual).all(|((_, e), (_, a))| fields_equal(e, a, rules))
+        }
+    }
+}
+
+fn fields_equal(expected: &str, actual: &str, rules: &CompareRules) -> bool {
+    if expected == actual {
+        return true;
+    }
+    rules.normalization == Normalization::NumericTolerant
+        && matches!((number(expected), number(actual)),
+                    (Some(e), Some(a)) if (e - a).abs() <= rules.numeric_tolerance)
+}
+
+/// Whitespace-separated fields of a line with their 1-based columns
+fn fields(line: &str) -> Vec<(usize, &str)> {
+    let mut fields = Vec::new();
+    let mut start = None;
+    for (column, (i, c)) in line.char_indices().enumerate() {
+        match (c.is_whitespace(), start) {
+            (false, None) => start = Some((column, i)),
+            (true, Some((col, from))) => {
+                fields.push((col + 1, &line[from..i]));
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    if let Some((col, from)) = start {
+        fields.push((col + 1, &line[from..]));
+    }
+    fields
+}
+
+/// A field read as a number: optional sign (leading or trailing, as COBOL
+/// edits it), digits with optional thousands separators and decimal point
+fn number(field: &str) -> Option<f64> {
+    let (negative, digits) = match field.strip_suffix('-').or_else(|| field.strip_prefix('-')) {
+        Some(rest) => (true, rest),
+        None => (false, field.strip_suffix('+').or_else(|| field.strip_prefix('+')).unwrap_or(field)),
+    };
+    if !digits.chars().any(|c| c.is_ascii_digit())
+        || !digits.chars().all(|c| c.is_ascii_digit() || c == ',' || c == '.') {
+        return None;
+    }
+    let value: f64 = digits.replace(',', "").parse().ok()?;
+    Some(if negative { -value } else { value })
+}
+
+/// Changed, moved and missing fields of two differing lines
+fn field_diffs(expected: &str, actual: &str, rules: &CompareRules) -> Vec<FieldDiff> {
+    let expected = fields(expected);
+    let actual = fields(actual);
+    // Columns only matter where spacing does
+    let columns_matter = rules.normalization != Normalization::NumericTolerant;
+    (0..expected.len().max(actual.len())).filter_map(|i| {
+        let e = expected.get(i);
+        let a = actual.get(i);
+        let same = match (e, a) {
+            (Some((ec, e)), Some((ac, a))) => fields_equal(e, a, rules) && (!columns_matter || ec == ac),
+            _ => false,
+        };
+        (!same).then(|| FieldDiff {
+            field: i + 1,
+            expected: e.map(|(_, f)| f.to_string()),
+            actual: a.map(|(_, f)| f.to_string()),
+            expected_column: e.map(|(c, _)| *c),
+            actual_column: a.map(|(c, _)| *c),
+        })
+    }).collect()
+}
+
+/// Differing lines as (expected index, actual index) pairs. Lines outside the
+/// longest common subsequence are paired up in order; the rest are missing or extra.
+fn align(expected: &[&str], actual: &[&str], equal: impl Fn(&str, &str) -> bool) -> Vec<(Option<usize>, Option<usize>)> {
+    let (n, m) = (expected.len(), actual.len());
+    if n.saturating_mul(m) > MAX_ALIGN_CELLS {
+        return (0..n.max(m))
+            .filter(|&i| !matches!((expected.get(i), actual.get(i)), (Some(e), Some(a)) if equal(e, a)))
+            .map(|i| ((i < n).then_some(i), (i < m).then_some(i)))
+            .collect();
+    }
+
+    // lcs[i][j]: length of the longest common subsequence of expected[i..] and actual[j..]
+    let mut lcs = vec![vec![0u32; m + 1]; n + 1];
+    for i in (0..n).rev() {
+        for j in (0..m).rev() {
+            lcs[i][j] = if equal(expected[i], actual[j]) {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut pairs = Vec::new();
+    let (mut missing, mut extra) = (Vec::new(), Vec::new());
+    let (mut i, mut j) = (0, 0);
+    while i < n || j < m {
+        if i < n && j < m && equal(expected[i], actual[j]) {
+            pair_up(&mut pairs, &mut missing, &mut extra);
+            i += 1;
+            j += 1;
+        } else if j == m || (i < n && lcs[i + 1][j] >= lcs[i][j + 1]) {
+            missing.push(i);
+            i += 1;
+        } else {
+            extra.push(j);
+            j += 1;
+        }
+    }
+    pair_up(&mut pairs, &mut missing, &mut extra);
+    pairs
+}
+
+fn pair_up(pairs: &mut Vec<(Option<usize>, Option<usize>)>, missing: &mut Vec<usize>, extra: &mut Vec<usize>) {
+    for k in 0..missing.len().max(extra.len()) {
+        pairs.push((missing.get(k).copied(), extra.get(k).copied()));
+    }
+    missing.clear();
+    extra.clear();
+}
+
+impl fmt::Display for OutputDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} of {} expected lines differ ({} comparison; the Rust output has {} lines)",
+               self.differing_lines, self.expected_lines, self.normalization.as_str(), self.actual_lines)?;
+        let quoted = |line: &Option<String>| line.as_ref().map_or("<missing>".to_string(), |l| format!("{:?}", l));
+        for line in self.lines.iter().take(MAX_TEXT_LINES) {
+            let at = |n: Option<usize>| n.map_or("-".to_string(), |n| n.to_string());
+            write!(f, "\nline {} (rust line {}):\n  - cobol: {}\n  + rust:  {}",
+                   at(line.expected_line), at(line.actual_line), quoted(&line.expected), quoted(&line.actual))?;
+            for field in &line.fields {
+                let column = |c: Option<usize>| c.map_or(String::new(), |c| format!(" (column {})", c));
+                let expected = format!("{}{}", quoted(&field.expected), column(field.expected_column));
+                let actual = format!("{}{}", quoted(&field.actual), column(field.actual_column));
+                match (&field.expected, &field.actual) {
+                    (Some(_), Some(_)) => write!(f, "\n    field {}: {} vs {}", field.field, expected, actual)?,
+                    (Some(_), None) => write!(f, "\n    field {}: {} is missing", field.field, expected)?,
+                    _ => write!(f, "\n    field {}: extra {}", field.field, actual)?,
+                }
+            }
+        }
+        if self.differing_lines > MAX_TEXT_LINES.min(self.lines.len()) {
+            write!(f, "\n... {} more differing lines", self.differing_lines - MAX_TEXT_LINES.min(self.lines.len()))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rules(normalization: Normalization, numeric_tolerance: f64) -> CompareRules {
+        CompareRules { normalization, numeric_tolerance }
+    }
+
+    /// (expected line, actual line) of each differing line
+    fn pairs(diff: &OutputDiff) -> Vec<(Option<usize>, Option<usize>)> {
+        diff.lines.iter().map(|l| (l.expected_line, l.actual_line)).collect()
+    }
+
+    #[test]
+    fn exact_keeps_carriage_returns_and_trailing_spaces() {
+        let exact = rules(Normalization::Exact, 0.0);
+        assert!(compare("A\nB\n", "A\nB\n", &exact).matched);
+
+        let diff = compare("A\nB\n", "A\r\nB\r\n", &exact);
+        assert_eq!(diff.expected_lines, 2);
+        assert_eq!(diff.actual_lines, 2);
+        assert_eq!(pairs(&diff), vec![(Some(1), Some(1)), (Some(2), Some(2))]);
+        assert_eq!(diff.lines[0].actual.as_deref(), Some("A\r"));
+
+        assert!(!compare("TOTAL 42 \n", "TOTAL 42\n", &exact).matched);
+        assert!(!compare("A\n", "A\n\n", &exact).matched);
+    }
+
+    #[test]
+    fn trim_trailing_ignores_trailing_whitespace_and_blank_lines() {
+        let trim = rules(Normalization::TrimTrailing, 0.0);
+        assert!(compare("A  \nB\t\n\n  \n", "A\r\nB", &trim).matched);
+
+        let diff = compare("A\n B\n", "A\nB\n", &trim);
+        assert_eq!(pairs(&diff), vec![(Some(2), Some(2))]);
+        assert_eq!(diff.lines[0].fields[0].expected_column, Some(2));
+        assert_eq!(diff.lines[0].fields[0].actual_column, Some(1));
+    }
+
+    #[test]
+    fn numeric_tolerant_compares_numbers_by_value() {
+        let numeric = rules(Normalization::NumericTolerant, 0.0);
+        assert!(compare("TOTAL   0042.50   1,000-\n", "TOTAL 42.5 -1000\n", &numeric).matched);
+        assert!(!compare("TOTAL 42.50\n", "SUM 42.50\n", &numeric).matched);
+        assert!(!compare("TOTAL 42.50\n", "TOTAL 42.50 0\n", &numeric).matched);
+
+        let diff = compare("TOTAL 42.50\n", "TOTAL 42.52\n", &numeric);
+        assert_eq!(diff.lines[0].fields.len(), 1);
+        assert_eq!(diff.lines[0].fields[0].field, 2);
+        assert!(compare("TOTAL 42.50\n", "TOTAL 42.52\n", &rules(Normalization::NumericTolerant, 0.05)).matched);
+    }
+
+    #[test]
+    fn missing_and_extra_lines_pair_up_between_matches() {
+        let exact = rules(Normalization::Exact, 0.0);
+
+        // One line lost and one added elsewhere: later lines still line up
+        let diff = compare("A\nB\nC\nD\n", "A\nC\nD\nE\n", &exact);
+        assert_eq!(pairs(&diff), vec![(Some(2), None), (None, Some(4))]);
+        assert_eq!(diff.differing_lines, 2);
+
+        // Between the same two matching lines, missing and extra lines are paired in order
+        let diff = compare("A\nB\nC\nD\n", "A\nX\nD\n", &exact);
+        assert_eq!(pairs(&diff), vec![(Some(2), Some(2)), (Some(3), None)]);
+        assert_eq!(diff.lines[0].expected.as_deref(), Some("B"));
+        assert_eq!(diff.lines[0].actual.as_deref(), Some("X"));
+        assert_eq!(diff.lines[1].actual, None);
+    }
+}
@@ -0,0 +1,311 @@
+// Jobs - /evaluate runs in the background
+//
+// A modernization run takes minutes when Claude is slow, longer than most
+// clients wait on one HTTP request. /evaluate therefore only queues a job and
+// answers with its id; the pipeline runs in a background task, at most
+// MAX_CONCURRENT_JOBS at a time, and records each step here as it goes:
+// status, timings and the artifacts it produced (the COBOL source, both
+// programs' output, the generated Rust). GET /jobs/{id} reports it and
+// DELETE /jobs/{id} cancels it. Jobs are kept in memory for JOB_RETENTION_HOURS
+// after they finish; a restart loses them.
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use tokio::sync::{futures::Notified, Notify, Semaphore, SemaphorePermit};
+
+use crate::cost::Usage;
+use crate::{ModernizeResponse, SourceLocation};
+
+/// Pipeline steps, in the order they run
+pub const STEPS: &[&str] = &["fetch_source", "load_tests", "compile_cobol", "translate", "compile_rust", "validate", "save_output"];
+
+/// Most jobs queued or running at once; further /evaluate calls are refused
+pub const MAX_ACTIVE_JOBS: usize = 50;
+
+/// Finished jobs stay queryable for this long
+pub const JOB_RETENTION_HOURS: i64 = 24;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Queued,    // Waiting for a free slot
+    Running,
+    Succeeded, // The pipeline ran to the end; `result` says whether the outputs matched
+    Failed,
+    Cancelled,
+}
+
+impl JobStatus {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            JobStatus::Queued => "queued",
+            JobStatus::Running => "running",
+            JobStatus::Succeeded => "succeeded",
+            JobStatus::Failed => "failed",
+            JobStatus::Cancelled => "cancelled",
+        }
+    }
+
+    pub fn is_finished(&self) -> bool {
+        !matches!(self, JobStatus::Queued | JobStatus::Running)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum StepStatus {
+    Pending,
+    Running,
+    Succeeded,
+    Failed,
+    Skipped,   // Not needed, or never reached because an earlier step failed
+    Cancelled, // Running when the job was cancelled
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct Step {
+    pub name: &'static str,
+    pub status: StepStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub started_at: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub finished_at: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration_ms: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub artifacts: Option<serde_json::Value>, // What the step produced
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize, Clone)]
+pub struct Job {
+    pub job_id: String,
+    pub task_id: String,
+    pub source_location: SourceLocation,
+    pub status: JobStatus,
+    pub created_at: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub started_at: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub finished_at: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration_ms: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub trace_id: Option<String>,
+    pub steps: Vec<Step>,
+    pub usage: Usage, // Claude tokens spent so far, over every attempt
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<ModernizeResponse>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(skip)]
+    cancel: Arc<Notify>,
+}
+
+#[derive(Debug)]
+pub enum JobError {
+    NotFound,
+    Finished(JobStatus),
+    QueueFull,
+    TaskActive(String), // job_id already running the task; they would share its task token
+}
+
+impl fmt::Display for JobError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JobError::NotFound => write!(f, "Job not found"),
+            JobError::Finished(status) => write!(f, "Job already {}", status.as_str()),
+            JobError::QueueFull => write!(f, "{} jobs are already queued or running", MAX_ACTIVE_JOBS),
+            JobError::TaskActive(job_id) => write!(f, "Task already has an unfinished job: {}", job_id),
+        }
+    }
+}
+
+pub struct JobStore {
+    jobs: Mutex<HashMap<String, Job>>,
+    slots: Semaphore, // One permit per job allowed to run at a time
+    freed: Notify,    // Fires whenever a job ends, freeing a place in the queue
+}
+
+impl JobStore {
+    pub fn new(max_concurrent: usize) -> Self {
+        JobStore {
+            jobs: Mutex::new(HashMap::new()),
+            slots: Semaphore::new(max_concurrent.max(1)),
+            freed: Notify::new(),
+        }
+    }
+
+    /// Queue a job; the returned signal fires when it is cancelled
+    pub fn create(
+        &self,
+        task_id: &str,
+        source_location: &SourceLocation,
+        trace_id: Option<String>,
+    ) -> Result<(Job, Arc<Notify>), JobError> {
+        let now = Utc::now();
+        let mut jobs = self.jobs.lock().unwrap();
+        let retention = Duration::hours(JOB_RETENTION_HOURS);
+        jobs.retain(|_, job| job.finished_at.is_none_or(|at| at + retention > now));
+        let active = || jobs.values().filter(|job| !job.status.is_finished());
+        if let Some(job) = active().find(|job| job.task_id == task_id) {
+            return Err(JobError::TaskActive(job.job_id.clone()));
+        }
+        if active().count() >= MAX_ACTIVE_JOBS {
+            return Err(JobError::QueueFull);
+        }
+
+        let cancel = Arc::new(Notify::new());
+        let job = Job {
+            job_id: uuid::Uuid::new_v4().to_string(),
+            task_id: task_id.to_string(),
+            source_location: source_location.clone(),
+            status: JobStatus::Queued,
+            created_at: now,
+            started_at: None,
+            finished_at: None,
+            duration_ms: None,
+            trace_id,
+            steps: STEPS.iter().map(|name| Step {
+                name,
+                status: StepStatus::Pending,
+                started_at: None,
+                finished_at: None,
+                duration_ms: None,
+                artifacts: None,
+                error: None,
+            }).collect(),
+            usage: Usage::default(),
+            result: None,
+            error: None,
+            cancel: cancel.clone(),
+        };
+        jobs.insert(job.job_id.clone(), job.clone());
+        Ok((job, cancel))
+    }
+
+    pub fn get(&self, job_id: &str) -> Option<Job> {
+        self.jobs.lock().unwrap().get(job_id).cloned()
+    }
+
+    /// Wait for a free slot; the job runs while the permit is held
+    pub async fn slot(&self) -> Option<SemaphorePermit<'_>> {
+        self.slots.acquire().await.ok()
+    }
+
+    /// Resolves once a job ends after it is created; enable it before trying
+    /// `create` so an end in between is not missed
+    pub fn freed(&self) -> Notified<'_> {
+        self.freed.notified()
+    }
+
+    /// Ask a queued or running job to stop
+    pub fn cancel(&self, job_id: &str) -> Result<(), JobError> {
+        let jobs = self.jobs.lock().unwrap();
+        let job = jobs.get(job_id).ok_or(JobError::NotFound)?;
+        if job.status.is_finished() {
+            return Err(JobError::Finished(job.status));
+        }
+        job.cancel.notify_one();
+        Ok(())
+    }
+
+    pub fn start(&self, job_id: &str) {
+        self.update(job_id, |job| {
+            job.status = JobStatus::Running;
+            job.started_at = Some(Utc::now());
+        });
+    }
+
+    pub fn step_started(&self, job_id: &str, name: &str) {
+        self.update_step(job_id, name, |step| {
+            step.status = StepStatus::Running;
+            step.started_at = Some(Utc::now());
+        });
+    }
+
+    pub fn step_succeeded(&self, job_id: &str, name: &str, artifacts: serde_json::Value) {
+        self.update_step(job_id, name, |step| {
+            step.status = StepStatus::Succeeded;
+            step.artifacts = Some(artifacts);
+            finish_step(step);
+        });
+    }
+
+    pub fn step_failed(&self, job_id: &str, name: &str, error: &str) {
+        self.update_step(job_id, name, |step| {
+            step.status = StepStatus::Failed;
+            step.error = Some(error.to_string());
+            finish_step(step);
+        });
+    }
+
+    pub fn step_skipped(&self, job_id: &str, name: &str) {
+        self.update_step(job_id, name, |step| step.status = StepStatus::Skipped);
+    }
+
+    pub fn add_usage(&self, job_id: &str, usage: Usage) {
+        self.update(job_id, |job| job.usage += usage);
+    }
+
+    /// Set steps back to pending before they run again for a repair attempt
+    pub fn steps_reset(&self, job_id: &str, names: &[&str]) {
+        for name in names {
+            self.update_step(job_id, name, |step| {
+                step.status = StepStatus::Pending;
+                step.started_at = None;
+                step.finished_at = None;
+                step.duration_ms = None;
+                step.artifacts = None;
+                step.error = None;
+            });
+        }
+    }
+
+    /// Record how the job ended; steps it never reached are marked skipped
+    pub fn finish(&self, job_id: &str, status: JobStatus, result: Option<ModernizeResponse>, error: Option<String>) {
+        self.update(job_id, |job| {
+            let now = Utc::now();
+            job.status = status;
+            job.finished_at = Some(now);
+            job.duration_ms = job.started_at.map(|at| (now - at).num_milliseconds());
+            job.result = result;
+            job.error = error;
+            for step in &mut job.steps {
+                match step.status {
+                    StepStatus::Pending => step.status = StepStatus::Skipped,
+                    StepStatus::Running => {
+                        step.status = StepStatus::Cancelled;
+                        finish_step(step);
+                    }
+                    _ => {}
+                }
+            }
+        });
+        self.freed.notify_waiters();
+    }
+
+    fn update(&self, job_id: &str, change: impl FnOnce(&mut Job)) {
+        if let Some(job) = self.jobs.lock().unwrap().get_mut(job_id) {
+            change(job);
+        }
+    }
+
+    fn update_step(&self, job_id: &str, name: &str, change: impl FnOnce(&mut Step)) {
+        self.update(job_id, |job| {
+            if let Some(step) = job.steps.iter_mut().find(|s| s.name == name) {
+                change(step);
+            }
+        });
+    }
+}
+
+fn finish_step(step: &mut Step) {
+    let now = Utc::now();
+    step.finished_at = Some(now);
+    step.duration_ms = step.started_at.map(|at| (now - at).num_milliseconds());
+}
@@ -3,46 +3,197 @@
 //
 // Flow: Green Agent -> Agent Gateway (JWT) -> MCP Server
 //       Previously: Green Agent -> MCP Server directly
+//
+// /evaluate queues the run as a job and answers at once; the pipeline runs in
+// the background and GET /jobs/{id} follows it step by step (see jobs.rs).
+// Both programs run on every test case of the run's suite (see suite.rs), and
+// a translation that fails to compile or to match is repaired (see repair.rs).
+// /batches runs a job for every program under an S3 prefix (see batch.rs).
+//
+// Each run is one trace: the W3C trace context goes to the gateway with every
+// call and on to the MCP servers, so all six steps line up in the collector
+// (see mcp_common::telemetry).
 
-use actix_web::{web, App, HttpServer, HttpResponse};
+mod batch;
+mod cost;
+mod diff;
+mod jobs;
+mod repair;
+mod suite;
+
+use actix_web::{web, App, HttpServer, HttpResponse, middleware};
 use serde::{Deserialize, Serialize};
-use std::sync::RwLock;
-use log::{info, error};
+use std::future::Future;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+use tokio::sync::{Notify, Semaphore};
+use log::{info, warn, error};
+use mcp_common::telemetry::{self, KeyValue};
+
+use batch::{BatchError, BatchRequest, BatchStore, PortfolioReport, Program};
+use cost::{Pricing, Usage};
+use diff::{CompareRules, Normalization, OutputDiff};
+use jobs::{JobError, JobStatus, JobStore};
+use repair::Attempt;
+use suite::{TestCase, TestReport};
 
 // ─── Gateway Client ───────────────────────────────────────────────────────────
 
+/// Renew the access token this long before it expires
+const TOKEN_RENEW_MARGIN_SECS: u64 = 60;
+
+/// Lifetime requested for a pipeline run's task token; revoked earlier when the run ends
+const TASK_TOKEN_TTL_SECS: u64 = 1800;
+
 pub struct GatewayClient {
     pub gateway_url: String,
-    pub agent_id: String,    
-    pub access_token: RwLock<Option<String>>,
+    pub agent_id: String,
+    api_key: String,
+    session: RwLock<Option<Session>>,
+    renewal: tokio::sync::Mutex<()>, // Held while renewing, so concurrent callers share one renewal
     http_client: reqwest::Client,
 }
 
+/// Current gateway credentials
+struct Session {
+    access_token: String,
+    refresh_token: Option<String>,
+    renew_at: Instant,
+    expires_at: Instant,
+}
+
+/// Token scoped to one pipeline run, minted by the gateway at /auth/delegate
+pub struct TaskToken {
+    pub task_id: String,
+    scope: serde_json::Value,
+    access_token: Mutex<String>, // Replaced if the gateway rejects it mid-run
+    last_request_id: Mutex<Option<String>>, // Gateway request id of the run's latest MCP call
+}
+
+impl TaskToken {
+    /// Request id of the latest MCP call the gateway answered, to look up in its audit log
+    pub fn audit_request_id(&self) -> Option<String> {
+        self.last_request_id.lock().unwrap().clone()
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Hash)]
 struct TokenResponse {
     access_token: String,
+    expires_in: u64,
+    refresh_token: Option<String>,
 }
 
 impl GatewayClient {
-    pub fn new(gateway_url: String, agent_id: String, _api_key: String) -> Self {
-        // Store api_key for token refresh (not shown for brevity - use Arc<Mutex>)
-        GatewayClient {
+    pub fn new(gateway_url: String, agent_id: String, api_key: String) -> Result<Self, String> {
+        Ok(GatewayClient {
             gateway_url,
             agent_id,
-            access_token: RwLock::new(None),
-            http_client: reqwest::Client::new(),
+            api_key,
+            session: RwLock::new(None),
+            renewal: tokio::sync::Mutex::new(()),
+            http_client: Self::build_http_client()?,
+        })
+    }
+
+    /// HTTP client for the gateway. For mutual TLS it trusts TLS_CA_PATH (e.g. a
+    /// local test CA) and presents TLS_CLIENT_CERT_PATH / TLS_CLIENT_KEY_PATH.
+    fn build_http_client() -> Result<reqwest::Client, String> {
+        let read = |path: &str| std::fs::read(path).map_err(|e| format!("{}: {}", path, e));
+        let mut builder = reqwest::Client::builder().use_rustls_tls();
+
+        if let Ok(ca_path) = std::env::var("TLS_CA_PATH") {
+            let ca = reqwest::Certificate::from_pem(&read(&ca_path)?)
+                .map_err(|e| format!("{}: {}", ca_path, e))?;
+            builder = builder.add_root_certificate(ca);
         }
+
+        if let (Ok(cert_path), Ok(key_path)) = (std::env::var("TLS_CLIENT_CERT_PATH"), std::env::var("TLS_CLIENT_KEY_PATH")) {
+            let mut pem = read(&cert_path)?;
+            pem.extend(read(&key_path)?);
+            let identity = reqwest::Identity::from_pem(&pem)
+                .map_err(|e| format!("{}: {}", cert_path, e))?;
+            info!("🔒 Presenting client certificate {} to the gateway", cert_path);
+            builder = builder.identity(identity);
+        }
+
+        builder.build().map_err(|e| e.to_string())
     }
 
     /// Authenticate with gateway and get JWT token
-    pub async fn authenticate(&self, api_key: &str) -> Result<(), String> {
-        let response = self.http_client
-            .post(format!("{}/auth/token", self.gateway_url))
-            .json(&serde_json::json!({
-                "agent_id": self.agent_id,
-                "api_key": api_key,
-                "requested_role": "orchestrator"
-            }))
+    pub async fn authenticate(&self) -> Result<(), String> {
+        let session = self.login().await?;
+        *self.session.write().unwrap() = Some(session);
+        info!("✅ Green Agent authenticated with gateway");
+        Ok(())
+    }
+
+    /// A valid access token, renewed first if it is about to expire or has
+    /// less than `lifetime` left
+    async fn access_token(&self, lifetime: Duration) -> Result<String, String> {
+        if let Some(token) = self.current_token(lifetime) {
+            return Ok(token);
+        }
+
+        let _renewal = self.renewal.lock().await;
+        // Another call may have renewed while we waited
+        if let Some(token) = self.current_token(lifetime) {
+            return Ok(token);
+        }
+
+        let refresh_token = self.session.read().unwrap()
+            .as_ref()
+            .and_then(|s| s.refresh_token.clone());
+        let session = match refresh_token {
+            Some(refresh_token) => {
+                let refreshed = self.request_token(
+                    "refresh", serde_json::json!({ "refresh_token": refresh_token })).await;
+                match refreshed {
+                    Ok(session) => session,
+                    Err(e) => {
+                        warn!("Token refresh failed ({}), logging in again", e);
+                        self.login().await?
+                    }
+                }
+            }
+            None => self.login().await?,
+        };
+
+        info!("🔄 Gateway access token renewed");
+        let token = session.access_token.clone();
+        *self.session.write().unwrap() = Some(session);
+        Ok(token)
+    }
+
+    fn current_token(&self, lifetime: Duration) -> Option<String> {
+        let now = Instant::now();
+        self.session.read().unwrap()
+            .as_ref()
+            .filter(|s| now < s.renew_at && now + lifetime <= s.expires_at)
+            .map(|s| s.access_token.clone())
+    }
+
+    /// Force renewal of `token` after the gateway rejected it
+    fn expire(&self, token: &str) {
+        if let Some(session) = self.session.write().unwrap().as_mut() {
+            if session.access_token == token {
+                session.renew_at = Instant::now();
+            }
+        }
+    }
+
+    async fn login(&self) -> Result<Session, String> {
+        self.request_token("token", serde_json::json!({
+            "agent_id": self.agent_id,
+            "api_key": self.api_key,
+            "requested_role": "orchestrator"
+        })).await
+    }
+
+    /// POST /auth/{endpoint} and start a session from the issued tokens
+    async fn request_token(&self, endpoint: &str, body: serde_json::Value) -> Result<Session, String> {
+        let response = telemetry::inject(self.http_client.post(format!("{}/auth/{}", self.gateway_url, endpoint)))
+            .json(&body)
             .send()
             .await
             .map_err(|e| format!("Gateway auth failed: {}", e))?;
@@ -54,77 +205,140 @@ impl GatewayClient {
         let token_resp: TokenResponse = response.json().await
             .map_err(|e| format!("Invalid auth response: {}", e))?;
 
-        let mut token = self.access_token.write().unwrap();
-        *token = Some(token_resp.access_token);
-        info!("✅ Green Agent authenticated with gateway");
-        Ok(())
+        let now = Instant::now();
+        let lifetime = token_resp.expires_in.saturating_sub(TOKEN_RENEW_MARGIN_SECS);
+        Ok(Session {
+            access_token: token_resp.access_token,
+            refresh_token: token_resp.refresh_token,
+            renew_at: now + Duration::from_secs(lifetime),
+            expires_at: now + Duration::from_secs(token_resp.expires_in),
+        })
+    }
+
+    /// POST to a gateway endpoint with the agent's own access token, good for
+    /// at least `lifetime`. A rejected access token is renewed and the request
+    /// retried once.
+    async fn post_with_session(&self, path: &str, body: &serde_json::Value, lifetime: Duration) -> Result<reqwest::Response, String> {
+        let mut retried = false;
+        loop {
+            let token = self.access_token(lifetime).await?;
+            let response = telemetry::inject(self.http_client.post(format!("{}{}", self.gateway_url, path)))
+                .header("Authorization", format!("Bearer {}", token))
+                .json(body)
+                .send()
+                .await
+                .map_err(|e| format!("Gateway request failed: {}", e))?;
+
+            if response.status().as_u16() == 401 && !retried {
+                info!("🔄 Gateway rejected the access token, renewing and retrying");
+                self.expire(&token);
+                retried = true;
+                continue;
+            }
+            return Ok(response);
+        }
     }
 
-    /// Call an MCP server via the gateway (requires prior authentication)
+    /// Mint a token for `task_id` that can only make the calls in `scope`
+    pub async fn delegate(&self, task_id: &str, scope: serde_json::Value) -> Result<TaskToken, String> {
+        let access_token = self.mint_task_token(task_id, &scope).await?;
+        info!("🎫 Task token issued for {}", task_id);
+        Ok(TaskToken {
+            task_id: task_id.to_string(),
+            scope,
+            access_token: Mutex::new(access_token),
+            last_request_id: Mutex::new(None),
+        })
+    }
+
+    /// A task token expires with the session that delegated it, so the session
+    /// is renewed first unless it outlives TASK_TOKEN_TTL_SECS
+    async fn mint_task_token(&self, task_id: &str, scope: &serde_json::Value) -> Result<String, String> {
+        let response = self.post_with_session("/auth/delegate", &serde_json::json!({
+            "task_id": task_id,
+            "scope": scope,
+            "ttl_secs": TASK_TOKEN_TTL_SECS
+        }), Duration::from_secs(TASK_TOKEN_TTL_SECS)).await?;
+
+        let status = response.status();
+        let body: serde_json::Value = response.json().await
+            .map_err(|e| format!("Invalid delegation response: {}", e))?;
+        if !status.is_success() {
+            return Err(format!("Delegation rejected ({}): {}", status, body["error"].as_str().unwrap_or("")));
+        }
+
+        body["access_token"].as_str()
+            .map(String::from)
+            .ok_or("Delegation response has no access_token".to_string())
+    }
+
+    /// Revoke the task's tokens once its run is over
+    pub async fn end_task(&self, task: TaskToken) {
+        let revoked = self.post_with_session("/auth/revoke", &serde_json::json!({
+            "task_id": task.task_id
+        }), Duration::ZERO).await;
+        match revoked {
+            Ok(response) if response.status().is_success() => info!("🎫 Task token revoked for {}", task.task_id),
+            Ok(response) => warn!("Task token revocation for {} rejected: {}", task.task_id, response.status()),
+            Err(e) => warn!("Task token revocation for {} failed: {}", task.task_id, e),
+        }
+    }
+
+    /// Call an MCP server via the gateway with the task's token, in a span of its own
     pub async fn invoke_mcp(
         &self,
+        task: &TaskToken,
         target_mcp: &str,
         operation: &str,
         payload: serde_json::Value,
     ) -> Result<serde_json::Value, String> {
-        let token = {
-            let t = self.access_token.read().unwrap();
-            t.clone().ok_or("Not authenticated with gateway")?
-        };
-
-        let response = self.http_client
-            .post(format!("{}/mcp/invoke", self.gateway_url))
-            .header("Authorization", format!("Bearer {}", token))
-            .json(&serde_json::json!({
-                "target_mcp": target_mcp,
-                "operation": operation,
-                "payload": payload
-            }))
-            .send()
-            .await
-            .map_err(|e| format!("Gateway request failed: {}", e))?;
-
-        let status = response.status();
-
-        // ── AUTO REFRESH on 401 ExpiredSignature ──────────────────────────
-    if status.as_u16() == 401 {
-        info!("🔄 JWT expired — refreshing token...");
-        let api_key = std::env::var("AGENT_API_KEY")
-            .map_err(|_| "AGENT_API_KEY not set")?;
-        self.authenticate(&api_key).await?;
-
-        // Retry with new token
-        let new_token = {
-            let t = self.access_token.read().unwrap();
-            t.clone().ok_or("Token missing after refresh")?
-        };
-
-        let retry = self.http_client
-            .post(format!("{}/mcp/invoke", self.gateway_url))
-            .header("Authorization", format!("Bearer {}", new_token))
-            .json(&serde_json::json!({
-                "target_mcp": target_mcp,
-                "operation": operation,
-                "payload": payload
-            }))
-            .send()
-            .await
-            .map_err(|e| format!("Retry request failed: {}", e))?;
+        let attributes = vec![
+            KeyValue::new("task.id", task.task_id.clone()),
+            KeyValue::new("mcp.server", target_mcp.to_string()),
+            KeyValue::new("mcp.operation", operation.to_string()),
+        ];
+        telemetry::in_span(format!("invoke {} {}", target_mcp, operation), attributes,
+                           self.send_invoke(task, target_mcp, operation, payload)).await
+    }
 
-        let retry_status = retry.status();
-        let retry_body: serde_json::Value = retry.json().await
-            .map_err(|e| format!("Invalid retry response: {}", e))?;
+    async fn send_invoke(
+        &self,
+        task: &TaskToken,
+        target_mcp: &str,
+        operation: &str,
+        payload: serde_json::Value,
+    ) -> Result<serde_json::Value, String> {
+        let request = serde_json::json!({
+            "target_mcp": target_mcp,
+            "operation": operation,
+            "payload": payload
+        });
+        let mut retried = false;
+        let (status, body) = loop {
+            let token = task.access_token.lock().unwrap().clone();
+            let response = telemetry::inject(self.http_client.post(format!("{}/mcp/invoke", self.gateway_url)))
+                .header("Authorization", format!("Bearer {}", token))
+                .json(&request)
+                .send()
+                .await
+                .map_err(|e| format!("Gateway request failed: {}", e))?;
 
-        return if retry_status.is_success() && retry_body["success"].as_bool().unwrap_or(false) {
-            Ok(retry_body["result"].clone())
-        } else {
-            Err(format!("Gateway error after refresh {}: {:?}", retry_status, retry_body))
+            let status = response.status();
+            let body: serde_json::Value = response.json().await
+                .map_err(|e| format!("Invalid gateway response: {}", e))?;
+            // A revoked task token means the run was stopped, not that it ran out
+            let revoked = body["error"].as_str().is_some_and(|e| e.contains("revoked"));
+            if status.as_u16() == 401 && !retried && !revoked {
+                info!("🔄 Gateway rejected the task token for {}, delegating again", task.task_id);
+                *task.access_token.lock().unwrap() = self.mint_task_token(&task.task_id, &task.scope).await?;
+                retried = true;
+                continue;
+            }
+            break (status, body);
         };
-    }
-    // ─────────────────────────────────────────────────────────────────
-
-        let body: serde_json::Value = response.json().await
-            .map_err(|e| format!("Invalid gateway response: {}", e))?;
+        if let Some(request_id) = body["request_id"].as_str() {
+            *task.last_request_id.lock().unwrap() = Some(request_id.to_string());
+        }
 
         if status.is_success() {
             let success = body["success"].as_bool().unwrap_or(false);
@@ -145,10 +359,18 @@ impl GatewayClient {
 // ─── Pipeline Structs ─────────────────────────────────────────────────────────
 
 //#[derive(Debug, Deserialize)]
-#[derive(Debug, Deserialize, Serialize, Clone, PartialEq, Eq, Hash)]
+#[derive(Debug, Deserialize, Serialize, Clone, PartialEq)]
 pub struct ModernizeRequest {
     pub task_id: String,
     pub source_location: SourceLocation,
+    #[serde(default)]
+    pub max_repair_attempts: Option<u32>, // At most the agent's MAX_REPAIR_ATTEMPTS
+    #[serde(default)]
+    pub comparison: Option<CompareRules>, // Overrides the agent's default
+    #[serde(default)]
+    pub test_cases: Option<Vec<TestCase>>, // Inline test suite
+    #[serde(default)]
+    pub test_suite: Option<SourceLocation>, // Test suite manifest in S3
 }
 
 //#[derive(Debug, Deserialize)]
@@ -167,131 +389,695 @@ pub struct ModernizeResponse {
     pub rust_code_url: Option<String>,
     //pub logs_url: Option<String>,
     pub audit_request_id: Option<String>,
+    pub trace_id: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub diff: Option<OutputDiff>, // Of the first failing test case, or the first case when all pass
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tests: Option<TestReport>, // Of the accepted translation, if it compiled
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub attempts: Vec<Attempt>, // Every translation tried, the accepted one last
 }
 
 pub struct AppState {
     pub gateway: GatewayClient,
     pub s3_bucket: String,
+    pub jobs: JobStore,
+    pub max_repair_attempts: u32,
+    pub comparison: CompareRules,
+    pub batches: BatchStore,
+    pub max_batch_concurrency: usize,
+    pub pricing: Pricing,
 }
 
 // ─── Pipeline Handler ─────────────────────────────────────────────────────────
 
+/// POST /evaluate - queue a modernization run; poll GET /jobs/{job_id} for it
 async fn evaluate(
     state: web::Data<AppState>,
     req: web::Json<ModernizeRequest>,
 ) -> HttpResponse {
-    info!("🚀 Starting modernization for task: {}", req.task_id);
-    let gw = &state.gateway;
+    let req = req.into_inner();
+    if let Err(e) = check_request(&req) {
+        return HttpResponse::BadRequest().json(serde_json::json!({
+            "error": e,
+            "code": "INVALID_REQUEST"
+        }));
+    }
+    let trace_id = telemetry::current_trace_id();
+    let (job, cancel) = match state.jobs.create(&req.task_id, &req.source_location, trace_id.clone()) {
+        Ok(created) => created,
+        Err(e) => return job_error(e),
+    };
+    info!("🚀 Queued modernization for task: {} (job {})", req.task_id, job.job_id);
 
-    // Step 1: Fetch COBOL source via Agent Gateway -> S3 MCP
-    let cobol_source = match gw.invoke_mcp(
-        "s3_mcp",
-        "fetch_source",
-        serde_json::json!({
-            "bucket": req.source_location.bucket,
-            "key": req.source_location.key,
-        })
-    ).await {
-        Ok(result) => result["content"].as_str().unwrap_or("").to_string(),
-        Err(e) => {
-            error!("Failed to fetch COBOL: {}", e);
-            return HttpResponse::InternalServerError().json(ModernizeResponse {
-                task_id: req.task_id.clone(),
-                status: format!("FAILED: {}", e),
-                match_confirmed: false,
-                rust_code_url: None,
-                //logs_url: None,
-                audit_request_id: None,
-            });
+    let attributes = vec![
+        KeyValue::new("task.id", req.task_id.clone()),
+        KeyValue::new("job.id", job.job_id.clone()),
+    ];
+    let run = run_job(state.clone(), job.job_id.clone(), req, cancel);
+    actix_web::rt::spawn(telemetry::with_current_context(
+        telemetry::in_span(format!("job {}", job.job_id), attributes, run)));
+
+    HttpResponse::Accepted().json(serde_json::json!({
+        "job_id": job.job_id,
+        "task_id": job.task_id,
+        "status": job.status,
+        "status_url": format!("/jobs/{}", job.job_id),
+        "trace_id": trace_id,
+    }))
+}
+
+fn check_request(req: &ModernizeRequest) -> Result<(), String> {
+    if let Some(rules) = &req.comparison {
+        rules.validate()?;
+    }
+    match (&req.test_cases, &req.test_suite) {
+        (Some(_), Some(_)) => Err("Give either test_cases or test_suite, not both".to_string()),
+        (Some(cases), None) => suite::check(cases),
+        _ => Ok(()),
+    }
+}
+
+/// GET /jobs/{job_id} - status, timings and artifacts of each step
+async fn get_job(state: web::Data<AppState>, path: web::Path<String>) -> HttpResponse {
+    match state.jobs.get(&path) {
+        Some(job) => HttpResponse::Ok().json(job),
+        None => job_error(JobError::NotFound),
+    }
+}
+
+/// DELETE /jobs/{job_id} - stop a queued or running job; its task token is revoked
+async fn cancel_job(state: web::Data<AppState>, path: web::Path<String>) -> HttpResponse {
+    match state.jobs.cancel(&path) {
+        Ok(()) => {
+            info!("🛑 Cancelling job {}", path);
+            HttpResponse::Accepted().json(serde_json::json!({
+                "job_id": path.into_inner(),
+                "status": "cancelling",
+            }))
         }
+        Err(e) => job_error(e),
+    }
+}
+
+fn job_error(e: JobError) -> HttpResponse {
+    let (mut response, code) = match &e {
+        JobError::NotFound => (HttpResponse::NotFound(), "JOB_NOT_FOUND"),
+        JobError::Finished(_) => (HttpResponse::Conflict(), "JOB_FINISHED"),
+        JobError::TaskActive(_) => (HttpResponse::Conflict(), "TASK_ACTIVE"),
+        JobError::QueueFull => (HttpResponse::ServiceUnavailable(), "JOB_QUEUE_FULL"),
     };
+    response.json(serde_json::json!({
+        "error": e.to_string(),
+        "code": code
+    }))
+}
 
-    // Step 2: Compile and execute COBOL via Agent Gateway -> COBOL MCP
-    let cobol_output = match gw.invoke_mcp(
-        "cobol_mcp",
-        "compile",
-        serde_json::json!({"source": cobol_source})
-    ).await {
-        Ok(r) => r["output"].as_str().unwrap_or("").to_string(),
-        Err(e) => return error_response(&req.task_id, &e),
+/// Run a queued job once a slot is free, until it ends or is cancelled
+async fn run_job(
+    state: web::Data<AppState>,
+    job_id: String,
+    req: ModernizeRequest,
+    cancel: Arc<Notify>,
+) -> Result<(), String> {
+    let jobs = &state.jobs;
+    let gw = &state.gateway;
+
+    let _slot = tokio::select! {
+        _ = cancel.notified() => {
+            info!("🛑 Job {} cancelled before it started", job_id);
+            jobs.finish(&job_id, JobStatus::Cancelled, None, None);
+            return Ok(());
+        }
+        slot = jobs.slot() => slot,
     };
+    jobs.start(&job_id);
+    info!("🚀 Starting modernization for task: {} (job {})", req.task_id, job_id);
 
-    // Step 3: Translate to Rust via Agent Gateway -> AI MCP
-    let rust_code = match gw.invoke_mcp(
-        "ai_mcp",
-        "translate_cobol",
-        serde_json::json!({"source": cobol_source})
-    ).await {
-        Ok(r) => r["rust_code"].as_str().unwrap_or("").to_string(),
-        Err(e) => return error_response(&req.task_id, &e),
+    // The run gets a token for exactly the calls below, revoked when it ends
+    let mut scope = vec![
+        serde_json::json!({"target_mcp": "s3_mcp", "operation": "fetch_source",
+         "bucket": req.source_location.bucket, "key_prefix": req.source_location.key}),
+        serde_json::json!({"target_mcp": "cobol_mcp", "operation": "compile"}),
+        serde_json::json!({"target_mcp": "ai_mcp", "operation": "translate_cobol"}),
+        serde_json::json!({"target_mcp": "rust_mcp", "operation": "compile"}),
+        serde_json::json!({"target_mcp": "s3_mcp", "operation": "save_output",
+         "bucket": state.s3_bucket, "key_prefix": format!("modernized/{}/", req.task_id)}),
+    ];
+    if let Some(manifest) = &req.test_suite {
+        scope.push(serde_json::json!({"target_mcp": "s3_mcp", "operation": "fetch_data",
+         "bucket": manifest.bucket, "key_prefix": manifest.key}));
+    }
+    let task = match gw.delegate(&req.task_id, serde_json::json!(scope)).await {
+        Ok(task) => task,
+        Err(e) => {
+            error!("Failed to get a task token: {}", e);
+            let response = failed_response(&req.task_id, None, &e, Vec::new());
+            jobs.finish(&job_id, JobStatus::Failed, Some(response), Some(e.clone()));
+            return Err(e);
+        }
     };
 
-    // Step 4: Compile and execute Rust via Agent Gateway -> Rust MCP
-    let rust_output = match gw.invoke_mcp(
-        "rust_mcp",
-        "compile",
-        serde_json::json!({"source": rust_code})
-    ).await {
-        Ok(r) => r["output"].as_str().unwrap_or("").to_string(),
-        Err(e) => return error_response(&req.task_id, &e),
+    // Cancelling drops the pipeline mid-step; an MCP call already sent may still finish upstream.
+    // Attempts live out here so a run that fails mid-repair still reports them.
+    let mut attempts = Vec::new();
+    let outcome = tokio::select! {
+        _ = cancel.notified() => None,
+        outcome = run_pipeline(&state, &job_id, &req, &task, &mut attempts) => Some(outcome),
     };
+    let audit_request_id = task.audit_request_id();
+    gw.end_task(task).await;
+
+    match outcome {
+        None => {
+            info!("🛑 Job {} cancelled", job_id);
+            jobs.finish(&job_id, JobStatus::Cancelled, None, None);
+            Ok(())
+        }
+        Some(Ok(response)) => {
+            info!("🏁 Job {} finished: {}", job_id, response.status);
+            jobs.finish(&job_id, JobStatus::Succeeded, Some(response), None);
+            Ok(())
+        }
+        Some(Err(e)) => {
+            error!("Job {} failed: {}", job_id, e);
+            let response = failed_response(&req.task_id, audit_request_id, &e, attempts);
+            jobs.finish(&job_id, JobStatus::Failed, Some(response), Some(e.clone()));
+            Err(e)
+        }
+    }
+}
+
+/// Run one pipeline step, recording its timing and what it produced on the job
+async fn step<T>(
+    jobs: &JobStore,
+    job_id: &str,
+    name: &str,
+    work: impl Future<Output = Result<T, String>>,
+    artifacts: impl FnOnce(&T) -> serde_json::Value,
+) -> Result<T, String> {
+    jobs.step_started(job_id, name);
+    let result = work.await;
+    match &result {
+        Ok(value) => jobs.step_succeeded(job_id, name, artifacts(value)),
+        Err(e) => jobs.step_failed(job_id, name, e),
+    }
+    result
+}
+
+/// The modernization steps, every MCP call made with the run's task token.
+/// Each translation tried is added to `attempts` as soon as it is judged.
+async fn run_pipeline(
+    state: &AppState,
+    job_id: &str,
+    req: &ModernizeRequest,
+    task: &TaskToken,
+    attempts: &mut Vec<Attempt>,
+) -> Result<ModernizeResponse, String> {
+    let gw = &state.gateway;
+    let jobs = &state.jobs;
+
+    // Step 1: Fetch COBOL source via Agent Gateway -> S3 MCP
+    let cobol_source = step(jobs, job_id, "fetch_source", async {
+        let result = gw.invoke_mcp(
+            task,
+            "s3_mcp",
+            "fetch_source",
+            serde_json::json!({
+                "bucket": req.source_location.bucket,
+                "key": req.source_location.key,
+            })
+        ).await?;
+        Ok(result["content"].as_str().unwrap_or("").to_string())
+    }, |source| serde_json::json!({"cobol_source": source})).await?;
+
+    // Step 2: Load the test cases: inline, from S3, or one run with no input
+    let cases = step(jobs, job_id, "load_tests", async {
+        match (&req.test_cases, &req.test_suite) {
+            (Some(cases), _) => Ok(cases.clone()),
+            (None, Some(manifest)) => {
+                let r = gw.invoke_mcp(task, "s3_mcp", "fetch_data", serde_json::json!({
+                    "bucket": manifest.bucket,
+                    "key": manifest.key,
+                })).await?;
+                suite::parse_manifest(r["content"].as_str().unwrap_or(""))
+            }
+            (None, None) => Ok(suite::default_cases()),
+        }
+    }, |cases| serde_json::json!({
+        "cases": cases.iter().map(|c| &c.name).collect::<Vec<_>>(),
+    })).await?;
+
+    // Step 3: Compile COBOL and run it on every case via Agent Gateway -> COBOL MCP
+    let expected = step(jobs, job_id, "compile_cobol", async {
+        let r = gw.invoke_mcp(task, "cobol_mcp", "compile", serde_json::json!({
+            "source": cobol_source,
+            "cases": cases,
+        })).await?;
+        if r["success"].as_bool() == Some(false) {
+            let log = r["compile_log"].as_str().or(r["error"].as_str()).unwrap_or("");
+            return Err(format!("COBOL compilation failed: {}", log.trim()));
+        }
+        // A run that never exited is no reference; a RETURN-CODE is part of what is expected
+        let runs = suite::runs(&r, &cases)?;
+        if let Some((run, case)) = runs.iter().zip(&cases).find(|(run, _)| run.error.is_some()) {
+            return Err(format!("COBOL program failed on test case {}: {}",
+                               case.name, run.error.as_deref().unwrap_or("")));
+        }
+        Ok(runs)
+    }, |runs| serde_json::json!({
+        "cobol_outputs": cases.iter().zip(runs)
+            .map(|(case, run)| serde_json::json!({"case": case.name, "output": run.output, "exit_code": run.exit_code}))
+            .collect::<Vec<_>>(),
+    })).await?;
+
+    // Steps 4-6: Translate, compile and test, feeding failures back into the
+    // translation until every case matches or the repair attempts run out
+    let rules = req.comparison.unwrap_or(state.comparison);
+    let repairs = req.max_repair_attempts.map_or(state.max_repair_attempts, |n| n.min(state.max_repair_attempts));
+    let mut context: Option<String> = None;
+    let attempt = loop {
+        let n = attempts.len() as u32 + 1;
+        if n > 1 {
+            jobs.steps_reset(job_id, &["translate", "compile_rust", "validate"]);
+        }
+
+        // Step 4: Translate to Rust via Agent Gateway -> AI MCP
+        let (rust_code, usage) = step(jobs, job_id, "translate", async {
+            let r = gw.invoke_mcp(task, "ai_mcp", "translate_cobol", serde_json::json!({
+                "source": cobol_source,
+                "context": context,
+            })).await?;
+            Ok((r["rust_code"].as_str().unwrap_or("").to_string(), Usage::from_result(&r)))
+        }, |(code, usage)| serde_json::json!({"attempt": n, "rust_code": code, "usage": usage})).await?;
+        jobs.add_usage(job_id, usage);
 
-    // Step 5: Validate outputs match
-    let match_confirmed = outputs_match(&cobol_output, &rust_output);
-    info!("Validation: COBOL={:?} RUST={:?} MATCH={}", cobol_output, rust_output, match_confirmed);
+        // Step 5: Compile Rust and run it on every case via Agent Gateway -> Rust MCP
+        let compiled = step(jobs, job_id, "compile_rust", async {
+            let r = gw.invoke_mcp(task, "rust_mcp", "compile", serde_json::json!({
+                "source": rust_code,
+                "cases": cases,
+            })).await?;
+            if r["success"].as_bool() == Some(false) {
+                let log = r["compile_log"].as_str().or(r["error"].as_str()).unwrap_or("");
+                return Ok(Err(repair::compile_errors(log)));
+            }
+            Ok(Ok(suite::runs(&r, &cases)?))
+        }, |compiled| match compiled {
+            Ok(runs) => serde_json::json!({
+                "attempt": n,
+                "rust_outputs": cases.iter().zip(runs)
+                    .map(|(case, run)| serde_json::json!({
+                        "case": case.name, "output": run.output, "exit_code": run.exit_code, "error": run.error,
+                    }))
+                    .collect::<Vec<_>>(),
+            }),
+            Err(errors) => serde_json::json!({"attempt": n, "compile_errors": errors}),
+        }).await?;
+
+        // Step 6: Validate outputs match on every case
+        let attempt = match compiled {
+            Ok(runs) => {
+                let report = step(jobs, job_id, "validate", async {
+                    Ok(suite::evaluate(&cases, &expected, &runs, &rules))
+                }, |report| serde_json::json!({
+                    "attempt": n,
+                    "match_confirmed": report.passed(),
+                    "coverage": report.coverage,
+                })).await?;
+                info!("Validation (attempt {}): {} of {} cases match ({})",
+                      n, report.coverage.passed, report.coverage.cases, rules.normalization.as_str());
+                Attempt {
+                    attempt: n,
+                    rust_code,
+                    usage,
+                    compiled: true,
+                    compile_errors: None,
+                    match_confirmed: report.passed(),
+                    tests: Some(report),
+                }
+            }
+            Err(errors) => {
+                jobs.step_failed(job_id, "compile_rust", "Rust compilation failed");
+                jobs.step_skipped(job_id, "validate");
+                warn!("Rust compilation failed (attempt {})", n);
+                Attempt {
+                    attempt: n,
+                    rust_code,
+                    usage,
+                    compiled: false,
+                    compile_errors: Some(errors),
+                    tests: None,
+                    match_confirmed: false,
+                }
+            }
+        };
+
+        if attempt.match_confirmed || n > repairs {
+            attempts.push(attempt.clone());
+            break attempt;
+        }
+        info!("🔁 Repairing translation for task {} (attempt {} of {})", req.task_id, n + 1, repairs + 1);
+        context = Some(repair::feedback(&attempt, &cases, &expected));
+        attempts.push(attempt);
+    };
+    let match_confirmed = attempt.match_confirmed;
+    let tests = attempt.tests;
+    let diff = tests.as_ref().and_then(TestReport::first_diff).cloned();
+    let rust_code = attempt.rust_code;
 
-    // Step 6: Save to S3 only if validated
+    // Step 7: Save to S3 only if validated
     let rust_code_url = if match_confirmed {
         let output_key = format!("modernized/{}/{}.rs",
             req.task_id,
             req.source_location.key.replace(".cbl", ""));
 
-        match gw.invoke_mcp("s3_mcp", "save_output", serde_json::json!({
-            "bucket": state.s3_bucket,
-            "key": output_key,
-            "content": rust_code,
-        })).await {
-            Ok(r) => r["presigned_url"].as_str().map(String::from),
+        let saved = step(jobs, job_id, "save_output", async {
+            let r = gw.invoke_mcp(task, "s3_mcp", "save_output", serde_json::json!({
+                "bucket": state.s3_bucket,
+                "key": output_key,
+                "content": rust_code,
+            })).await?;
+            Ok(r["presigned_url"].as_str().map(String::from))
+        }, |url| serde_json::json!({"key": output_key, "rust_code_url": url})).await;
+        match saved {
+            Ok(url) => url,
             Err(e) => {
                 error!("Save failed: {}", e);
                 None
             }
         }
     } else {
+        jobs.step_skipped(job_id, "save_output");
         None
     };
 
-    HttpResponse::Ok().json(ModernizeResponse {
+    Ok(ModernizeResponse {
         task_id: req.task_id.clone(),
-        status: if match_confirmed {
-            "SUCCESS - Outputs match! ✅".to_string()
-        } else {
-            "MISMATCH - Needs review ⚠️".to_string()
+        status: match &tests {
+            _ if !attempt.compiled => "COMPILE FAILED - Needs review ⚠️".to_string(),
+            Some(tests) if tests.coverage.cases > 1 && match_confirmed =>
+                format!("SUCCESS - Outputs match on all {} test cases! ✅", tests.coverage.cases),
+            Some(tests) if tests.coverage.cases > 1 =>
+                format!("MISMATCH - {} of {} test cases differ, needs review ⚠️", tests.coverage.failed, tests.coverage.cases),
+            _ if match_confirmed => "SUCCESS - Outputs match! ✅".to_string(),
+            _ => "MISMATCH - Needs review ⚠️".to_string(),
         },
         match_confirmed,
         rust_code_url,
         //logs_url: None,
-        audit_request_id: None, // Would be populated from gateway response
+        audit_request_id: task.audit_request_id(),
+        trace_id: telemetry::current_trace_id(),
+        diff,
+        tests,
+        attempts: std::mem::take(attempts),
     })
 }
 
-fn outputs_match(cobol: &str, rust: &str) -> bool {
-    // Normalize whitespace for comparison
-    let normalize = |s: &str| -> String {
-        s.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase()
-    };
-    normalize(cobol) == normalize(rust)
-}
-
-fn error_response(task_id: &str, error: &str) -> HttpResponse {
-    HttpResponse::InternalServerError().json(ModernizeResponse {
+fn failed_response(task_id: &str, audit_request_id: Option<String>, error: &str, attempts: Vec<Attempt>) -> ModernizeResponse {
+    ModernizeResponse {
         task_id: task_id.to_string(),
         status: format!("FAILED: {}", error),
         match_confirmed: false,
         rust_code_url: None,
         //logs_url: None,
-        audit_request_id: None,
-    })
+        audit_request_id,
+        trace_id: telemetry::current_trace_id(),
+        diff: None,
+        tests: None,
+        attempts,
+    }
+}
+
+// ─── Batch Handlers ───────────────────────────────────────────────────────────
+
+/// POST /batches - modernize every program under a prefix; poll GET /batches/{batch_id}
+async fn create_batch(
+    state: web::Data<AppState>,
+    req: web::Json<BatchRequest>,
+) -> HttpResponse {
+    let req = req.into_inner();
+    if let Err(e) = req.check() {
+        return HttpResponse::BadRequest().json(serde_json::json!({
+            "error": e,
+            "code": "INVALID_REQUEST"
+        }));
+    }
+    let max_concurrency = req.max_concurrency.map_or(state.max_batch_concurrency, |n| n.min(state.max_batch_concurrency));
+    let trace_id = telemetry::current_trace_id();
+    let (batch, cancel) = match state.batches.create(&req, max_concurrency, trace_id.clone()) {
+        Ok(created) => created,
+        Err(e) => return batch_error(e),
+    };
+    info!("📦 Started batch {} for s3://{}/{}", req.batch_id, req.bucket, req.prefix);
+
+    let attributes = vec![
+        KeyValue::new("batch.id", req.batch_id.clone()),
+        KeyValue::new("batch.prefix", req.prefix.clone()),
+    ];
+    let run = run_batch(state.clone(), req, max_concurrency, cancel);
+    actix_web::rt::spawn(telemetry::with_current_context(
+        telemetry::in_span(format!("batch {}", batch.batch_id), attributes, run)));
+
+    HttpResponse::Accepted().json(serde_json::json!({
+        "batch_id": batch.batch_id,
+        "status": batch.status,
+        "max_concurrency": batch.max_concurrency,
+        "status_url": format!("/batches/{}", batch.batch_id),
+        "trace_id": trace_id,
+    }))
+}
+
+/// GET /batches/{batch_id} - programs found, their jobs' status and, once done, the report
+async fn get_batch(state: web::Data<AppState>, path: web::Path<String>) -> HttpResponse {
+    let Some(mut batch) = state.batches.get(&path) else {
+        return batch_error(BatchError::NotFound);
+    };
+    // Programs still running show where their job is
+    for program in batch.programs.iter_mut().filter(|p| !p.status.is_finished()) {
+        if let Some(job) = program.job_id.as_deref().and_then(|id| state.jobs.get(id)) {
+            program.status = job.status;
+        }
+    }
+    HttpResponse::Ok().json(batch)
+}
+
+/// DELETE /batches/{batch_id} - start no more jobs and cancel the running ones
+async fn cancel_batch(state: web::Data<AppState>, path: web::Path<String>) -> HttpResponse {
+    match state.batches.cancel(&path) {
+        Ok(()) => {
+            info!("🛑 Cancelling batch {}", path);
+            HttpResponse::Accepted().json(serde_json::json!({
+                "batch_id": path.into_inner(),
+                "status": "cancelling",
+            }))
+        }
+        Err(e) => batch_error(e),
+    }
+}
+
+fn batch_error(e: BatchError) -> HttpResponse {
+    let (mut response, code) = match &e {
+        BatchError::NotFound => (HttpResponse::NotFound(), "BATCH_NOT_FOUND"),
+        BatchError::Finished(_) => (HttpResponse::Conflict(), "BATCH_FINISHED"),
+        BatchError::BatchActive => (HttpResponse::Conflict(), "BATCH_ACTIVE"),
+        BatchError::TooMany => (HttpResponse::ServiceUnavailable(), "BATCH_LIMIT_REACHED"),
+    };
+    response.json(serde_json::json!({
+        "error": e.to_string(),
+        "code": code
+    }))
+}
+
+/// Find the batch's programs, run a job for each and save the portfolio report
+async fn run_batch(
+    state: web::Data<AppState>,
+    req: BatchRequest,
+    max_concurrency: usize,
+    cancel: Arc<Notify>,
+) -> Result<(), String> {
+    let batches = &state.batches;
+    let batch_id = &req.batch_id;
+
+    let programs = match discover(&state, &req).await {
+        Ok(programs) => programs,
+        Err(e) => {
+            error!("Batch {} failed to list its programs: {}", batch_id, e);
+            batches.finish(batch_id, JobStatus::Failed, None, None, Some(e.clone()));
+            return Err(e);
+        }
+    };
+    info!("📦 Batch {} found {} programs under s3://{}/{}", batch_id, programs.len(), req.bucket, req.prefix);
+    batches.set_programs(batch_id, programs.clone());
+
+    let cancelled = run_programs(&state, &req, &programs, max_concurrency, &cancel).await;
+
+    let batch = batches.get(batch_id).ok_or("Batch disappeared while running")?;
+    let report = batch::report(&batch, |job_id| state.jobs.get(job_id), &state.pricing);
+    let summary = &report.summary;
+    info!("📊 Batch {}: {} of {} programs match, {} mismatch, {} did not compile, {} failed, ${:.2} spent",
+          batch_id, summary.matched, summary.programs, summary.mismatch, summary.compile_failed,
+          summary.failed, summary.cost_usd);
+
+    let status = if cancelled { JobStatus::Cancelled } else { JobStatus::Succeeded };
+    match save_report(&state, batch_id, &report).await {
+        Ok(url) => {
+            batches.finish(batch_id, status, Some(report), url, None);
+            Ok(())
+        }
+        Err(e) => {
+            error!("Batch {} report was not saved: {}", batch_id, e);
+            let e = format!("Failed to save the portfolio report: {}", e);
+            batches.finish(batch_id, JobStatus::Failed, Some(report), None, Some(e.clone()));
+            Err(e)
+        }
+    }
+}
+
+/// List the prefix with a token good for that alone, and pick out the programs
+async fn discover(state: &AppState, req: &BatchRequest) -> Result<Vec<Program>, String> {
+    let gw = &state.gateway;
+    let task = gw.delegate(&format!("{}-list", req.batch_id), serde_json::json!([
+        {"target_mcp": "s3_mcp", "operation": "list_objects", "bucket": req.bucket},
+    ])).await?;
+    let listed = gw.invoke_mcp(&task, "s3_mcp", "list_objects", serde_json::json!({
+        "bucket": req.bucket,
+        "prefix": req.prefix,
+    })).await;
+    gw.end_task(task).await;
+
+    let keys: Vec<String> = serde_json::from_value(listed?["objects"].clone())
+        .map_err(|e| format!("Invalid object list: {}", e))?;
+    let programs = batch::programs(&req.batch_id, &keys);
+    if programs.len() > batch::MAX_BATCH_PROGRAMS {
+        return Err(format!("{} programs found; a batch takes at most {}",
+                           programs.len(), batch::MAX_BATCH_PROGRAMS));
+    }
+    Ok(programs)
+}
+
+/// Run a job per program, at most `max_concurrency` at once, until all have
+/// ended; true if the batch was cancelled on the way
+async fn run_programs(
+    state: &web::Data<AppState>,
+    req: &BatchRequest,
+    programs: &[Program],
+    max_concurrency: usize,
+    cancel: &Notify,
+) -> bool {
+    let batch_id = &req.batch_id;
+    let limit = Arc::new(Semaphore::new(max_concurrency.max(1)));
+    let mut running = tokio::task::JoinSet::new();
+    let mut cancelled = false;
+
+    'programs: for (i, program) in programs.iter().enumerate() {
+        let permit = tokio::select! {
+            _ = cancel.notified() => {
+                cancelled = true;
+                break;
+            }
+            permit = limit.clone().acquire_owned() => permit.expect("batch semaphore is never closed"),
+        };
+
+        let job_req = ModernizeRequest {
+            task_id: program.task_id.clone(),
+            source_location: SourceLocation { bucket: req.bucket.clone(), key: program.key.clone() },
+            max_repair_attempts: req.max_repair_attempts,
+            comparison: req.comparison,
+            test_cases: None,
+            test_suite: program.test_suite.clone()
+                .map(|key| SourceLocation { bucket: req.bucket.clone(), key }),
+        };
+        // A full queue (other batches, single jobs) holds the program back until a job ends
+        let created = loop {
+            let freed = state.jobs.freed();
+            tokio::pin!(freed);
+            freed.as_mut().enable();
+            match state.jobs.create(&job_req.task_id, &job_req.source_location, telemetry::current_trace_id()) {
+                Err(JobError::QueueFull) => info!("⏳ Batch {} waits for room in the job queue for {}", batch_id, program.key),
+                created => break created,
+            }
+            tokio::select! {
+                _ = cancel.notified() => {
+                    cancelled = true;
+                    break 'programs;
+                }
+                _ = freed => {}
+            }
+        };
+        let (job, job_cancel) = match created {
+            Ok(created) => created,
+            Err(e) => {
+                warn!("Batch {} could not queue {}: {}", batch_id, program.key, e);
+                state.batches.program_failed(batch_id, i, &e.to_string());
+                continue;
+            }
+        };
+        state.batches.program_queued(batch_id, i, &job.job_id);
+        info!("🚀 Batch {} queued {} as job {}", batch_id, program.key, job.job_id);
+
+        let attributes = vec![
+            KeyValue::new("task.id", job_req.task_id.clone()),
+            KeyValue::new("job.id", job.job_id.clone()),
+        ];
+        let (state, batch_id, job_id) = (state.clone(), batch_id.clone(), job.job_id.clone());
+        let run = run_job(state.clone(), job_id.clone(), job_req, job_cancel);
+        running.spawn_local(telemetry::with_current_context(
+            telemetry::in_span(format!("job {}", job_id), attributes, async move {
+                let result = run.await;
+                drop(permit);
+                let status = state.jobs.get(&job_id).map_or(JobStatus::Failed, |job| job.status);
+                state.batches.program_finished(&batch_id, i, status);
+                result
+            })));
+    }
+
+    if cancelled {
+        cancel_jobs(state, batch_id);
+    }
+    loop {
+        tokio::select! {
+            _ = cancel.notified(), if !cancelled => {
+                cancelled = true;
+                cancel_jobs(state, batch_id);
+            }
+            done = running.join_next() => if done.is_none() {
+                break;
+            },
+        }
+    }
+    cancelled
+}
+
+/// Cancel every job the batch started that is still queued or running
+fn cancel_jobs(state: &AppState, batch_id: &str) {
+    info!("🛑 Batch {} cancelled; stopping its jobs", batch_id);
+    let Some(batch) = state.batches.get(batch_id) else { return };
+    for job_id in batch.programs.iter().filter_map(|p| p.job_id.as_deref()) {
+        // Jobs that already ended refuse, which is fine
+        let _ = state.jobs.cancel(job_id);
+    }
+}
+
+/// Save the report under modernized/{batch_id}/ with a token good for that alone
+async fn save_report(state: &AppState, batch_id: &str, report: &PortfolioReport) -> Result<Option<String>, String> {
+    let gw = &state.gateway;
+    let key = format!("modernized/{}/{}", batch_id, batch::REPORT_NAME);
+    let content = serde_json::to_string_pretty(report)
+        .map_err(|e| format!("Failed to serialize the report: {}", e))?;
+    let task = gw.delegate(&format!("{}-report", batch_id), serde_json::json!([
+        {"target_mcp": "s3_mcp", "operation": "save_output",
+         "bucket": state.s3_bucket, "key_prefix": format!("modernized/{}/", batch_id)},
+    ])).await?;
+    let saved = gw.invoke_mcp(&task, "s3_mcp", "save_output", serde_json::json!({
+        "bucket": state.s3_bucket,
+        "key": key,
+        "content": content,
+    })).await;
+    gw.end_task(task).await;
+    let saved = saved?;
+    info!("💾 Batch {} report saved to {}", batch_id, key);
+    Ok(saved["presigned_url"].as_str().map(String::from))
 }
 
 async fn health() -> HttpResponse {
@@ -301,6 +1087,7 @@ async fn health() -> HttpResponse {
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
     env_logger::init_from_env(env_logger::Env::new().default_filter_or("info"));
+    let telemetry = telemetry::init("green-agent");
 
     let gateway_url = std::env::var("AGENT_GATEWAY_URL")
         .unwrap_or("http://agent-gateway:8090".to_string());
@@ -310,23 +1097,76 @@ async fn main() -> std::io::Result<()> {
     let s3_bucket = std::env::var("S3_BUCKET")
         .unwrap_or("mainframe-refactor-lab-venkatnagala".to_string());
 
-    let gateway = GatewayClient::new(gateway_url.clone(), agent_id, api_key.clone());
+    let gateway = GatewayClient::new(gateway_url.clone(), agent_id, api_key)
+        .expect("Invalid gateway TLS configuration");
 
     // Authenticate with gateway on startup
-    gateway.authenticate(&api_key).await
+    gateway.authenticate().await
         .expect("Failed to authenticate with Agent Gateway");
 
-    let state = web::Data::new(AppState { gateway, s3_bucket });
+    let max_jobs = std::env::var("MAX_CONCURRENT_JOBS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(2);
+    let max_repair_attempts = std::env::var("MAX_REPAIR_ATTEMPTS")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(2);
+    let normalization = std::env::var("OUTPUT_NORMALIZATION")
+        .map(|v| Normalization::parse(&v)
+            .expect("OUTPUT_NORMALIZATION must be exact, trim_trailing or numeric_tolerant"))
+        .unwrap_or(Normalization::Exact);
+    let comparison = CompareRules {
+        normalization,
+        numeric_tolerance: std::env::var("NUMERIC_TOLERANCE")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(0.0),
+    };
+    comparison.validate().expect("Invalid NUMERIC_TOLERANCE");
+    let max_batch_concurrency = std::env::var("MAX_BATCH_CONCURRENCY")
+        .ok()
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(4);
+    let pricing = Pricing {
+        input_per_mtok: std::env::var("CLAUDE_INPUT_COST_PER_MTOK")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(5.0),
+        output_per_mtok: std::env::var("CLAUDE_OUTPUT_COST_PER_MTOK")
+            .ok()
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(25.0),
+    };
+
+    let state = web::Data::new(AppState {
+        gateway,
+        s3_bucket,
+        jobs: JobStore::new(max_jobs),
+        max_repair_attempts,
+        comparison,
+        batches: BatchStore::default(),
+        max_batch_concurrency,
+        pricing,
+    });
 
     info!("🟢 Green Agent (Orchestrator) starting - Gateway: {}", gateway_url);
 
-    HttpServer::new(move || {
+    let result = HttpServer::new(move || {
         App::new()
             .app_data(state.clone())
+            .wrap(middleware::from_fn(telemetry::trace_requests))
             .route("/evaluate", web::post().to(evaluate))
+            .route("/jobs/{job_id}", web::get().to(get_job))
+            .route("/jobs/{job_id}", web::delete().to(cancel_job))
+            .route("/batches", web::post().to(create_batch))
+            .route("/batches/{batch_id}", web::get().to(get_batch))
+            .route("/batches/{batch_id}", web::delete().to(cancel_batch))
             .route("/health", web::get().to(health))
     })
     .bind("0.0.0.0:8080")?
     .run()
-    .await
+    .await;
+    telemetry.shutdown();
+    result
 }
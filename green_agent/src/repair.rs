@@ -0,0 +1,94 @@
+// Repair - feeding failures back into the translation
+//
+// A first translation that does not compile, or whose output differs from the
+// COBOL program's on some test case, is not the end of the run. The rustc
+// errors or the failing cases' diffs go back to ai_mcp as the translation's
+// `context`, together with the code that failed, and the new translation is
+// compiled and compared again, up to MAX_REPAIR_ATTEMPTS times. Every attempt
+// is kept in the job result.
+
+use serde::{Deserialize, Serialize};
+
+use mcp_common::run::RunOutput;
+
+use crate::cost::Usage;
+use crate::diff::OutputDiff;
+use crate::suite::{self, TestCase, TestReport};
+
+/// Most compiler output or diff sent back to ai_mcp, and kept per attempt
+const MAX_FEEDBACK_CHARS: usize = 8000;
+
+/// Most failing test cases described to ai_mcp
+const MAX_FEEDBACK_CASES: usize = 3;
+
+/// One translation of the program and how it fared
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Hash)]
+pub struct Attempt {
+    pub attempt: u32,
+    pub rust_code: String,
+    #[serde(default)]
+    pub usage: Usage, // Claude tokens the translation cost
+    pub compiled: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub compile_errors: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tests: Option<TestReport>, // Once the code compiled
+    pub match_confirmed: bool,
+}
+
+/// The errors from a cargo build log, without the "Compiling ..." lines before them
+pub fn compile_errors(compile_log: &str) -> String {
+    let start = compile_log.find("error").unwrap_or(0);
+    truncate(compile_log[start..].trim())
+}
+
+/// Context for the next translation, explaining what went wrong with
+/// `attempt`; `expected` is the COBOL run for each of `cases`
+pub fn feedback(attempt: &Attempt, cases: &[TestCase], expected: &[RunOutput]) -> String {
+    if let Some(errors) = &attempt.compile_errors {
+        return format!(
+            "Your previous translation did not compile. Fix these rustc errors and return \
+             the complete corrected program.\n\nPrevious translation:\n{}\n\nCompiler errors:\n{}",
+            attempt.rust_code, errors,
+        );
+    }
+
+    let results = attempt.tests.as_ref().map_or(&[][..], |t| &t.cases[..]);
+    let failing: Vec<String> = cases.iter().zip(expected).zip(results)
+        .filter(|(_, result)| !result.passed)
+        .take(MAX_FEEDBACK_CASES)
+        .map(|((case, expected), result)| {
+            let mut text = format!("Test case {}", case.name);
+            if let Some(stdin) = &case.input_data {
+                text.push_str(&format!("\nstdin:\n{}", truncate(stdin)));
+            }
+            for (name, content) in &case.files {
+                text.push_str(&format!("\ninput file {}:\n{}", name, truncate(content)));
+            }
+            text.push_str(&format!("\nExpected output (from COBOL):\n{}", truncate(suite::output(expected))));
+            if let Some(error) = &result.error {
+                text.push_str(&format!("\nThe Rust program failed: {}", truncate(error)));
+            }
+            if let Some(diff) = result.diff.as_ref().filter(|d| !d.matched) {
+                text.push_str(&format!(
+                    "\nDifferences (expected vs actual):\n{}", truncate(&OutputDiff::to_string(diff))));
+            }
+            text
+        })
+        .collect();
+    let failed = results.iter().filter(|r| !r.passed).count();
+
+    format!(
+        "Your previous translation compiled, but it behaves differently from the COBOL \
+         program on {} of {} test cases. Make it print exactly what the COBOL program \
+         prints, and exit with the same status, for every input.\n\nPrevious translation:\n{}\n\n{}",
+        failed, results.len(), attempt.rust_code, failing.join("\n\n"),
+    )
+}
+
+fn truncate(text: &str) -> String {
+    match text.char_indices().nth(MAX_FEEDBACK_CHARS) {
+        Some((end, _)) => format!("{}\n... (truncated)", &text[..end]),
+        None => text.to_string(),
+    }
+}
@@ -0,0 +1,240 @@
+// Suite - differential test cases for a run
+//
+// One run of each program with no input proves little about a translation.
+// A test suite lists the cases to run both programs on: the stdin for each,
+// and the files to put in its working directory (the INPUT.DAT a program
+// opens). It comes inline with /evaluate as `test_cases`, or from S3 as
+// `test_suite`, a JSON manifest `{"cases": [...]}` fetched with
+// s3_mcp/fetch_data. The COBOL program is run on every case for the expected
+// output, and the translation is only validated when the Rust program prints
+// the same and exits with the same status on all of them. Without a suite
+// both programs run once, with no input.
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+
+use mcp_common::run::{self, RunOutput};
+
+use crate::diff::{self, CompareRules, OutputDiff};
+
+/// Most test cases in a suite; the compile servers run at most this many per call
+pub const MAX_TEST_CASES: usize = run::MAX_CASES;
+
+/// Name of the single case run when no suite is given
+const DEFAULT_CASE: &str = "default";
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Hash)]
+pub struct TestCase {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub input_data: Option<String>, // stdin
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub files: BTreeMap<String, String>, // File name -> contents
+}
+
+#[derive(Deserialize)]
+struct Manifest {
+    cases: Vec<TestCase>,
+}
+
+/// How one case went for the Rust program
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Hash)]
+pub struct CaseResult {
+    pub name: String,
+    pub passed: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rust_output: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rust_exit_code: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cobol_exit_code: Option<i32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub diff: Option<OutputDiff>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>, // The Rust program failed, or exited unlike the COBOL one
+}
+
+/// What the suite exercised and how much of it passed
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Hash)]
+pub struct Coverage {
+    pub cases: usize,
+    pub passed: usize,
+    pub failed: usize,
+    pub stdin_cases: usize,              // Cases with stdin input
+    pub file_cases: usize,               // Cases with input files
+    pub distinct_expected_outputs: usize, // Different COBOL outputs; 1 suggests the cases take the same path
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq, Hash)]
+pub struct TestReport {
+    pub coverage: Coverage,
+    pub cases: Vec<CaseResult>,
+}
+
+impl TestReport {
+    /// Every case ran and matched
+    pub fn passed(&self) -> bool {
+        self.coverage.cases > 0 && self.coverage.failed == 0
+    }
+
+    /// The diff of the first failing case, or of the first case when all pass
+    pub fn first_diff(&self) -> Option<&OutputDiff> {
+        self.cases.iter()
+            .find(|c| !c.passed)
+            .or(self.cases.first())
+            .and_then(|c| c.diff.as_ref())
+    }
+}
+
+pub fn default_cases() -> Vec<TestCase> {
+    vec![TestCase { name: DEFAULT_CASE.to_string(), input_data: None, files: BTreeMap::new() }]
+}
+
+/// Cases from a manifest fetched from S3
+pub fn parse_manifest(content: &str) -> Result<Vec<TestCase>, String> {
+    let manifest: Manifest = serde_json::from_str(content)
+        .map_err(|e| format!("Invalid test suite manifest: {}", e))?;
+    check(&manifest.cases)?;
+    Ok(manifest.cases)
+}
+
+pub fn check(cases: &[TestCase]) -> Result<(), String> {
+    if cases.is_empty() || cases.len() > MAX_TEST_CASES {
+        return Err(format!("A test suite needs 1 to {} cases", MAX_TEST_CASES));
+    }
+    let mut names = HashSet::new();
+    for case in cases {
+        if case.name.trim().is_empty() {
+            return Err("Every test case needs a name".to_string());
+        }
+        if !names.insert(case.name.as_str()) {
+            return Err(format!("Duplicate test case name: {}", case.name));
+        }
+        case.files.keys().try_for_each(|name| run::check_file_name(name))?;
+    }
+    Ok(())
+}
+
+/// The per-case runs in a compile server's answer, one for each of `cases`
+pub fn runs(result: &serde_json::Value, cases: &[TestCase]) -> Result<Vec<RunOutput>, String> {
+    let runs: Vec<RunOutput> = serde_json::from_value(result["cases"].clone())
+        .map_err(|e| format!("Invalid case results: {}", e))?;
+    if runs.len() != cases.len() {
+        return Err(format!("Expected results for {} cases, got {}", cases.len(), runs.len()));
+    }
+    Ok(runs)
+}
+
+/// stdout of a run, empty if it printed nothing
+pub fn output(run: &RunOutput) -> &str {
+    run.output.as_deref().unwrap_or("")
+}
+
+/// Compare the Rust program's runs with the COBOL program's, case by case:
+/// both the output and the exit status must match
+pub fn evaluate(cases: &[TestCase], expected: &[RunOutput], actual: &[RunOutput], rules: &CompareRules) -> TestReport {
+    let results: Vec<CaseResult> = cases.iter().zip(expected).zip(actual).map(|((case, expected), run)| {
+        let diff = run.output.as_ref().map(|o| diff::compare(output(expected), o, rules));
+        let same_exit = run.exit_code == expected.exit_code;
+        let error = match (&run.error, &run.output) {
+            (Some(error), _) => Some(error.clone()),
+            (None, None) => Some("The program did not run".to_string()),
+            (None, Some(_)) if !same_exit => Some(format!(
+                "Exited with {}, the COBOL program with {}{}", status(run.exit_code), status(expected.exit_code),
+                run.stderr.as_ref().map_or(String::new(), |stderr| format!(": {}", stderr)))),
+            _ => None,
+        };
+        CaseResult {
+            name: case.name.clone(),
+            passed: same_exit && error.is_none() && diff.as_ref().is_some_and(|d| d.matched),
+            rust_output: run.output.clone(),
+            rust_exit_code: run.exit_code,
+            cobol_exit_code: expected.exit_code,
+            diff,
+            error,
+        }
+    }).collect();
+
+    let passed = results.iter().filter(|r| r.passed).count();
+    TestReport {
+        coverage: Coverage {
+            cases: results.len(),
+            passed,
+            failed: results.len() - passed,
+            stdin_cases: cases.iter().filter(|c| c.input_data.is_some()).count(),
+            file_cases: cases.iter().filter(|c| !c.files.is_empty()).count(),
+            distinct_expected_outputs: expected.iter().map(output).collect::<HashSet<_>>().len(),
+        },
+        cases: results,
+    }
+}
+
+fn status(exit_code: Option<i32>) -> String {
+    exit_code.map_or("no status (killed by a signal)".to_string(), |code| format!("status {}", code))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::diff::Normalization;
+
+    fn case(name: &str) -> TestCase {
+        TestCase { name: name.to_string(), input_data: None, files: BTreeMap::new() }
+    }
+
+    fn exited(code: i32, output: &str, stderr: Option<&str>) -> RunOutput {
+        RunOutput {
+            name: None,
+            success: code == 0,
+            output: Some(output.to_string()),
+            exit_code: Some(code),
+            stderr: stderr.map(String::from),
+            error: None,
+        }
+    }
+
+    fn exact() -> CompareRules {
+        CompareRules { normalization: Normalization::Exact, numeric_tolerance: 0.0 }
+    }
+
+    #[test]
+    fn same_non_zero_status_and_output_passes() {
+        let report = evaluate(&[case("rc4")], &[exited(4, "TOTAL 42\n", None)], &[exited(4, "TOTAL 42\n", None)], &exact());
+        assert!(report.cases[0].passed);
+        assert_eq!(report.cases[0].error, None);
+        assert_eq!(report.coverage.passed, 1);
+    }
+
+    #[test]
+    fn different_status_fails_even_with_the_same_output() {
+        let report = evaluate(&[case("rc")], &[exited(4, "TOTAL 42\n", None)],
+                              &[exited(101, "TOTAL 42\n", Some("panicked at src/main.rs:3"))], &exact());
+        let result = &report.cases[0];
+        assert!(!result.passed);
+        assert!(result.diff.as_ref().is_some_and(|d| d.matched));
+        assert_eq!(result.error.as_deref(),
+                   Some("Exited with status 101, the COBOL program with status 4: panicked at src/main.rs:3"));
+    }
+
+    #[test]
+    fn different_output_fails_with_the_same_status() {
+        let report = evaluate(&[case("rc4")], &[exited(4, "TOTAL 42\n", None)], &[exited(4, "TOTAL 43\n", None)], &exact());
+        assert!(!report.cases[0].passed);
+        assert_eq!(report.cases[0].error, None);
+    }
+
+    #[test]
+    fn a_run_that_did_not_exit_fails() {
+        let killed = RunOutput {
+            name: None,
+            success: false,
+            output: None,
+            exit_code: None,
+            stderr: None,
+            error: Some("Execution timed out after 30s".to_string()),
+        };
+        let report = evaluate(&[case("slow")], &[exited(0, "", None)], &[killed], &exact());
+        assert!(!report.cases[0].passed);
+        assert_eq!(report.cases[0].error.as_deref(), Some("Execution timed out after 30s"));
+    }
+}
@@ -0,0 +1,254 @@
+// Gateway Assertions - proof that an MCP call came through the Agent Gateway
+//
+// After authorizing a call, the gateway signs a short-lived assertion naming
+// the calling agent, its role, the operation, the request ID and the SHA-256
+// of the exact body it forwards, scoped to the target MCP server (`aud`). MCP
+// servers verify it against the gateway's JWKS, so a request that skipped the
+// gateway, was meant for another server, names a different operation or
+// carries a different payload is rejected even from inside the network. Each
+// assertion is accepted once: a captured one cannot be replayed while it lives.
+
+use actix_web::body::{EitherBody, MessageBody};
+use actix_web::dev::{Payload, ServiceRequest, ServiceResponse};
+use actix_web::middleware::Next;
+use actix_web::{web, HttpMessage, HttpResponse};
+use jsonwebtoken::jwk::JwkSet;
+use jsonwebtoken::{decode, decode_header, Algorithm, DecodingKey, Validation};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::{Mutex, RwLock};
+use std::time::{Duration, Instant};
+use log::{info, warn};
+
+use crate::mcp::MCP_PATH;
+use crate::tls::{self, PeerCertificate, TlsSettings};
+
+/// Header carrying the signed assertion
+pub const ASSERTION_HEADER: &str = "X-Gateway-Assertion";
+
+/// `iss` of every assertion
+pub const ASSERTION_ISSUER: &str = "agent-gateway";
+
+/// Assertions only need to outlive a single MCP call
+pub const ASSERTION_TTL_SECS: i64 = 60;
+
+/// Paths MCP servers serve without an assertion (probes)
+pub const UNAUTHENTICATED_PATHS: &[&str] = &["/health", "/ready"];
+
+/// Minimum time between JWKS fetches, so unknown kids can't hammer the gateway
+const JWKS_REFETCH_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Clock skew tolerated between the gateway and MCP servers
+const LEEWAY_SECS: u64 = 5;
+
+/// Largest request body hashed and passed on to the handlers
+const MAX_BODY_BYTES: usize = 8 * 1024 * 1024;
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct GatewayAssertion {
+    pub iss: String,        // Always ASSERTION_ISSUER
+    pub aud: String,        // Target MCP server name
+    pub sub: String,        // Calling agent_id
+    pub role: String,       // Calling agent's role
+    pub operation: String,  // Operation the gateway authorized
+    pub request_id: String, // Gateway request ID (also in X-Request-ID)
+    pub payload_sha256: String, // Hex SHA-256 of the exact request body forwarded
+    pub iat: usize,
+    pub exp: usize,
+    pub jti: String,
+}
+
+/// Verifies assertions for one MCP server against the gateway's JWKS
+pub struct AssertionVerifier {
+    audience: String,
+    jwks_url: String,
+    client: reqwest::Client,
+    keys: RwLock<HashMap<String, (Algorithm, DecodingKey)>>, // kid -> key
+    last_fetch: tokio::sync::Mutex<Option<Instant>>,        // Also serializes fetches
+    require_client_cert: bool,                              // Listener verifies client certificates
+    seen: Mutex<HashMap<String, usize>>,                    // jti -> exp of assertions already used
+}
+
+/// Hex SHA-256 of a request body, as carried in `payload_sha256`
+pub fn payload_sha256(body: &[u8]) -> String {
+    hex::encode(Sha256::digest(body))
+}
+
+impl AssertionVerifier {
+    /// Verifier for the MCP server named `audience`, using GATEWAY_JWKS_URL.
+    /// When the listener verifies client certificates, requests must also present one.
+    pub fn from_env(audience: &str) -> std::io::Result<Self> {
+        let jwks_url = std::env::var("GATEWAY_JWKS_URL")
+            .unwrap_or("http://agent-gateway:8090/.well-known/jwks.json".to_string());
+        info!("🔏 Requiring gateway assertions for '{}' (keys from {})", audience, jwks_url);
+
+        Ok(AssertionVerifier {
+            audience: audience.to_string(),
+            jwks_url,
+            client: tls::http_client(Duration::from_secs(5))?,
+            keys: RwLock::new(HashMap::new()),
+            last_fetch: tokio::sync::Mutex::new(None),
+            require_client_cert: TlsSettings::from_env().is_some_and(|t| t.verifies_clients()),
+            seen: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Check the signature, issuer, audience and expiry of `token`
+    pub async fn verify(&self, token: &str) -> Result<GatewayAssertion, String> {
+        let header = decode_header(token).map_err(|e| format!("Assertion decode failed: {}", e))?;
+        let kid = header.kid.ok_or("Assertion has no key ID")?;
+        let (algorithm, key) = self.key(&kid).await?;
+        if header.alg != algorithm {
+            return Err(format!("Algorithm {:?} does not match key '{}'", header.alg, kid));
+        }
+
+        let mut validation = Validation::new(algorithm);
+        validation.leeway = LEEWAY_SECS;
+        validation.set_audience(&[&self.audience]);
+        validation.set_issuer(&[ASSERTION_ISSUER]);
+        validation.set_required_spec_claims(&["exp", "aud", "iss", "sub"]);
+
+        decode::<GatewayAssertion>(token, &key, &validation)
+            .map(|data| data.claims)
+            .map_err(|e| format!("Invalid assertion: {}", e))
+    }
+
+    /// Accept `assertion` only the first time its jti is seen. Entries are
+    /// dropped once the assertion has expired, when it can't verify anyway.
+    fn first_use(&self, assertion: &GatewayAssertion) -> Result<(), String> {
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs() as usize);
+        let mut seen = self.seen.lock().unwrap();
+        seen.retain(|_, exp| *exp + LEEWAY_SECS as usize >= now);
+        if seen.contains_key(&assertion.jti) {
+            return Err(format!("Assertion {} was already used", assertion.jti));
+        }
+        seen.insert(assertion.jti.clone(), assertion.exp);
+        Ok(())
+    }
+
+    /// Key for `kid`, fetching the JWKS again if it is not known yet
+    async fn key(&self, kid: &str) -> Result<(Algorithm, DecodingKey), String> {
+        if let Some(key) = self.keys.read().unwrap().get(kid) {
+            return Ok(key.clone());
+        }
+
+        let mut last_fetch = self.last_fetch.lock().await;
+        // Another request may have fetched while we waited
+        if let Some(key) = self.keys.read().unwrap().get(kid) {
+            return Ok(key.clone());
+        }
+        if last_fetch.is_some_and(|at| at.elapsed() < JWKS_REFETCH_INTERVAL) {
+            return Err(format!("Unknown signing key '{}'", kid));
+        }
+        *last_fetch = Some(Instant::now());
+
+        let keys = self.fetch_jwks().await?;
+        let found = keys.get(kid).cloned();
+        *self.keys.write().unwrap() = keys;
+        found.ok_or_else(|| format!("Unknown signing key '{}'", kid))
+    }
+
+    async fn fetch_jwks(&self) -> Result<HashMap<String, (Algorithm, DecodingKey)>, String> {
+        let jwks: JwkSet = self.client
+            .get(&self.jwks_url)
+            .send()
+            .await
+            .and_then(|r| r.error_for_status())
+            .map_err(|e| format!("Gateway JWKS unavailable: {}", e))?
+            .json()
+            .await
+            .map_err(|e| format!("Invalid gateway JWKS: {}", e))?;
+
+        let mut keys = HashMap::new();
+        for jwk in &jwks.keys {
+            let (Some(kid), Some(alg)) = (&jwk.common.key_id, &jwk.common.key_algorithm) else {
+                warn!("Skipping JWKS key without kid or alg");
+                continue;
+            };
+            let Ok(algorithm) = Algorithm::from_str(&alg.to_string()) else {
+                warn!("Skipping JWKS key '{}' with unsupported alg {}", kid, alg);
+                continue;
+            };
+            match DecodingKey::from_jwk(jwk) {
+                Ok(key) => { keys.insert(kid.clone(), (algorithm, key)); }
+                Err(e) => warn!("Skipping JWKS key '{}': {}", kid, e),
+            }
+        }
+
+        info!("🔏 Loaded {} gateway signing keys", keys.len());
+        Ok(keys)
+    }
+}
+
+/// Middleware (for `middleware::from_fn`) rejecting requests without a valid
+/// assertion for this server and the requested operation. Needs an
+/// `AssertionVerifier` in app data; handlers can take `web::ReqData<GatewayAssertion>`.
+pub async fn require_assertion<B: MessageBody>(
+    mut req: ServiceRequest,
+    next: Next<B>,
+) -> Result<ServiceResponse<EitherBody<B>>, actix_web::Error> {
+    if UNAUTHENTICATED_PATHS.contains(&req.path()) {
+        return next.call(req).await.map(ServiceResponse::map_into_left_body);
+    }
+
+    match check_assertion(&mut req).await {
+        Ok(assertion) => {
+            info!("Gateway assertion OK: agent={} op={} req_id={}",
+                  assertion.sub, assertion.operation, assertion.request_id);
+            req.extensions_mut().insert(assertion);
+            next.call(req).await.map(ServiceResponse::map_into_left_body)
+        }
+        Err((code, message)) => {
+            warn!("Rejected {} {}: {}", req.method(), req.path(), message);
+            let response = HttpResponse::Unauthorized().json(serde_json::json!({
+                "error": message,
+                "code": code
+            }));
+            Ok(req.into_response(response).map_into_right_body())
+        }
+    }
+}
+
+async fn check_assertion(req: &mut ServiceRequest) -> Result<GatewayAssertion, (&'static str, String)> {
+    let verifier = req.app_data::<web::Data<AssertionVerifier>>()
+        .cloned()
+        .ok_or(("ASSERTION_UNCONFIGURED", "No AssertionVerifier configured".to_string()))?;
+
+    if verifier.require_client_cert && req.conn_data::<PeerCertificate>().is_none() {
+        return Err(("CLIENT_CERT_REQUIRED", "A verified client certificate is required".to_string()));
+    }
+
+    let token = req.headers()
+        .get(ASSERTION_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .map(String::from)
+        .ok_or(("MISSING_ASSERTION", format!("Missing {} header; call via the Agent Gateway", ASSERTION_HEADER)))?;
+
+    let assertion = verifier.verify(&token).await.map_err(|e| ("INVALID_ASSERTION", e))?;
+
+    // An assertion for one operation must not unlock another. JSON-RPC calls
+    // name their tool in the body, so the MCP endpoint checks those itself.
+    let operation = req.path().trim_start_matches('/');
+    if req.path() != MCP_PATH && assertion.operation != operation {
+        return Err(("INVALID_ASSERTION", format!(
+            "Assertion is for operation '{}', not '{}'", assertion.operation, operation)));
+    }
+
+    // The body must be the one the gateway checked; it is put back for the handler
+    let body = req.extract::<web::Payload>().await
+        .map_err(|e| ("INVALID_BODY", format!("Failed to read the request body: {}", e)))?
+        .to_bytes_limited(MAX_BODY_BYTES).await
+        .map_err(|_| ("INVALID_BODY", format!("Request body exceeds {} bytes", MAX_BODY_BYTES)))?
+        .map_err(|e| ("INVALID_BODY", format!("Failed to read the request body: {}", e)))?;
+    if payload_sha256(&body) != assertion.payload_sha256 {
+        return Err(("INVALID_ASSERTION", "Request body does not match the assertion".to_string()));
+    }
+    req.set_payload(Payload::from(body));
+
+    verifier.first_use(&assertion).map_err(|e| ("ASSERTION_REPLAYED", e))?;
+    Ok(assertion)
+}
@@ -0,0 +1,83 @@
+// Health - readiness reports for the MCP servers
+//
+// GET /health only says the process is up. GET /ready runs the server's
+// dependency checks (toolchains, scratch space, credentials, bucket access)
+// and answers 200 when every one passes and 503 otherwise, with each check's
+// outcome in the body. The gateway's own /ready polls these to report on the
+// whole fleet.
+
+use actix_web::HttpResponse;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::time::Duration;
+
+/// Longest a single command check may run before it counts as failed
+pub const CHECK_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// The outcome of one dependency check
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Check {
+    pub name: String,
+    pub ok: bool,
+    pub detail: String, // What was found on success, why it failed otherwise
+}
+
+impl Check {
+    pub fn pass(name: &str, detail: impl Into<String>) -> Self {
+        Check { name: name.to_string(), ok: true, detail: detail.into() }
+    }
+
+    pub fn fail(name: &str, detail: impl Into<String>) -> Self {
+        Check { name: name.to_string(), ok: false, detail: detail.into() }
+    }
+}
+
+/// Run a command and pass if it exits successfully within CHECK_TIMEOUT.
+/// The detail is the first line it printed, typically a version string.
+pub async fn command(name: &str, program: &str, args: &[&str]) -> Check {
+    let run = tokio::process::Command::new(program)
+        .args(args)
+        .kill_on_drop(true)
+        .output();
+
+    match tokio::time::timeout(CHECK_TIMEOUT, run).await {
+        Ok(Ok(output)) if output.status.success() => {
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            Check::pass(name, stdout.lines().next().unwrap_or_default().trim())
+        }
+        Ok(Ok(output)) => {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            Check::fail(name, format!("{} exited with {}: {}", program, output.status, stderr.trim()))
+        }
+        Ok(Err(e)) => Check::fail(name, format!("{} could not be run: {}", program, e)),
+        Err(_) => Check::fail(name, format!("{} did not finish within {}s", program, CHECK_TIMEOUT.as_secs())),
+    }
+}
+
+/// Pass if a file can be created (and removed again) in `dir`
+pub fn writable_dir(name: &str, dir: &Path) -> Check {
+    let probe = dir.join(format!(".ready-probe-{}", std::process::id()));
+    match std::fs::write(&probe, b"ok") {
+        Ok(()) => {
+            let _ = std::fs::remove_file(&probe);
+            Check::pass(name, format!("{} is writable", dir.display()))
+        }
+        Err(e) => Check::fail(name, format!("{} is not writable: {}", dir.display(), e)),
+    }
+}
+
+/// 200 {"status": "ready", ...} when every check passed, 503 "not_ready" otherwise
+pub fn readiness(service: &str, version: &str, checks: Vec<Check>) -> HttpResponse {
+    let ready = checks.iter().all(|c| c.ok);
+    let body = serde_json::json!({
+        "status": if ready { "ready" } else { "not_ready" },
+        "service": service,
+        "version": version,
+        "checks": checks,
+    });
+    if ready {
+        HttpResponse::Ok().json(body)
+    } else {
+        HttpResponse::ServiceUnavailable().json(body)
+    }
+}
@@ -0,0 +1,20 @@
+// MCP Common - code shared by the MCP servers, the Agent Gateway and Green Agent
+//
+//   auth      - the signed assertion the gateway forwards with every MCP call,
+//               and the middleware MCP servers use to require it
+//   health    - dependency checks behind each server's GET /ready
+//   mcp       - Model Context Protocol (JSON-RPC 2.0) tool server over HTTP and
+//               stdio, plus the REST routes the gateway calls
+//   run       - running compiled programs on stdin and input files, one test
+//               case at a time
+//   telemetry - OpenTelemetry spans, W3C trace-context propagation and OTLP
+//               export, for the agents as well
+//   tls       - optional HTTPS listeners that verify client certificates, and an
+//               HTTP client that presents one
+
+pub mod auth;
+pub mod health;
+pub mod mcp;
+pub mod run;
+pub mod telemetry;
+pub mod tls;
@@ -0,0 +1,284 @@
+// Model Context Protocol - JSON-RPC 2.0 tool server shared by the MCP binaries
+//
+// Each server registers its operations once as typed tools; the input schema
+// comes from the request struct's JsonSchema derive. The same tools are then
+// served three ways:
+//   POST /mcp          - MCP streamable HTTP (one JSON-RPC message per POST)
+//   --stdio            - MCP over stdin/stdout, one JSON-RPC message per line
+//   POST /{operation}  - the original REST routes, used by the Agent Gateway
+
+use actix_web::{web, HttpMessage, HttpRequest, HttpResponse};
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{json, Value};
+use std::future::Future;
+use std::pin::Pin;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt};
+use log::{info, warn};
+
+use crate::auth::GatewayAssertion;
+use crate::telemetry::{self, KeyValue};
+
+/// Route of the streamable HTTP endpoint
+pub const MCP_PATH: &str = "/mcp";
+
+/// Protocol revisions this server speaks, newest first
+pub const PROTOCOL_VERSIONS: &[&str] = &["2025-06-18", "2025-03-26", "2024-11-05"];
+
+// JSON-RPC 2.0 error codes
+pub const PARSE_ERROR: i64 = -32700;
+pub const INVALID_REQUEST: i64 = -32600;
+pub const METHOD_NOT_FOUND: i64 = -32601;
+pub const INVALID_PARAMS: i64 = -32602;
+pub const INTERNAL_ERROR: i64 = -32603;
+
+type ToolFuture = Pin<Box<dyn Future<Output = Result<ToolOutput, ToolError>> + Send>>;
+
+/// What a tool returned; `is_error` is set when the operation itself failed
+pub struct ToolOutput {
+    pub is_error: bool,
+    pub content: Value,
+}
+
+#[derive(Debug)]
+pub enum ToolError {
+    UnknownTool(String),
+    InvalidArguments(String),
+}
+
+impl std::fmt::Display for ToolError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ToolError::UnknownTool(name) => write!(f, "Unknown tool: {}", name),
+            ToolError::InvalidArguments(e) => write!(f, "Invalid arguments: {}", e),
+        }
+    }
+}
+
+struct Tool {
+    name: String,
+    description: String,
+    input_schema: Value,
+    call: Box<dyn Fn(Value) -> ToolFuture + Send + Sync>,
+}
+
+pub struct McpServer {
+    name: String,
+    version: String,
+    tools: Vec<Tool>,
+}
+
+impl McpServer {
+    pub fn new(name: &str, version: &str) -> Self {
+        McpServer { name: name.to_string(), version: version.to_string(), tools: Vec::new() }
+    }
+
+    /// Register an operation. `Ok` responses are normal results; `Err`
+    /// responses are returned too, but flagged as errors (HTTP 500 over REST).
+    pub fn tool<Req, Resp, F, Fut>(mut self, name: &str, description: &str, handler: F) -> Self
+    where
+        Req: JsonSchema + DeserializeOwned + Send + 'static,
+        Resp: Serialize + 'static,
+        F: Fn(Req) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = Result<Resp, Resp>> + Send + 'static,
+    {
+        let mut input_schema = serde_json::to_value(schemars::schema_for!(Req))
+            .expect("JSON Schema is always serializable");
+        if let Some(schema) = input_schema.as_object_mut() {
+            schema.remove("$schema");
+        }
+
+        let handler = std::sync::Arc::new(handler);
+        let call = move |arguments: Value| -> ToolFuture {
+            let handler = handler.clone();
+            Box::pin(async move {
+                let request: Req = serde_json::from_value(arguments)
+                    .map_err(|e| ToolError::InvalidArguments(e.to_string()))?;
+                let (is_error, response) = match handler(request).await {
+                    Ok(r) => (false, r),
+                    Err(r) => (true, r),
+                };
+                let content = serde_json::to_value(response).unwrap_or(Value::Null);
+                Ok(ToolOutput { is_error, content })
+            })
+        };
+
+        self.tools.push(Tool {
+            name: name.to_string(),
+            description: description.to_string(),
+            input_schema,
+            call: Box::new(call),
+        });
+        self
+    }
+
+    /// Run the named tool with JSON arguments, in a span of its own
+    pub async fn call(&self, name: &str, arguments: Value) -> Result<ToolOutput, ToolError> {
+        let tool = self.tools.iter()
+            .find(|t| t.name == name)
+            .ok_or_else(|| ToolError::UnknownTool(name.to_string()))?;
+        let attributes = vec![
+            KeyValue::new("mcp.server", self.name.clone()),
+            KeyValue::new("mcp.tool", tool.name.clone()),
+        ];
+        telemetry::in_span(format!("tool {}", name), attributes, (tool.call)(arguments)).await
+    }
+
+    /// Answer one JSON-RPC message; `None` for notifications and responses.
+    /// `assertion` is the verified gateway assertion on HTTP, and `None` on stdio.
+    pub async fn handle(&self, message: Value, assertion: Option<&GatewayAssertion>) -> Option<Value> {
+        let id = message.get("id").cloned();
+        let Some(method) = message.get("method").and_then(Value::as_str) else {
+            // A response to something we never send, or not JSON-RPC at all
+            return id.map(|id| jsonrpc_error(id, INVALID_REQUEST, "Missing method".to_string()));
+        };
+        let id = id?; // Notifications (e.g. notifications/initialized) get no reply
+        let params = message.get("params").cloned().unwrap_or(Value::Null);
+
+        let result = match method {
+            "initialize" => Ok(self.initialize(&params)),
+            "ping" => Ok(json!({})),
+            "tools/list" => Ok(json!({ "tools": self.list_tools() })),
+            "tools/call" => self.call_tool(&params, assertion).await,
+            _ => Err((METHOD_NOT_FOUND, format!("Method not found: {}", method))),
+        };
+
+        Some(match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err((code, message)) => jsonrpc_error(id, code, message),
+        })
+    }
+
+    fn initialize(&self, params: &Value) -> Value {
+        let requested = params.get("protocolVersion").and_then(Value::as_str);
+        let version = requested
+            .filter(|v| PROTOCOL_VERSIONS.contains(v))
+            .unwrap_or(PROTOCOL_VERSIONS[0]);
+
+        json!({
+            "protocolVersion": version,
+            "capabilities": { "tools": { "listChanged": false } },
+            "serverInfo": { "name": self.name, "version": self.version }
+        })
+    }
+
+    fn list_tools(&self) -> Vec<Value> {
+        self.tools.iter()
+            .map(|t| json!({
+                "name": t.name,
+                "description": t.description,
+                "inputSchema": t.input_schema
+            }))
+            .collect()
+    }
+
+    async fn call_tool(&self, params: &Value, assertion: Option<&GatewayAssertion>) -> Result<Value, (i64, String)> {
+        let name = params.get("name")
+            .and_then(Value::as_str)
+            .ok_or((INVALID_PARAMS, "tools/call requires a tool name".to_string()))?;
+
+        // Over HTTP, the gateway authorized exactly one operation
+        if let Some(assertion) = assertion {
+            if assertion.operation != name {
+                return Err((INVALID_PARAMS, format!(
+                    "Assertion is for operation '{}', not '{}'", assertion.operation, name)));
+            }
+        }
+
+        let arguments = params.get("arguments").cloned().unwrap_or(json!({}));
+        let output = self.call(name, arguments).await
+            .map_err(|e| (INVALID_PARAMS, e.to_string()))?;
+
+        Ok(json!({
+            "content": [{ "type": "text", "text": output.content.to_string() }],
+            "structuredContent": output.content,
+            "isError": output.is_error
+        }))
+    }
+
+    /// Serve MCP over stdin/stdout until stdin closes
+    pub async fn serve_stdio(&self) -> std::io::Result<()> {
+        info!("🔌 {} serving MCP over stdio", self.name);
+        let mut lines = tokio::io::BufReader::new(tokio::io::stdin()).lines();
+        let mut stdout = tokio::io::stdout();
+
+        while let Some(line) = lines.next_line().await? {
+            if line.trim().is_empty() {
+                continue;
+            }
+            let reply = match serde_json::from_str::<Value>(&line) {
+                Ok(message) => self.handle(message, None).await,
+                Err(e) => Some(jsonrpc_error(Value::Null, PARSE_ERROR, format!("Parse error: {}", e))),
+            };
+            if let Some(reply) = reply {
+                let mut out = reply.to_string();
+                out.push('\n');
+                stdout.write_all(out.as_bytes()).await?;
+                stdout.flush().await?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// JSON-RPC error response for request `id`
+pub fn jsonrpc_error(id: Value, code: i64, message: String) -> Value {
+    json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } })
+}
+
+/// POST /mcp - streamable HTTP transport, answering with plain JSON
+pub async fn mcp_endpoint(server: web::Data<McpServer>, req: HttpRequest, body: web::Bytes) -> HttpResponse {
+    let message: Value = match serde_json::from_slice(&body) {
+        Ok(m) => m,
+        Err(e) => {
+            return HttpResponse::BadRequest()
+                .json(jsonrpc_error(Value::Null, PARSE_ERROR, format!("Parse error: {}", e)));
+        }
+    };
+    if !message.is_object() {
+        return HttpResponse::BadRequest().json(jsonrpc_error(
+            Value::Null, INVALID_REQUEST, "Expected a single JSON-RPC message".to_string()));
+    }
+
+    let assertion = req.extensions().get::<GatewayAssertion>().cloned();
+    match server.handle(message, assertion.as_ref()).await {
+        Some(reply) => HttpResponse::Ok().json(reply),
+        None => HttpResponse::Accepted().finish(),
+    }
+}
+
+/// GET /mcp - no server-initiated stream is offered
+pub async fn mcp_stream_unsupported() -> HttpResponse {
+    HttpResponse::MethodNotAllowed().insert_header(("Allow", "POST")).finish()
+}
+
+/// POST /{operation} - the REST form of every tool
+pub async fn rest_endpoint(
+    server: web::Data<McpServer>,
+    operation: web::Path<String>,
+    body: web::Json<Value>,
+) -> HttpResponse {
+    match server.call(&operation, body.into_inner()).await {
+        Ok(output) if output.is_error => HttpResponse::InternalServerError().json(output.content),
+        Ok(output) => HttpResponse::Ok().json(output.content),
+        Err(e @ ToolError::UnknownTool(_)) => HttpResponse::NotFound().json(json!({
+            "error": e.to_string(),
+            "code": "UNKNOWN_OPERATION"
+        })),
+        Err(e @ ToolError::InvalidArguments(_)) => {
+            warn!("Rejected {}: {}", operation, e);
+            HttpResponse::BadRequest().json(json!({
+                "error": e.to_string(),
+                "code": "INVALID_ARGUMENTS"
+            }))
+        }
+    }
+}
+
+/// Register the MCP and REST routes for `server` (after any fixed routes like /health)
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.route(MCP_PATH, web::post().to(mcp_endpoint))
+        .route(MCP_PATH, web::get().to(mcp_stream_unsupported))
+        .route("/{operation}", web::post().to(rest_endpoint));
+}
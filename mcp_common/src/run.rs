@@ -0,0 +1,198 @@
+// Run - executing the programs the compile servers build
+//
+// cobol_mcp and rust_mcp compile a program once and run it either once, with
+// `input_data` on stdin and `files` in its working directory, or once per
+// test case in `cases`. Every run gets a directory of its own holding its
+// input files, so a program that reads INPUT.DAT or writes a report file sees
+// only its own case. A run that does not finish in RUN_TIMEOUT is killed, and
+// all the runs of one call share RUNS_TIMEOUT: cases still waiting when it is
+// spent fail without running, so a call answers in bounded time. A non-zero
+// exit status is part of what a program does (a COBOL RETURN-CODE), so it is
+// reported as data with the run's stderr; only a run that never got to exit -
+// killed by a signal, timed out, or not started - has an error.
+
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+use std::process::Stdio;
+use std::time::{Duration, Instant};
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+use crate::telemetry::Outcome;
+
+/// Most test cases one compile call may run
+pub const MAX_CASES: usize = 100;
+
+/// Longest a single run of a program may take
+pub const RUN_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Longest all the runs of one compile call may take together; the gateway's
+/// timeouts for cobol_mcp and rust_mcp allow for it on top of the build
+pub const RUNS_TIMEOUT: Duration = Duration::from_secs(120);
+
+/// Most of a failed run's stderr kept
+const MAX_STDERR_CHARS: usize = 8000;
+
+/// The input of one run
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct RunInput {
+    /// Case name, echoed in the result
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Optional stdin input
+    #[serde(default)]
+    pub input_data: Option<String>,
+    /// Files to create in the program's working directory, by file name
+    #[serde(default)]
+    pub files: BTreeMap<String, String>,
+}
+
+/// What one run printed, or why it failed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunOutput {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    pub success: bool,          // Exited with status 0
+    pub output: Option<String>, // stdout
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub exit_code: Option<i32>, // None when killed by a signal
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub stderr: Option<String>, // Of a run that exited with a non-zero status
+    pub error: Option<String>,  // Why it did not run to an exit status
+}
+
+impl RunOutput {
+    /// Why the run did not succeed: its error, or the status it exited with
+    pub fn failure(&self) -> Option<String> {
+        if self.success {
+            return None;
+        }
+        self.error.clone().or_else(|| Some(failure(self.exit_code, self.stderr.as_deref().unwrap_or(""))))
+    }
+}
+
+impl Outcome for Vec<RunOutput> {
+    fn error(&self) -> Option<String> {
+        let failed = self.iter().filter(|o| o.error.is_some()).count();
+        (failed > 0).then(|| format!("{} of {} runs did not finish", failed, self.len()))
+    }
+}
+
+/// Input file names are plain names: no directories, nothing hidden
+pub fn check_file_name(name: &str) -> Result<(), String> {
+    let plain = !name.is_empty()
+        && !name.starts_with('.')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'));
+    if plain {
+        Ok(())
+    } else {
+        Err(format!("Invalid input file name {:?}: use letters, digits, '.', '_' and '-'", name))
+    }
+}
+
+/// Check the cases of a compile request before anything is built
+pub fn check_cases(files: &BTreeMap<String, String>, cases: &[RunInput]) -> Result<(), String> {
+    if cases.len() > MAX_CASES {
+        return Err(format!("At most {} cases may be run at once", MAX_CASES));
+    }
+    files.keys()
+        .chain(cases.iter().flat_map(|case| case.files.keys()))
+        .try_for_each(|name| check_file_name(name))
+}
+
+/// The runs a compile request asks for: one per case, or a single one with
+/// `input_data`. `files` go to every run, under any file a case brings itself.
+pub fn runs(input_data: Option<String>, files: BTreeMap<String, String>, cases: Vec<RunInput>) -> Vec<RunInput> {
+    if cases.is_empty() {
+        return vec![RunInput { name: None, input_data, files }];
+    }
+    cases.into_iter().map(|mut case| {
+        for (name, content) in &files {
+            case.files.entry(name.clone()).or_insert_with(|| content.clone());
+        }
+        case
+    }).collect()
+}
+
+/// Run `binary` on each case in turn, each in a directory of its own under
+/// `work_dir`, until RUNS_TIMEOUT is spent
+pub async fn run_cases(binary: &Path, work_dir: &Path, cases: &[RunInput]) -> Vec<RunOutput> {
+    let deadline = Instant::now() + RUNS_TIMEOUT;
+    let mut outputs = Vec::with_capacity(cases.len());
+    for (i, case) in cases.iter().enumerate() {
+        let dir = work_dir.join(format!("case-{}", i + 1));
+        let left = deadline.saturating_duration_since(Instant::now());
+        let result = if left.is_zero() {
+            Err(format!("Not run: the cases of one call may take {}s in all", RUNS_TIMEOUT.as_secs()))
+        } else {
+            run(binary, &dir, case, left.min(RUN_TIMEOUT)).await
+        };
+        let output = match result {
+            Ok(output) => output,
+            Err(e) => RunOutput { name: case.name.clone(), success: false, output: None, exit_code: None, stderr: None, error: Some(e) },
+        };
+        outputs.push(output);
+    }
+    outputs
+}
+
+async fn run(binary: &Path, dir: &Path, input: &RunInput, timeout: Duration) -> Result<RunOutput, String> {
+    std::fs::create_dir_all(dir).map_err(|e| format!("Failed to create run dir: {}", e))?;
+    for (name, content) in &input.files {
+        check_file_name(name)?;
+        std::fs::write(dir.join(name), content).map_err(|e| format!("Failed to write {}: {}", name, e))?;
+    }
+
+    let mut child = Command::new(binary)
+        .current_dir(dir)
+        .stdin(if input.input_data.is_some() { Stdio::piped() } else { Stdio::null() })
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| format!("Failed to spawn process: {}", e))?;
+    // Fed while the output is read, so neither pipe can fill up and stall the
+    // other; a program that exits without reading all of its input is not an error
+    let stdin = child.stdin.take();
+    let feed = async {
+        if let (Some(mut stdin), Some(data)) = (stdin, &input.input_data) {
+            let _ = stdin.write_all(data.as_bytes()).await;
+        }
+    };
+    let (_, output) = tokio::join!(feed, tokio::time::timeout(timeout, child.wait_with_output()));
+    let output = output
+        .map_err(|_| format!("Execution timed out after {}s", timeout.as_secs_f32().ceil()))?
+        .map_err(|e| format!("Execution failed: {}", e))?;
+    let success = output.status.success();
+    let exit_code = output.status.code();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    Ok(RunOutput {
+        name: input.name.clone(),
+        success,
+        output: Some(String::from_utf8_lossy(&output.stdout).to_string()),
+        exit_code,
+        stderr: (!success && exit_code.is_some()).then(|| truncate(stderr.trim())),
+        error: exit_code.is_none().then(|| failure(None, &stderr)),
+    })
+}
+
+/// The error of a run that exited with `exit_code`, or was killed
+fn failure(exit_code: Option<i32>, stderr: &str) -> String {
+    let status = match exit_code {
+        Some(code) => format!("Exited with status {}", code),
+        None => "Killed by a signal".to_string(),
+    };
+    match stderr.trim() {
+        "" => status,
+        stderr => format!("{}: {}", status, truncate(stderr)),
+    }
+}
+
+fn truncate(stderr: &str) -> String {
+    match stderr.char_indices().nth(MAX_STDERR_CHARS) {
+        Some((end, _)) => format!("{}\n... (truncated)", &stderr[..end]),
+        None => stderr.to_string(),
+    }
+}
@@ -0,0 +1,195 @@
+// Telemetry - OpenTelemetry traces across the pipeline
+//
+// Every HTTP service joins the caller's trace from the W3C `traceparent`
+// header and passes its own context on with each outgoing call, so one
+// modernization run is a single trace from green_agent through the gateway
+// to every MCP server it touches. Spans are exported over OTLP/HTTP when
+// OTEL_EXPORTER_OTLP_ENDPOINT (or OTEL_EXPORTER_OTLP_TRACES_ENDPOINT) is set,
+// e.g. http://otel-collector:4318; without it trace ids are still generated
+// and propagated, so logs and audit entries stay correlated.
+
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::http::header::HeaderMap;
+use actix_web::middleware::Next;
+use log::{info, warn};
+use opentelemetry::propagation::{Extractor, Injector};
+use opentelemetry::trace::{FutureExt, SpanKind, Status, TraceContextExt, Tracer};
+use opentelemetry::{global, Context};
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use opentelemetry_sdk::trace::SdkTracerProvider;
+use opentelemetry_sdk::Resource;
+use std::borrow::Cow;
+use std::fmt::Display;
+use std::future::Future;
+
+pub use opentelemetry::KeyValue;
+
+/// Instrumentation scope name on every span
+const TRACER_NAME: &str = "mainframe-modernization";
+
+/// Probe endpoints, polled every few seconds, are not traced
+const UNTRACED_PATHS: &[&str] = &["/health", "/ready", "/metrics"];
+
+/// The installed tracer provider; flush it before the process exits
+pub struct Telemetry {
+    provider: SdkTracerProvider,
+}
+
+impl Telemetry {
+    /// Export whatever spans are still buffered
+    pub fn shutdown(self) {
+        if let Err(e) = self.provider.shutdown() {
+            warn!("Trace export shutdown failed: {}", e);
+        }
+    }
+}
+
+/// Install W3C trace-context propagation and a tracer provider for
+/// `service_name` (OTEL_SERVICE_NAME overrides it), exporting over OTLP if an
+/// endpoint is configured
+pub fn init(service_name: &'static str) -> Telemetry {
+    global::set_text_map_propagator(TraceContextPropagator::new());
+
+    let mut resource = Resource::builder();
+    if std::env::var("OTEL_SERVICE_NAME").is_err() {
+        resource = resource.with_service_name(service_name);
+    }
+    let mut provider = SdkTracerProvider::builder().with_resource(resource.build());
+
+    let endpoint = ["OTEL_EXPORTER_OTLP_TRACES_ENDPOINT", "OTEL_EXPORTER_OTLP_ENDPOINT"]
+        .into_iter()
+        .find_map(|var| std::env::var(var).ok().filter(|v| !v.is_empty()));
+    if let Some(endpoint) = endpoint {
+        match opentelemetry_otlp::SpanExporter::builder().with_http().build() {
+            Ok(exporter) => {
+                info!("🔭 Exporting traces to {}", endpoint);
+                provider = provider.with_batch_exporter(exporter);
+            }
+            Err(e) => warn!("Trace export disabled, cannot build the OTLP exporter: {}", e),
+        }
+    }
+
+    let provider = provider.build();
+    global::set_tracer_provider(provider.clone());
+    Telemetry { provider }
+}
+
+/// How the work a span covers ended, for its status
+pub trait Outcome {
+    fn error(&self) -> Option<String>;
+}
+
+impl<T, E: Display> Outcome for Result<T, E> {
+    fn error(&self) -> Option<String> {
+        self.as_ref().err().map(|e| e.to_string())
+    }
+}
+
+/// Run `work` in a child span of the current context, marking the span as
+/// failed if the work does. Outgoing calls made by `work` carry this span.
+pub async fn in_span<F>(name: impl Into<Cow<'static, str>>, attributes: Vec<KeyValue>, work: F) -> F::Output
+where
+    F: Future,
+    F::Output: Outcome,
+{
+    let tracer = global::tracer(TRACER_NAME);
+    let span = tracer.span_builder(name)
+        .with_kind(SpanKind::Internal)
+        .with_attributes(attributes)
+        .start(&tracer);
+    let cx = Context::current_with_span(span);
+
+    let output = work.with_context(cx.clone()).await;
+    if let Some(error) = output.error() {
+        cx.span().set_status(Status::error(error));
+    }
+    output
+}
+
+/// Carry the current trace context into `work`, e.g. a task spawned to keep
+/// going after the request that started it has been answered
+pub fn with_current_context<F: Future>(work: F) -> impl Future<Output = F::Output> {
+    work.with_context(Context::current())
+}
+
+/// Add the current trace context to an outgoing request
+pub fn inject(request: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+    let mut headers = reqwest::header::HeaderMap::new();
+    global::get_text_map_propagator(|propagator| {
+        propagator.inject_context(&Context::current(), &mut HeaderInjector(&mut headers));
+    });
+    request.headers(headers)
+}
+
+/// Trace id of the current context, if there is a trace
+pub fn current_trace_id() -> Option<String> {
+    let cx = Context::current();
+    let span = cx.span();
+    let span_context = span.span_context();
+    span_context.is_valid().then(|| span_context.trace_id().to_string())
+}
+
+/// Middleware: serve each request in a server span that continues the
+/// caller's trace
+pub async fn trace_requests(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    if UNTRACED_PATHS.contains(&req.path()) {
+        return next.call(req).await;
+    }
+
+    let parent = global::get_text_map_propagator(|propagator| {
+        propagator.extract(&HeaderExtractor(req.headers()))
+    });
+    let route = req.match_pattern().unwrap_or_else(|| req.path().to_string());
+    let tracer = global::tracer(TRACER_NAME);
+    let span = tracer.span_builder(format!("{} {}", req.method(), route))
+        .with_kind(SpanKind::Server)
+        .with_attributes(vec![
+            KeyValue::new("http.request.method", req.method().to_string()),
+            KeyValue::new("http.route", route),
+        ])
+        .start_with_context(&tracer, &parent);
+    let cx = parent.with_span(span);
+
+    let result = next.call(req).with_context(cx.clone()).await;
+    let span = cx.span();
+    match &result {
+        Ok(response) => {
+            let status = response.status();
+            span.set_attribute(KeyValue::new("http.response.status_code", status.as_u16() as i64));
+            if status.is_server_error() {
+                span.set_status(Status::error(status.to_string()));
+            }
+        }
+        Err(e) => span.set_status(Status::error(e.to_string())),
+    }
+    result
+}
+
+struct HeaderExtractor<'a>(&'a HeaderMap);
+
+impl Extractor for HeaderExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|v| v.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(|k| k.as_str()).collect()
+    }
+}
+
+struct HeaderInjector<'a>(&'a mut reqwest::header::HeaderMap);
+
+impl Injector for HeaderInjector<'_> {
+    fn set(&mut self, key: &str, value: String) {
+        if let (Ok(name), Ok(value)) = (
+            reqwest::header::HeaderName::from_bytes(key.as_bytes()),
+            reqwest::header::HeaderValue::from_str(&value),
+        ) {
+            self.0.insert(name, value);
+        }
+    }
+}
@@ -0,0 +1,160 @@
+// Mutual TLS - optional HTTPS listeners and client certificates
+//
+// TLS is off unless TLS_CERT_PATH and TLS_KEY_PATH name a PEM certificate
+// chain and private key; the service then serves HTTPS only. TLS_CLIENT_CA_PATH
+// additionally asks every client for a certificate and verifies it against
+// that CA. Clients without one still complete the handshake so that probes can
+// reach /health; each service decides which routes need a certificate, using
+// the `PeerCertificate` that `on_connect` records for the connection.
+//
+// Outbound, `http_client` trusts TLS_CA_PATH (e.g. a local test CA) on top of
+// the usual roots and presents TLS_CLIENT_CERT_PATH / TLS_CLIENT_KEY_PATH.
+
+use actix_tls::accept::rustls_0_23::TlsStream;
+use actix_web::dev::Extensions;
+use actix_web::rt::net::TcpStream;
+use actix_web::HttpRequest;
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::server::WebPkiClientVerifier;
+use rustls::{RootCertStore, ServerConfig};
+use std::any::Any;
+use std::io;
+use std::sync::Arc;
+use std::time::Duration;
+use log::info;
+use x509_parser::extensions::GeneralName;
+use x509_parser::prelude::{FromDer, X509Certificate};
+
+/// Listener certificate, key and (optionally) client CA, from the environment
+pub struct TlsSettings {
+    pub cert_path: String,
+    pub key_path: String,
+    pub client_ca_path: Option<String>, // Set to verify client certificates
+}
+
+/// Names from the verified client certificate of a connection
+#[derive(Debug, Clone)]
+pub struct PeerCertificate {
+    pub names: Vec<String>, // Subject CN, then DNS subject alternative names
+}
+
+impl TlsSettings {
+    /// `None` (plain HTTP) unless TLS_CERT_PATH and TLS_KEY_PATH are both set
+    pub fn from_env() -> Option<Self> {
+        let cert_path = std::env::var("TLS_CERT_PATH").ok()?;
+        let key_path = std::env::var("TLS_KEY_PATH").ok()?;
+        Some(TlsSettings {
+            cert_path,
+            key_path,
+            client_ca_path: std::env::var("TLS_CLIENT_CA_PATH").ok(),
+        })
+    }
+
+    /// Whether clients are asked for certificates
+    pub fn verifies_clients(&self) -> bool {
+        self.client_ca_path.is_some()
+    }
+
+    /// rustls configuration for `HttpServer::bind_rustls_0_23`
+    pub fn server_config(&self) -> io::Result<ServerConfig> {
+        let provider = Arc::new(rustls::crypto::ring::default_provider());
+        let certs = load_certs(&self.cert_path)?;
+        let key = PrivateKeyDer::from_pem_file(&self.key_path)
+            .map_err(|e| invalid(&self.key_path, e))?;
+
+        let builder = ServerConfig::builder_with_provider(provider.clone())
+            .with_safe_default_protocol_versions()
+            .map_err(io::Error::other)?;
+        let builder = match &self.client_ca_path {
+            Some(ca_path) => {
+                let verifier = WebPkiClientVerifier::builder_with_provider(Arc::new(load_roots(ca_path)?), provider)
+                    .allow_unauthenticated()
+                    .build()
+                    .map_err(|e| invalid(ca_path, e))?;
+                info!("🔒 TLS enabled, verifying client certificates against {}", ca_path);
+                builder.with_client_cert_verifier(verifier)
+            }
+            None => {
+                info!("🔒 TLS enabled");
+                builder.with_no_client_auth()
+            }
+        };
+
+        builder.with_single_cert(certs, key).map_err(|e| invalid(&self.cert_path, e))
+    }
+}
+
+/// Record the client certificate of a TLS connection (for `HttpServer::on_connect`)
+pub fn on_connect(conn: &dyn Any, ext: &mut Extensions) {
+    let Some(stream) = conn.downcast_ref::<TlsStream<TcpStream>>() else { return };
+    let (_, session) = stream.get_ref();
+    if let Some(cert) = session.peer_certificates().and_then(|chain| chain.first()) {
+        ext.insert(PeerCertificate { names: certificate_names(cert) });
+    }
+}
+
+/// The verified client certificate of the request's connection, if any
+pub fn peer_certificate(req: &HttpRequest) -> Option<PeerCertificate> {
+    req.conn_data::<PeerCertificate>().cloned()
+}
+
+/// HTTP client trusting TLS_CA_PATH and presenting the TLS_CLIENT_CERT_PATH certificate
+pub fn http_client(timeout: Duration) -> io::Result<reqwest::Client> {
+    let mut builder = reqwest::Client::builder().timeout(timeout).use_rustls_tls();
+
+    if let Ok(ca_path) = std::env::var("TLS_CA_PATH") {
+        let pem = std::fs::read(&ca_path).map_err(|e| invalid(&ca_path, e))?;
+        let ca = reqwest::Certificate::from_pem(&pem).map_err(|e| invalid(&ca_path, e))?;
+        builder = builder.add_root_certificate(ca);
+    }
+
+    if let (Ok(cert_path), Ok(key_path)) = (std::env::var("TLS_CLIENT_CERT_PATH"), std::env::var("TLS_CLIENT_KEY_PATH")) {
+        let mut pem = std::fs::read(&cert_path).map_err(|e| invalid(&cert_path, e))?;
+        pem.extend(std::fs::read(&key_path).map_err(|e| invalid(&key_path, e))?);
+        let identity = reqwest::Identity::from_pem(&pem).map_err(|e| invalid(&cert_path, e))?;
+        builder = builder.identity(identity);
+    }
+
+    builder.build().map_err(io::Error::other)
+}
+
+fn certificate_names(der: &CertificateDer<'_>) -> Vec<String> {
+    let Ok((_, cert)) = X509Certificate::from_der(der.as_ref()) else { return Vec::new() };
+    let mut names: Vec<String> = cert.subject()
+        .iter_common_name()
+        .filter_map(|cn| cn.as_str().ok().map(String::from))
+        .collect();
+    if let Ok(Some(san)) = cert.subject_alternative_name() {
+        for name in &san.value.general_names {
+            if let GeneralName::DNSName(dns) = name {
+                if !names.iter().any(|n| n == dns) {
+                    names.push(dns.to_string());
+                }
+            }
+        }
+    }
+    names
+}
+
+fn load_certs(path: &str) -> io::Result<Vec<CertificateDer<'static>>> {
+    let certs = CertificateDer::pem_file_iter(path)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| invalid(path, e))?;
+    if certs.is_empty() {
+        return Err(invalid(path, "no certificates found"));
+    }
+    Ok(certs)
+}
+
+fn load_roots(path: &str) -> io::Result<RootCertStore> {
+    let mut roots = RootCertStore::empty();
+    for cert in load_certs(path)? {
+        roots.add(cert).map_err(|e| invalid(path, e))?;
+    }
+    Ok(roots)
+}
+
+fn invalid(path: &str, e: impl std::fmt::Display) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("{}: {}", path, e))
+}
@@ -5,21 +5,41 @@
 //   POST /execute      - Execute pre-compiled Rust
 //   POST /cargo_check  - Check Rust code without executing
 //   POST /clippy       - Run Clippy lints
+//   POST /mcp          - The same operations as MCP tools (JSON-RPC)
 //   GET  /health       - Health check
+//   GET  /ready        - Readiness: cargo, rustc and clippy run and the scratch directory is writable
+// Run with --stdio to serve MCP over stdin/stdout instead of HTTP.
 
 use actix_web::{web, App, HttpServer, HttpResponse, middleware};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
 use std::process::Command;
 use std::fs;
 use uuid::Uuid;
 use log::{info, error};
+use mcp_common::auth::{require_assertion, AssertionVerifier};
+use mcp_common::health;
+use mcp_common::mcp::{self, McpServer};
+use mcp_common::run::{self, RunInput, RunOutput};
+use mcp_common::telemetry::{self, KeyValue};
+use mcp_common::tls::{self, TlsSettings};
 
 // ─── Request/Response Types ───────────────────────────────────────────────────
 
-#[derive(Deserialize)]
+#[derive(Deserialize, JsonSchema)]
 pub struct CompileRequest {
-    pub source: String,           // Rust source code
-    pub input_data: Option<String>, // Optional stdin input
+    /// Rust source code (src/main.rs)
+    pub source: String,
+    /// Optional stdin input
+    pub input_data: Option<String>,
+    /// Files to create in the program's working directory, by file name; shared by every case
+    #[serde(default)]
+    pub files: BTreeMap<String, String>,
+    /// Test cases to run the program on, instead of a single run with input_data
+    #[serde(default)]
+    pub cases: Vec<RunInput>,
 }
 
 #[derive(Serialize)]
@@ -28,10 +48,13 @@ pub struct CompileResponse {
     pub output: Option<String>,   // Execution stdout
     pub compile_log: Option<String>,
     pub error: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub cases: Vec<RunOutput>,    // One per case, in order
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, JsonSchema)]
 pub struct CheckRequest {
+    /// Rust source code (src/main.rs)
     pub source: String,
 }
 
@@ -46,14 +69,18 @@ pub struct CheckResponse {
 
 /// Compile Rust source and execute it, returning stdout output
 async fn compile(
-    body: web::Json<CompileRequest>,
-) -> HttpResponse {
+    body: CompileRequest,
+) -> Result<CompileResponse, CompileResponse> {
     let job_id = Uuid::new_v4().to_string();
     let work_dir = format!("/tmp/rust_{}", job_id);
     let src_dir = format!("{}/src", work_dir);
 
     info!("Compiling Rust job: {}", job_id);
 
+    if let Err(e) = run::check_cases(&body.files, &body.cases) {
+        return error_response(&e);
+    }
+
     // Create Cargo project structure
     if let Err(e) = fs::create_dir_all(&src_dir) {
         return error_response(&format!("Failed to create work dir: {}", e));
@@ -101,11 +128,13 @@ edition = "2021"
     }
 
     // Build with cargo
-    let build_output = Command::new("cargo")
-        .args(["build", "--release"])
-        .current_dir(&work_dir)
-        .env("CARGO_HOME", "/home/mcpuser/.cargo")  // Shared cargo cache
-        .output();
+    let build_output = telemetry::in_span("cargo build", vec![KeyValue::new("rust.job_id", job_id.clone())], async {
+        Command::new("cargo")
+            .args(["build", "--release"])
+            .current_dir(&work_dir)
+            .env("CARGO_HOME", "/home/mcpuser/.cargo")  // Shared cargo cache
+            .output()
+    }).await;
 
     match build_output {
         Ok(output) => {
@@ -118,65 +147,64 @@ edition = "2021"
             if !output.status.success() {
                 error!("Rust compile failed:\n{}", compile_log);
                 cleanup(&work_dir);
-                return HttpResponse::Ok().json(CompileResponse {
+                return Ok(CompileResponse {
                     success: false,
                     output: None,
                     compile_log: Some(compile_log),
                     error: Some("Rust compilation failed".to_string()),
+                    cases: vec![],
                 });
             }
 
             info!("Rust compiled successfully, executing...");
 
-            // Execute the compiled binary
+            // One run with input_data, or one per test case
             let binary_path = format!("{}/target/release/modernized", work_dir);
-            let exec_result = if let Some(input) = &body.input_data {
-                use std::process::Stdio;
-                use std::io::Write;
-
-                let mut child = Command::new(&binary_path)
-                    .stdin(Stdio::piped())
-                    .stdout(Stdio::piped())
-                    .stderr(Stdio::piped())
-                    .spawn()
-                    .map_err(|e| e.to_string());
-
-                match child {
-                    Ok(mut c) => {
-                        if let Some(mut stdin) = c.stdin.take() {
-                            let _ = stdin.write_all(input.as_bytes());
-                        }
-                        c.wait_with_output()
-                            .map(|o| String::from_utf8_lossy(&o.stdout).to_string())
-                            .map_err(|e| e.to_string())
-                    }
-                    Err(e) => Err(e),
-                }
-            } else {
-                Command::new(&binary_path)
-                    .output()
-                    .map(|o| String::from_utf8_lossy(&o.stdout).to_string())
-                    .map_err(|e| e.to_string())
-            };
-
+            let single = body.cases.is_empty();
+            let cases = run::runs(body.input_data, body.files, body.cases);
+            let attributes = vec![
+                KeyValue::new("rust.job_id", job_id.clone()),
+                KeyValue::new("rust.cases", cases.len() as i64),
+            ];
+            let outputs = telemetry::in_span("rust execute", attributes,
+                run::run_cases(Path::new(&binary_path), Path::new(&work_dir), &cases)).await;
             cleanup(&work_dir);
 
-            match exec_result {
-                Ok(stdout) => {
-                    info!("Rust output: {}", stdout.trim());
-                    HttpResponse::Ok().json(CompileResponse {
+            if !single {
+                info!("Rust ran {} cases, {} failed", outputs.len(), outputs.iter().filter(|o| !o.success).count());
+                return Ok(CompileResponse {
+                    success: true,
+                    output: None,
+                    compile_log: Some(compile_log),
+                    error: None,
+                    cases: outputs,
+                });
+            }
+            match outputs.into_iter().next() {
+                Some(RunOutput { success: true, output, .. }) => {
+                    info!("Rust output: {}", output.as_deref().unwrap_or("").trim());
+                    Ok(CompileResponse {
                         success: true,
-                        output: Some(stdout),
+                        output,
                         compile_log: Some(compile_log),
                         error: None,
+                        cases: vec![],
+                    })
+                }
+                failed => {
+                    // Whatever it printed before failing helps to see why
+                    let (output, error) = failed.map_or((None, None), |o| {
+                        let error = o.failure();
+                        (o.output, error)
+                    });
+                    Err(CompileResponse {
+                        success: false,
+                        output,
+                        compile_log: Some(compile_log),
+                        error,
+                        cases: vec![],
                     })
                 }
-                Err(e) => HttpResponse::InternalServerError().json(CompileResponse {
-                    success: false,
-                    output: None,
-                    compile_log: Some(compile_log),
-                    error: Some(format!("Execution failed: {}", e)),
-                }),
             }
         }
         Err(e) => {
@@ -188,8 +216,8 @@ edition = "2021"
 
 /// Check Rust code without executing (cargo check)
 async fn cargo_check(
-    body: web::Json<CheckRequest>,
-) -> HttpResponse {
+    body: CheckRequest,
+) -> Result<CheckResponse, CheckResponse> {
     let job_id = Uuid::new_v4().to_string();
     let work_dir = format!("/tmp/rust_check_{}", job_id);
     let src_dir = format!("{}/src", work_dir);
@@ -219,9 +247,9 @@ async fn cargo_check(
                 .filter(|l| l.contains("warning"))
                 .map(String::from)
                 .collect();
-            HttpResponse::Ok().json(CheckResponse { success, warnings, errors })
+            Ok(CheckResponse { success, warnings, errors })
         }
-        Err(e) => HttpResponse::InternalServerError().json(CheckResponse {
+        Err(e) => Err(CheckResponse {
             success: false,
             errors: vec![e.to_string()],
             warnings: vec![],
@@ -231,8 +259,8 @@ async fn cargo_check(
 
 /// Run Clippy lints
 async fn clippy(
-    body: web::Json<CheckRequest>,
-) -> HttpResponse {
+    body: CheckRequest,
+) -> Result<serde_json::Value, serde_json::Value> {
     let job_id = Uuid::new_v4().to_string();
     let work_dir = format!("/tmp/rust_clippy_{}", job_id);
     let src_dir = format!("{}/src", work_dir);
@@ -254,12 +282,12 @@ async fn clippy(
         Ok(output) => {
             let stderr = String::from_utf8_lossy(&output.stderr).to_string();
             let success = output.status.success();
-            HttpResponse::Ok().json(serde_json::json!({
+            Ok(serde_json::json!({
                 "success": success,
                 "output": stderr
             }))
         }
-        Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({
+        Err(e) => Err(serde_json::json!({
             "success": false,
             "error": e.to_string()
         })),
@@ -283,38 +311,81 @@ async fn health() -> HttpResponse {
     }))
 }
 
+/// GET /ready - compile, cargo_check and clippy need the toolchain and scratch space
+async fn ready() -> HttpResponse {
+    let checks = vec![
+        health::command("cargo", "cargo", &["--version"]).await,
+        health::command("rustc", "rustc", &["--version"]).await,
+        health::command("clippy", "cargo", &["clippy", "--version"]).await,
+        health::writable_dir("scratch", std::path::Path::new("/tmp")),
+    ];
+    health::readiness("rust-mcp", "1.0.0", checks)
+}
+
 fn cleanup(dir: &str) {
     let _ = fs::remove_dir_all(dir);
 }
 
-fn error_response(msg: &str) -> HttpResponse {
-    HttpResponse::InternalServerError().json(CompileResponse {
+fn error_response(msg: &str) -> Result<CompileResponse, CompileResponse> {
+    Err(CompileResponse {
         success: false,
         output: None,
         compile_log: None,
         error: Some(msg.to_string()),
+        cases: vec![],
     })
 }
 
+// ─── MCP Tools ────────────────────────────────────────────────────────────────
+
+fn mcp_server() -> McpServer {
+    McpServer::new("rust-mcp", "1.0.0")
+        .tool("compile", "Compile Rust source with Cargo and execute it", compile)
+        .tool("execute", "Execute Rust source (compiles first)", compile)
+        .tool("cargo_check", "Check Rust code without executing", cargo_check)
+        .tool("clippy", "Run Clippy lints with warnings denied", clippy)
+}
+
 // ─── Main ─────────────────────────────────────────────────────────────────────
 
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
     env_logger::init_from_env(env_logger::Env::new().default_filter_or("info"));
+    let telemetry = telemetry::init("rust-mcp");
+
+    let server = web::Data::new(mcp_server());
+
+    if std::env::args().any(|a| a == "--stdio") {
+        let result = server.serve_stdio().await;
+        telemetry.shutdown();
+        return result;
+    }
 
     let bind_addr = std::env::var("BIND_ADDR").unwrap_or("0.0.0.0:8084".to_string());
     info!("🦀 Rust MCP Service starting on {}", bind_addr);
 
-    HttpServer::new(|| {
+    // Every operation must carry a gateway assertion addressed to this server
+    let verifier = web::Data::new(AssertionVerifier::from_env("rust_mcp")?);
+    let tls = TlsSettings::from_env();
+
+    let http = HttpServer::new(move || {
         App::new()
+            .app_data(server.clone())
+            .app_data(verifier.clone())
+            .wrap(middleware::from_fn(require_assertion))
+            .wrap(middleware::from_fn(telemetry::trace_requests))
             .wrap(middleware::Logger::default())
-            .route("/compile", web::post().to(compile))
-            .route("/execute", web::post().to(compile))
-            .route("/cargo_check", web::post().to(cargo_check))
-            .route("/clippy", web::post().to(clippy))
             .route("/health", web::get().to(health))
+            .route("/ready", web::get().to(ready))
+            .configure(mcp::configure)
     })
-    .bind(&bind_addr)?
-    .run()
-    .await
+    .on_connect(tls::on_connect);
+
+    let http = match &tls {
+        Some(tls) => http.bind_rustls_0_23(&bind_addr, tls.server_config()?)?,
+        None => http.bind(&bind_addr)?,
+    };
+    let result = http.run().await;
+    telemetry.shutdown();
+    result
 }
@@ -6,33 +6,50 @@
 //   POST /save_output        - Save modernized Rust code to S3
 //   POST /generate_presigned_url - Generate pre-signed URL for download
 //   POST /list_objects       - List objects in bucket/prefix
+//   POST /mcp                - The same operations as MCP tools (JSON-RPC)
+//   GET  /health             - Health check
+//   GET  /ready              - Readiness: AWS credentials can reach the pipeline bucket
+// Run with --stdio to serve MCP over stdin/stdout instead of HTTP.
 
 use actix_web::{web, App, HttpServer, HttpResponse, middleware};
 use aws_config::BehaviorVersion;
 use aws_sdk_s3::Client;
 use aws_sdk_s3::presigning::PresigningConfig;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::time::Duration;
 use log::{info, error};
+use mcp_common::auth::{require_assertion, AssertionVerifier};
+use mcp_common::health::{self, Check};
+use mcp_common::mcp::{self, McpServer};
+use mcp_common::telemetry::{self, KeyValue};
+use mcp_common::tls::{self, TlsSettings};
 
 // ─── Request/Response Types ───────────────────────────────────────────────────
 
-#[derive(Deserialize)]
+#[derive(Deserialize, JsonSchema)]
 pub struct FetchRequest {
+    /// S3 bucket name
     pub bucket: String,
+    /// Object key within the bucket
     pub key: String,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, JsonSchema)]
 pub struct SaveRequest {
+    /// S3 bucket name
     pub bucket: String,
+    /// Object key to write
     pub key: String,
+    /// Text content to store
     pub content: String,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, JsonSchema)]
 pub struct ListRequest {
+    /// S3 bucket name
     pub bucket: String,
+    /// Only list keys starting with this prefix
     pub prefix: Option<String>,
 }
 
@@ -67,6 +84,7 @@ pub struct ListResponse {
 
 pub struct AppState {
     pub s3_client: Client,
+    pub bucket: String, // The pipeline bucket /ready checks access to
 }
 
 // ─── Handlers ─────────────────────────────────────────────────────────────────
@@ -74,15 +92,15 @@ pub struct AppState {
 /// Fetch COBOL source file from S3
 async fn fetch_source(
     state: web::Data<AppState>,
-    body: web::Json<FetchRequest>,
-) -> HttpResponse {
+    body: FetchRequest,
+) -> Result<FetchResponse, FetchResponse> {
     info!("Fetching source: s3://{}/{}", body.bucket, body.key);
 
     match get_s3_object(&state.s3_client, &body.bucket, &body.key).await {
         Ok(content) => {
             let size = content.len();
             info!("Fetched {} bytes from s3://{}/{}", size, body.bucket, body.key);
-            HttpResponse::Ok().json(FetchResponse {
+            Ok(FetchResponse {
                 success: true,
                 bucket: body.bucket.clone(),
                 key: body.key.clone(),
@@ -93,7 +111,7 @@ async fn fetch_source(
         }
         Err(e) => {
             error!("Failed to fetch s3://{}/{}: {}", body.bucket, body.key, e);
-            HttpResponse::InternalServerError().json(FetchResponse {
+            Err(FetchResponse {
                 success: false,
                 bucket: body.bucket.clone(),
                 key: body.key.clone(),
@@ -108,12 +126,12 @@ async fn fetch_source(
 /// Fetch test data from S3
 async fn fetch_data(
     state: web::Data<AppState>,
-    body: web::Json<FetchRequest>,
-) -> HttpResponse {
+    body: FetchRequest,
+) -> Result<FetchResponse, FetchResponse> {
     info!("Fetching data: s3://{}/{}", body.bucket, body.key);
 
     match get_s3_object(&state.s3_client, &body.bucket, &body.key).await {
-        Ok(content) => HttpResponse::Ok().json(FetchResponse {
+        Ok(content) => Ok(FetchResponse {
             success: true,
             bucket: body.bucket.clone(),
             key: body.key.clone(),
@@ -121,7 +139,7 @@ async fn fetch_data(
             size: Some(content.len()),
             error: None,
         }),
-        Err(e) => HttpResponse::InternalServerError().json(FetchResponse {
+        Err(e) => Err(FetchResponse {
             success: false,
             bucket: body.bucket.clone(),
             key: body.key.clone(),
@@ -135,19 +153,19 @@ async fn fetch_data(
 /// Save modernized Rust code to S3 and return pre-signed URL
 async fn save_output(
     state: web::Data<AppState>,
-    body: web::Json<SaveRequest>,
-) -> HttpResponse {
+    body: SaveRequest,
+) -> Result<SaveResponse, SaveResponse> {
     info!("Saving output: s3://{}/{}", body.bucket, body.key);
 
     // Upload to S3
-    let put_result = state.s3_client
+    let put = state.s3_client
         .put_object()
         .bucket(&body.bucket)
         .key(&body.key)
         .body(body.content.as_bytes().to_vec().into())
         .content_type("text/plain")
-        .send()
-        .await;
+        .send();
+    let put_result = telemetry::in_span("s3 PutObject", s3_attributes(&body.bucket, Some(&body.key)), put).await;
 
     match put_result {
         Ok(_) => {
@@ -156,7 +174,7 @@ async fn save_output(
             // Generate pre-signed URL for download (1 hour expiry)
             let presigned_url = generate_presigned(&state.s3_client, &body.bucket, &body.key).await;
 
-            HttpResponse::Ok().json(SaveResponse {
+            Ok(SaveResponse {
                 success: true,
                 bucket: body.bucket.clone(),
                 key: body.key.clone(),
@@ -166,7 +184,7 @@ async fn save_output(
         }
         Err(e) => {
             error!("Failed to save to s3://{}/{}: {}", body.bucket, body.key, e);
-            HttpResponse::InternalServerError().json(SaveResponse {
+            Err(SaveResponse {
                 success: false,
                 bucket: body.bucket.clone(),
                 key: body.key.clone(),
@@ -180,17 +198,17 @@ async fn save_output(
 /// Generate pre-signed URL for an existing S3 object
 async fn generate_presigned_url(
     state: web::Data<AppState>,
-    body: web::Json<FetchRequest>,
-) -> HttpResponse {
+    body: FetchRequest,
+) -> Result<serde_json::Value, serde_json::Value> {
     let url = generate_presigned(&state.s3_client, &body.bucket, &body.key).await;
 
     match url {
-        Some(u) => HttpResponse::Ok().json(serde_json::json!({
+        Some(u) => Ok(serde_json::json!({
             "success": true,
             "presigned_url": u,
             "expires_in": 3600
         })),
-        None => HttpResponse::InternalServerError().json(serde_json::json!({
+        None => Err(serde_json::json!({
             "success": false,
             "error": "Failed to generate pre-signed URL"
         })),
@@ -200,37 +218,55 @@ async fn generate_presigned_url(
 /// List objects in S3 bucket/prefix
 async fn list_objects(
     state: web::Data<AppState>,
-    body: web::Json<ListRequest>,
-) -> HttpResponse {
-    let mut req = state.s3_client
-        .list_objects_v2()
-        .bucket(&body.bucket);
-
-    if let Some(prefix) = &body.prefix {
-        req = req.prefix(prefix);
-    }
-
-    match req.send().await {
-        Ok(output) => {
-            let objects: Vec<String> = output.contents()
-                .iter()
-                .filter_map(|obj| obj.key().map(String::from))
-                .collect();
-
-            HttpResponse::Ok().json(ListResponse {
-                success: true,
+    body: ListRequest,
+) -> Result<ListResponse, ListResponse> {
+    // ListObjectsV2 answers at most 1000 keys per page; follow the continuation tokens
+    let mut objects: Vec<String> = Vec::new();
+    let mut continuation: Option<String> = None;
+    loop {
+        let req = state.s3_client
+            .list_objects_v2()
+            .bucket(&body.bucket)
+            .set_prefix(body.prefix.clone())
+            .set_continuation_token(continuation.take());
+
+        let attributes = s3_attributes(&body.bucket, body.prefix.as_deref());
+        match telemetry::in_span("s3 ListObjectsV2", attributes, req.send()).await {
+            Ok(output) => {
+                objects.extend(output.contents()
+                    .iter()
+                    .filter_map(|obj| obj.key().map(String::from)));
+                continuation = output.next_continuation_token().map(String::from);
+                if continuation.is_none() {
+                    break;
+                }
+            }
+            Err(e) => return Err(ListResponse {
+                success: false,
                 bucket: body.bucket.clone(),
-                objects,
-                error: None,
-            })
+                objects: vec![],
+                error: Some(e.to_string()),
+            }),
         }
-        Err(e) => HttpResponse::InternalServerError().json(ListResponse {
-            success: false,
-            bucket: body.bucket.clone(),
-            objects: vec![],
-            error: Some(e.to_string()),
-        }),
     }
+
+    Ok(ListResponse {
+        success: true,
+        bucket: body.bucket.clone(),
+        objects,
+        error: None,
+    })
+}
+
+/// GET /ready - HeadBucket on the pipeline bucket exercises credentials, region and permissions
+async fn ready(state: web::Data<AppState>) -> HttpResponse {
+    let head = state.s3_client.head_bucket().bucket(&state.bucket).send();
+    let check = match tokio::time::timeout(health::CHECK_TIMEOUT, head).await {
+        Ok(Ok(_)) => Check::pass("bucket", format!("s3://{} is accessible", state.bucket)),
+        Ok(Err(e)) => Check::fail("bucket", format!("S3 HeadBucket on {} failed: {}", state.bucket, e)),
+        Err(_) => Check::fail("bucket", format!("S3 HeadBucket on {} timed out", state.bucket)),
+    };
+    health::readiness("s3-mcp", "1.0.0", vec![check])
 }
 
 async fn health() -> HttpResponse {
@@ -241,9 +277,47 @@ async fn health() -> HttpResponse {
     }))
 }
 
+// ─── MCP Tools ────────────────────────────────────────────────────────────────
+
+fn mcp_server(state: &web::Data<AppState>) -> McpServer {
+    McpServer::new("s3-mcp", "1.0.0")
+        .tool("fetch_source", "Fetch COBOL source file from S3", {
+            let state = state.clone();
+            move |req| fetch_source(state.clone(), req)
+        })
+        .tool("fetch_data", "Fetch test data from S3", {
+            let state = state.clone();
+            move |req| fetch_data(state.clone(), req)
+        })
+        .tool("save_output", "Save modernized Rust code to S3 and return a pre-signed URL", {
+            let state = state.clone();
+            move |req| save_output(state.clone(), req)
+        })
+        .tool("generate_presigned_url", "Generate a 1-hour pre-signed download URL", {
+            let state = state.clone();
+            move |req| generate_presigned_url(state.clone(), req)
+        })
+        .tool("list_objects", "List objects in a bucket, optionally under a prefix", {
+            let state = state.clone();
+            move |req| list_objects(state.clone(), req)
+        })
+}
+
 // ─── Helpers ──────────────────────────────────────────────────────────────────
 
+fn s3_attributes(bucket: &str, key: Option<&str>) -> Vec<KeyValue> {
+    let mut attributes = vec![KeyValue::new("aws.s3.bucket", bucket.to_string())];
+    if let Some(key) = key {
+        attributes.push(KeyValue::new("aws.s3.key", key.to_string()));
+    }
+    attributes
+}
+
 async fn get_s3_object(client: &Client, bucket: &str, key: &str) -> Result<String, String> {
+    telemetry::in_span("s3 GetObject", s3_attributes(bucket, Some(key)), read_s3_object(client, bucket, key)).await
+}
+
+async fn read_s3_object(client: &Client, bucket: &str, key: &str) -> Result<String, String> {
     let response = client
         .get_object()
         .bucket(bucket)
@@ -279,6 +353,7 @@ async fn generate_presigned(client: &Client, bucket: &str, key: &str) -> Option<
 #[actix_web::main]
 async fn main() -> std::io::Result<()> {
     env_logger::init_from_env(env_logger::Env::new().default_filter_or("info"));
+    let telemetry = telemetry::init("s3-mcp");
 
     // Load AWS config from environment
     let aws_config = aws_config::defaults(BehaviorVersion::latest())
@@ -286,23 +361,43 @@ async fn main() -> std::io::Result<()> {
         .await;
 
     let s3_client = Client::new(&aws_config);
-    let state = web::Data::new(AppState { s3_client });
+    let bucket = std::env::var("S3_BUCKET")
+        .unwrap_or("mainframe-refactor-lab-venkatnagala".to_string());
+    let state = web::Data::new(AppState { s3_client, bucket });
+    let server = web::Data::new(mcp_server(&state));
+
+    if std::env::args().any(|a| a == "--stdio") {
+        let result = server.serve_stdio().await;
+        telemetry.shutdown();
+        return result;
+    }
 
     let bind_addr = std::env::var("BIND_ADDR").unwrap_or("0.0.0.0:8081".to_string());
     info!("🪣 S3 MCP Service starting on {}", bind_addr);
 
-    HttpServer::new(move || {
+    // Every operation must carry a gateway assertion addressed to this server
+    let verifier = web::Data::new(AssertionVerifier::from_env("s3_mcp")?);
+    let tls = TlsSettings::from_env();
+
+    let http = HttpServer::new(move || {
         App::new()
             .app_data(state.clone())
+            .app_data(server.clone())
+            .app_data(verifier.clone())
+            .wrap(middleware::from_fn(require_assertion))
+            .wrap(middleware::from_fn(telemetry::trace_requests))
             .wrap(middleware::Logger::default())
-            .route("/fetch_source", web::post().to(fetch_source))
-            .route("/fetch_data", web::post().to(fetch_data))
-            .route("/save_output", web::post().to(save_output))
-            .route("/generate_presigned_url", web::post().to(generate_presigned_url))
-            .route("/list_objects", web::post().to(list_objects))
             .route("/health", web::get().to(health))
+            .route("/ready", web::get().to(ready))
+            .configure(mcp::configure)
     })
-    .bind(&bind_addr)?
-    .run()
-    .await
+    .on_connect(tls::on_connect);
+
+    let http = match &tls {
+        Some(tls) => http.bind_rustls_0_23(&bind_addr, tls.server_config()?)?,
+        None => http.bind(&bind_addr)?,
+    };
+    let result = http.run().await;
+    telemetry.shutdown();
+    result
 }